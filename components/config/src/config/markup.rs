@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::{path::Path, sync::Arc};
 
 use serde_derive::{Deserialize, Serialize};
@@ -8,6 +9,10 @@ use syntect::{
 };
 
 use errors::{bail, Result};
+use utils::templates::{
+    set_shortcode_arg_types, set_shortcodes_decoding_entities, set_shortcodes_with_html_output,
+    ShortcodeArg, ShortcodeDefinition,
+};
 
 use crate::highlighting::{CLASS_STYLE, THEME_SET};
 
@@ -28,6 +33,131 @@ impl Default for ThemeCss {
     }
 }
 
+/// The direction of the text of the content
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextDirection {
+    Ltr,
+    Rtl,
+    /// Lets the browser decide, and sets the direction of paragraphs starting with right-to-left text
+    Auto,
+}
+
+impl TextDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TextDirection::Ltr => "ltr",
+            TextDirection::Rtl => "rtl",
+            TextDirection::Auto => "auto",
+        }
+    }
+}
+
+/// What to do when a footnote is defined more than once in the same content
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateFootnotes {
+    /// Prints a warning and only keeps the first definition
+    Warn,
+    /// Fails the rendering
+    Error,
+}
+
+/// Where the last modified note is added to the content
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LastModifiedPosition {
+    Start,
+    End,
+}
+
+/// What to do with a front matter block found at the start of the content of a page or section,
+/// typically from a file with two front matters
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeakedFrontMatter {
+    /// Prints a warning and renders the block as is
+    Warn,
+    /// Prints a warning and removes the block from the content
+    Strip,
+}
+
+/// What to do with links and images without a URL, like `[text]()` or `![]()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmptyLinks {
+    /// Links without a URL fail the rendering, images are rendered as is
+    Error,
+    /// Prints a warning and removes them, keeping the text of links
+    Drop,
+    /// Renders them as is
+    Keep,
+}
+
+/// What to do with headings without any text, like `## ` or `## {#}`. Headings with only
+/// symbols or an image are always kept, with a generated id
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmptyHeadings {
+    /// Prints a warning and removes them
+    Skip,
+    /// Renders them with a generated id, `heading`, `heading-1`...
+    Fallback,
+}
+
+/// How the declarations of the `style` attributes in the HTML of the content are filtered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StyleAttributes {
+    /// They are left as written
+    Keep,
+    /// Declarations that can run code, like `expression()`, and `url()` with external URLs
+    /// are removed
+    Strict,
+    /// Like `Strict` but `url()` with http(s) URLs are kept
+    Lenient,
+}
+
+/// How the attribution of a blockquote, a last line starting with `—` or `--`, is rendered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockquoteAttribution {
+    /// In a `<cite>` element
+    Cite,
+    /// In a `<footer>` element
+    Footer,
+}
+
+/// How the quotes right after a digit, like `6"` or `5'`, are rendered with smart punctuation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MeasurementQuotes {
+    /// As straight quotes, as written
+    Straight,
+    /// As the prime `′` and double prime `″` marks
+    Primes,
+}
+
+/// How the items of lists are rendered, whatever the blank lines between them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListSpacing {
+    /// Without `<p>` around their content
+    Tight,
+    /// With their content in `<p>`
+    Loose,
+}
+
+/// Where the caption of an image alone in its paragraph comes from, when it's rendered as a figure
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageCaptions {
+    /// The title of the image, eg `![alt](image.png "Caption")`
+    Title,
+    /// The alt text of the image
+    Alt,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Markdown {
@@ -36,8 +166,14 @@ pub struct Markdown {
     /// Which themes to use for code highlighting. See Readme for supported themes
     /// Defaults to "base16-ocean-dark"
     pub highlight_theme: String,
+    /// Whether to use the default theme instead of erroring if `highlight_theme` cannot be found.
+    /// Defaults to false
+    pub highlight_theme_fallback: bool,
     /// Generate CSS files for Themes out of syntect
     pub highlight_themes_css: Vec<ThemeCss>,
+    /// Whether the rules of the `highlight_themes_css` files not used by any page are removed
+    /// from the output when building the site
+    pub purge_highlight_css: bool,
     /// Whether to render emoji aliases (e.g.: :smile: => 😄) in the markdown files
     pub render_emoji: bool,
     /// Whether external links are to be opened in a new tab
@@ -49,6 +185,126 @@ pub struct Markdown {
     pub external_links_no_referrer: bool,
     /// Whether smart punctuation is enabled (changing quotes, dashes, dots etc in their typographic form)
     pub smart_punctuation: bool,
+    /// Whether the text both bold and italic, like `***x***`, is rendered in `<strong><em>`.
+    /// If false, it is only rendered in `<strong>`
+    pub combined_emphasis: bool,
+    /// If set, the quotes right after a digit, like `6"` or `5'`, are taken as measurement marks
+    /// instead of becoming curly quotes with smart punctuation
+    pub measurement_quotes: Option<MeasurementQuotes>,
+    /// A class to add to the `<hr>` elements created from horizontal rules
+    pub horizontal_rule_class: Option<String>,
+    /// Whether to turn email addresses found in text into `mailto:` links
+    pub link_bare_emails: bool,
+    /// Whether to encode the email links created by `link_bare_emails` as HTML entities
+    pub obfuscate_emails: bool,
+    /// If set, rendering content into more bytes of HTML than that is an error
+    pub max_output_size: Option<usize>,
+    /// How many levels of shortcodes found in the output of other shortcodes get expanded.
+    /// Defaults to 0, meaning shortcode output is never scanned for shortcodes
+    pub shortcode_max_depth: usize,
+    /// If set, unknown shortcodes are rendered with this template instead of failing the rendering
+    pub unknown_shortcode_template: Option<String>,
+    /// Whether the whitespace around the output of shortcodes without a body is removed
+    pub trim_inline_shortcodes: bool,
+    /// Whether shortcodes not found with their name are looked up lowercased, eg `YouTube`
+    /// finding `youtube`
+    pub case_insensitive_shortcodes: bool,
+    /// If set, version tags like `v1.2.3` found in text link to this URL followed by the tag
+    pub release_base_url: Option<String>,
+    /// If set, commit hashes found in text link to this URL followed by the hash
+    pub commit_base_url: Option<String>,
+    /// If set, mentions like `@username` found in text link to this URL followed by the username
+    pub mention_base_url: Option<String>,
+    /// Images inside those HTML elements (eg `blockquote`) are never picked as the cover image
+    pub cover_image_ignored_containers: Vec<String>,
+    /// The names of the shortcodes whose string arguments get their HTML entities decoded
+    pub shortcodes_decoding_entities: Vec<String>,
+    /// The names of the markdown shortcodes whose output is HTML to insert as is, instead of
+    /// being rendered as markdown with the rest of the content
+    pub shortcodes_with_html_output: Vec<String>,
+    /// The types the arguments of shortcodes should have and whether they are required, by
+    /// shortcode and argument name
+    pub shortcode_arg_types: HashMap<String, HashMap<String, ShortcodeArg>>,
+    /// Whether task list checkboxes are rendered enabled with an id, so scripts can persist their state
+    pub interactive_task_lists: bool,
+    /// Whether table cells can contain lists and paragraphs, using `<br>` to separate lines
+    pub table_cell_blocks: bool,
+    /// If set, that marker is rendered as a line break, `<br>`, in table cells
+    pub table_cell_line_break: Option<String>,
+    /// Whether code blocks and inline code are left as written instead of being scanned for shortcodes
+    pub protect_code_from_shortcodes: bool,
+    /// Whether the table of contents is also made available serialized as JSON
+    pub toc_json: bool,
+    /// Whether the content is marked up as a schema.org `Article` with microdata attributes
+    pub article_microdata: bool,
+    /// Whether each top level heading and what follows it, until the next heading of the same or
+    /// a higher level, is wrapped in a `<section>` labelled by the heading
+    pub heading_sections: bool,
+    /// Whether the whitespace at the end of the lines of the rendered HTML is removed, except in
+    /// `<pre>` and `<textarea>` elements
+    pub trim_trailing_whitespace: bool,
+    /// Whether consecutive fenced code blocks marked with the same `group=` are rendered as a
+    /// tab group labelled with their languages
+    pub code_tab_groups: bool,
+    /// If set, tables are wrapped in a `<div>` with that class
+    pub table_wrapper_class: Option<String>,
+    /// If set, this HTML is added after every external link, eg an icon
+    pub external_link_marker: Option<String>,
+    /// If set, the text of the URLs written as autolinks, like `<https://example.com>`, longer
+    /// than that many characters is shortened, without the scheme. The link keeps the full URL.
+    pub truncate_autolinks: Option<usize>,
+    /// What to do with footnotes defined more than once
+    pub duplicate_footnotes: DuplicateFootnotes,
+    /// If set, a note with the modification time of the content file formatted with this
+    /// `strftime` pattern is added to the rendered content
+    pub last_modified_note: Option<String>,
+    /// Whether the last modified note goes at the start or the end of the content
+    pub last_modified_position: LastModifiedPosition,
+    /// What to do with a front matter block found at the start of the content
+    pub leaked_front_matter: LeakedFrontMatter,
+    /// What to do with links and images without a URL
+    pub empty_links: EmptyLinks,
+    /// What to do with headings without any text
+    pub empty_headings: EmptyHeadings,
+    /// How the `style` attributes in the HTML of the content are filtered
+    pub style_attributes: StyleAttributes,
+    /// If set, that class is added to the headings getting an anchor link, eg to give them a
+    /// `scroll-margin-top` when the site has a fixed header
+    pub anchor_target_class: Option<String>,
+    /// Whether the ids of the headings are percent-encoded in the `href` of their anchor links,
+    /// eg for ids with non-ASCII characters
+    pub encode_anchor_fragments: bool,
+    /// Headings below that level only get an id, not an anchor link, when anchors are inserted
+    pub anchor_min_level: u32,
+    /// Whether links to local pages written as paths like `/page` get a trailing slash
+    pub trailing_slash_links: bool,
+    /// Whether the headings of the table of contents get the number of words in their section
+    pub heading_word_counts: bool,
+    /// Whether the rendered content comes with the id of each heading and how far in the HTML
+    /// it is, for reading progress indicators
+    pub heading_progress_map: bool,
+    /// Whether line numbers of code blocks are put in a `data-line` attribute of each line
+    /// instead of a table, so they can be shown with CSS without being copied with the code
+    pub copyable_line_numbers: bool,
+    /// If set, the tabs indenting the lines of the content are expanded to spaces, with tab stops
+    /// every that many columns, before it is parsed. Code in fenced code blocks is left as is
+    pub expand_tabs: Option<usize>,
+    /// If set, all lists are rendered that way instead of depending on the blank lines
+    /// between their items
+    pub list_spacing: Option<ListSpacing>,
+    /// If set, the first paragraph of the content that isn't only images gets that class
+    pub lead_paragraph_class: Option<String>,
+    /// Whether ordered lists always start at 1 instead of the number of their first item
+    pub normalize_list_start: bool,
+    /// Whether blockquotes only separated by blank lines are merged into a single one
+    pub merge_adjacent_blockquotes: bool,
+    /// If set, the attribution line ending a blockquote is rendered in that element
+    pub blockquote_attribution: Option<BlockquoteAttribution>,
+    /// If set, images alone in their paragraph are rendered in a `<figure>`, with a caption
+    /// taken from their title or alt text
+    pub image_captions: Option<ImageCaptions>,
+    /// If set, the rendered content is wrapped in a `<div>` with that `dir` attribute
+    pub text_direction: Option<TextDirection>,
     /// A list of directories to search for additional `.sublime-syntax` and `.tmTheme` files in.
     pub extra_syntaxes_and_themes: Vec<String>,
     /// The compiled extra syntaxes into a syntax set
@@ -71,11 +327,23 @@ impl Markdown {
     }
 
     /// Gets an arbitrary theme from the THEME_SET or the extra_theme_set
+    /// Falls back to the default theme if it doesn't exist in either and `highlight_theme_fallback`
+    /// is set, panics otherwise: the themes of the config are validated when loading it
     pub fn get_highlight_theme_by_name(&self, theme_name: &str) -> &Theme {
-        (*self.extra_theme_set)
+        self.find_highlight_theme(theme_name).unwrap_or_else(|| &THEME_SET.themes[theme_name])
+    }
+
+    /// Like `get_highlight_theme_by_name` but returns None instead of panicking
+    pub fn find_highlight_theme(&self, theme_name: &str) -> Option<&Theme> {
+        let theme = (*self.extra_theme_set)
             .as_ref()
             .and_then(|ts| ts.themes.get(theme_name))
-            .unwrap_or_else(|| &THEME_SET.themes[theme_name])
+            .or_else(|| THEME_SET.themes.get(theme_name));
+        if self.highlight_theme_fallback {
+            theme.or_else(|| THEME_SET.themes.get(DEFAULT_HIGHLIGHT_THEME))
+        } else {
+            theme
+        }
     }
 
     /// Attempt to load any extra syntaxes and themes found in the extra_syntaxes_and_themes folders
@@ -124,7 +392,17 @@ impl Markdown {
 
         // Validate that the chosen highlight_theme exists in the loaded highlight theme sets
         if !THEME_SET.themes.contains_key(&self.highlight_theme) {
-            if let Some(extra) = &*self.extra_theme_set {
+            let in_extra = match &*self.extra_theme_set {
+                Some(extra) => extra.themes.contains_key(&self.highlight_theme),
+                None => false,
+            };
+            if !in_extra && self.highlight_theme_fallback {
+                eprintln!(
+                    "Warning: Highlight theme {} not found, falling back to {}",
+                    self.highlight_theme, DEFAULT_HIGHLIGHT_THEME
+                );
+                self.highlight_theme = DEFAULT_HIGHLIGHT_THEME.to_owned();
+            } else if let Some(extra) = &*self.extra_theme_set {
                 if !extra.themes.contains_key(&self.highlight_theme) {
                     bail!(
                         "Highlight theme {} not found in the extra theme set",
@@ -140,14 +418,19 @@ impl Markdown {
         // Validate that all exported highlight themes exist as well
         for theme in self.highlight_themes_css.iter() {
             let theme_name = &theme.theme;
-            if !THEME_SET.themes.contains_key(theme_name) {
-                // Check extra themes
-                if let Some(extra) = &*self.extra_theme_set {
-                    if !extra.themes.contains_key(theme_name) {
-                        bail!("Can't export highlight theme {}, as it does not exist.\n\
-                        Make sure it's spelled correctly, or your custom .tmTheme' is defined properly.", theme_name)
-                    }
-                }
+            let in_extra = match &*self.extra_theme_set {
+                Some(extra) => extra.themes.contains_key(theme_name),
+                None => false,
+            };
+            if !THEME_SET.themes.contains_key(theme_name)
+                && !in_extra
+                && !self.highlight_theme_fallback
+            {
+                bail!(
+                    "Can't export highlight theme {}, as it does not exist.\n\
+                Make sure it's spelled correctly, or your custom .tmTheme' is defined properly.",
+                    theme_name
+                )
             }
         }
 
@@ -184,6 +467,14 @@ impl Markdown {
 
         format!("<a {}{}{}href=\"{}\">", rel, target, title, url)
     }
+
+    /// Applies the options of the shortcodes to their definitions: decoding the HTML entities
+    /// of their arguments, outputting HTML and the types of their arguments
+    pub fn apply_shortcode_config(&self, definitions: &mut HashMap<String, ShortcodeDefinition>) {
+        set_shortcodes_decoding_entities(definitions, &self.shortcodes_decoding_entities);
+        set_shortcodes_with_html_output(definitions, &self.shortcodes_with_html_output);
+        set_shortcode_arg_types(definitions, &self.shortcode_arg_types);
+    }
 }
 
 impl Default for Markdown {
@@ -191,12 +482,65 @@ impl Default for Markdown {
         Markdown {
             highlight_code: false,
             highlight_theme: DEFAULT_HIGHLIGHT_THEME.to_owned(),
+            highlight_theme_fallback: false,
             highlight_themes_css: Vec::new(),
+            purge_highlight_css: false,
             render_emoji: false,
             external_links_target_blank: false,
             external_links_no_follow: false,
             external_links_no_referrer: false,
             smart_punctuation: false,
+            combined_emphasis: true,
+            measurement_quotes: None,
+            horizontal_rule_class: None,
+            link_bare_emails: false,
+            obfuscate_emails: false,
+            shortcode_max_depth: 0,
+            max_output_size: None,
+            unknown_shortcode_template: None,
+            trim_inline_shortcodes: false,
+            case_insensitive_shortcodes: false,
+            release_base_url: None,
+            commit_base_url: None,
+            mention_base_url: None,
+            cover_image_ignored_containers: Vec::new(),
+            shortcodes_decoding_entities: Vec::new(),
+            shortcodes_with_html_output: Vec::new(),
+            shortcode_arg_types: HashMap::new(),
+            interactive_task_lists: false,
+            table_cell_blocks: false,
+            table_cell_line_break: None,
+            protect_code_from_shortcodes: false,
+            toc_json: false,
+            article_microdata: false,
+            heading_sections: false,
+            trim_trailing_whitespace: false,
+            code_tab_groups: false,
+            table_wrapper_class: None,
+            external_link_marker: None,
+            truncate_autolinks: None,
+            duplicate_footnotes: DuplicateFootnotes::Warn,
+            last_modified_note: None,
+            last_modified_position: LastModifiedPosition::End,
+            leaked_front_matter: LeakedFrontMatter::Warn,
+            empty_links: EmptyLinks::Error,
+            empty_headings: EmptyHeadings::Skip,
+            style_attributes: StyleAttributes::Keep,
+            anchor_target_class: None,
+            encode_anchor_fragments: false,
+            anchor_min_level: 1,
+            trailing_slash_links: false,
+            list_spacing: None,
+            lead_paragraph_class: None,
+            normalize_list_start: false,
+            merge_adjacent_blockquotes: false,
+            blockquote_attribution: None,
+            image_captions: None,
+            expand_tabs: None,
+            copyable_line_numbers: false,
+            heading_word_counts: false,
+            heading_progress_map: false,
+            text_direction: None,
             extra_syntaxes_and_themes: vec![],
             extra_syntax_set: None,
             extra_theme_set: Arc::new(None),
@@ -26,6 +26,16 @@ pub enum Mode {
     Check,
 }
 
+/// How the aliases of pages and sections are rendered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AliasRedirects {
+    /// An HTML page redirecting to the canonical one for each alias
+    Html,
+    /// A single `_redirects` file at the root of the output, eg for Netlify or Cloudflare Pages
+    File,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(default)]
 pub struct Config {
@@ -46,6 +56,10 @@ pub struct Config {
     /// The translations strings for the default language
     translations: HashMap<String, String>,
 
+    /// Whether `<link rel="alternate" hreflang="...">` elements pointing to the translations
+    /// of pages and sections are added to their `<head>`
+    pub inject_hreflang_links: bool,
+
     /// Whether to generate a feed. Defaults to false.
     pub generate_feed: bool,
     /// The number of articles to include in the feed. Defaults to including all items.
@@ -62,8 +76,19 @@ pub struct Config {
     pub compile_sass: bool,
     /// Whether to minify the html output
     pub minify_html: bool,
+    /// Whether the HTML comments are kept when minifying the HTML output
+    pub minify_html_keep_comments: bool,
+    /// If set, the pages whose rendered content is longer than that many bytes are split at
+    /// their headings into several pages
+    pub split_pages_longer_than: Option<usize>,
     /// Whether to build the search index for the content
     pub build_search_index: bool,
+    /// Whether the robots.txt disallows all crawling when drafts are included, eg for previews
+    pub disallow_robots_with_drafts: bool,
+    /// How the `aliases` of pages and sections are turned into redirects
+    pub alias_redirects: AliasRedirects,
+    /// Whether the local SVG icons used in `<img>` elements are inlined in a sprite in each page
+    pub inline_svg_icons: bool,
     /// A list of file glob patterns to ignore when processing the content folder. Defaults to none.
     /// Had to remove the PartialEq derive because GlobSet does not implement it. No impact
     /// because it's unused anyway (who wants to sort Configs?).
@@ -346,8 +371,14 @@ impl Default for Config {
             taxonomies: Vec::new(),
             compile_sass: false,
             minify_html: false,
+            minify_html_keep_comments: false,
+            split_pages_longer_than: None,
             mode: Mode::Build,
             build_search_index: false,
+            disallow_robots_with_drafts: false,
+            alias_redirects: AliasRedirects::Html,
+            inline_svg_icons: false,
+            inject_hreflang_links: false,
             ignored_content: Vec::new(),
             ignored_content_globset: None,
             translations: HashMap::new(),
@@ -365,6 +396,7 @@ impl Default for Config {
 mod tests {
     use super::*;
     use utils::slugs::SlugifyStrategy;
+    use utils::templates::{ShortcodeArg, ShortcodeArgType};
 
     #[test]
     fn can_import_valid_config() {
@@ -706,6 +738,85 @@ highlight_themes_css = [
         assert_eq!(config.is_err(), true);
     }
 
+    #[test]
+    fn can_fallback_to_default_highlight_theme() {
+        let config = r#"
+base_url = "https://www.getzola.org/"
+
+[markdown]
+highlight_code = true
+highlight_theme = "asdf"
+highlight_theme_fallback = true
+    "#;
+
+        let mut config = Config::parse(config).unwrap();
+        config.markdown.init_extra_syntaxes_and_highlight_themes(Path::new(".")).unwrap();
+        assert_eq!(config.markdown.highlight_theme, markup::DEFAULT_HIGHLIGHT_THEME);
+    }
+
+    #[test]
+    fn errors_on_unknown_highlight_theme_without_fallback() {
+        let config = r#"
+base_url = "https://www.getzola.org/"
+
+[markdown]
+highlight_code = true
+highlight_theme = "asdf"
+    "#;
+
+        let mut config = Config::parse(config).unwrap();
+        assert!(config.markdown.init_extra_syntaxes_and_highlight_themes(Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn errors_on_unknown_highlight_theme_css_export_without_fallback() {
+        let config = r#"
+base_url = "https://www.getzola.org/"
+
+[markdown]
+highlight_code = true
+highlight_themes_css = [
+  { theme = "asdf", filename = "asdf.css" },
+]
+    "#;
+
+        let mut config = Config::parse(config).unwrap();
+        assert!(config.markdown.init_extra_syntaxes_and_highlight_themes(Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn can_get_unknown_highlight_theme_with_fallback() {
+        let mut config = Config::default_for_test();
+        config.markdown.highlight_theme_fallback = true;
+        let theme = config.markdown.get_highlight_theme_by_name("asdf");
+        let default_theme =
+            config.markdown.get_highlight_theme_by_name(markup::DEFAULT_HIGHLIGHT_THEME);
+        assert_eq!(theme.name, default_theme.name);
+    }
+
+    #[test]
+    #[should_panic]
+    fn doesnt_fallback_to_default_highlight_theme_when_disabled() {
+        let config = Config::default_for_test();
+        config.markdown.get_highlight_theme_by_name("asdf");
+    }
+
+    #[test]
+    fn can_parse_shortcode_arg_types() {
+        let config = r#"
+base_url = "https://www.getzola.org/"
+
+[markdown.shortcode_arg_types.youtube]
+id = { type = "string", required = true }
+autoplay = "boolean"
+    "#;
+
+        let config = Config::parse(config).unwrap();
+        let types = &config.markdown.shortcode_arg_types["youtube"];
+        assert_eq!(types["id"], ShortcodeArg::required(ShortcodeArgType::String));
+        assert_eq!(types["autoplay"], ShortcodeArg::optional(ShortcodeArgType::Boolean));
+    }
+
     // https://github.com/getzola/zola/issues/1687
     #[test]
     fn regression_config_default_lang_data() {
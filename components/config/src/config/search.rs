@@ -15,6 +15,8 @@ pub struct Search {
     pub include_description: bool,
     /// Include the path of the page in the search index. `false` by default.
     pub include_path: bool,
+    /// Whether the code blocks are part of the indexed content. `true` by default.
+    pub include_code_blocks: bool,
 }
 
 impl Default for Search {
@@ -24,6 +26,7 @@ impl Default for Search {
             include_content: true,
             include_description: false,
             include_path: false,
+            include_code_blocks: true,
             truncate_content_length: None,
         }
     }
@@ -3,8 +3,17 @@ pub mod highlighting;
 mod theme;
 
 pub use crate::config::{
-    languages::LanguageOptions, link_checker::LinkChecker, search::Search, slugify::Slugify,
-    taxonomies::Taxonomy, Config,
+    languages::LanguageOptions,
+    link_checker::LinkChecker,
+    markup::{
+        BlockquoteAttribution, DuplicateFootnotes, EmptyHeadings, EmptyLinks, ImageCaptions,
+        LastModifiedPosition, LeakedFrontMatter, ListSpacing, MeasurementQuotes, StyleAttributes,
+        TextDirection,
+    },
+    search::Search,
+    slugify::Slugify,
+    taxonomies::Taxonomy,
+    AliasRedirects, Config,
 };
 use errors::Result;
 
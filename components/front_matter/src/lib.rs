@@ -3,6 +3,7 @@ use serde_derive::{Deserialize, Serialize};
 
 use errors::{bail, Error, Result};
 use regex::Regex;
+use std::collections::HashMap;
 use std::path::Path;
 
 mod page;
@@ -63,6 +64,8 @@ pub enum SortBy {
 pub enum InsertAnchor {
     Left,
     Right,
+    /// The whole heading text is the link to its anchor
+    Heading,
     None,
 }
 
@@ -127,12 +130,25 @@ pub fn split_page_content<'c>(
     Ok((meta, content))
 }
 
+/// Looks for another front matter block at the start of content already split from its front
+/// matter, eg in a file with two front matters, and returns the content after it if there is one.
+/// Only blocks with at least one `key = value` (or `key: value`) field are considered.
+pub fn find_leaked_front_matter(content: &str) -> Option<&str> {
+    let (front_matter, rest) = split_content(Path::new(""), content).ok()?;
+    let fields: HashMap<String, tera::Value> = front_matter.deserialize().ok()?;
+    if fields.is_empty() {
+        None
+    } else {
+        Some(rest)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
     use test_case::test_case;
 
-    use super::{split_page_content, split_section_content};
+    use super::{find_leaked_front_matter, split_page_content, split_section_content};
 
     #[test_case(r#"
 +++
@@ -278,4 +294,21 @@ date: 2002-10-12
         let res = split_page_content(Path::new(""), content);
         assert!(res.is_err());
     }
+
+    #[test_case(r#"
++++
+title = "Leaked"
++++
+Hello"#, Some("Hello"); "toml")]
+    #[test_case(r#"
+---
+title: Leaked
+---
+Hello"#, Some("Hello"); "yaml")]
+    #[test_case("Hello\n+++\ntitle = \"Not at the start\"\n+++\n", None; "not at the start")]
+    #[test_case("---\n\nSome text between rules\n\n---\nHello", None; "horizontal rules")]
+    #[test_case("Hello", None; "no front matter")]
+    fn can_find_leaked_front_matter(content: &str, expected: Option<&str>) {
+        assert_eq!(find_leaked_front_matter(content), expected);
+    }
 }
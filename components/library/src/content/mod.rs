@@ -12,7 +12,8 @@ pub use self::page::Page;
 pub use self::section::Section;
 pub use self::ser::{SerializingPage, SerializingSection};
 
-use config::Config;
+use config::{Config, LeakedFrontMatter};
+use front_matter::find_leaked_front_matter;
 use rendering::Heading;
 
 pub fn has_anchor(headings: &[Heading], anchor: &str) -> bool {
@@ -28,6 +29,36 @@ pub fn has_anchor(headings: &[Heading], anchor: &str) -> bool {
     false
 }
 
+/// Warns about a front matter block at the start of the content of the given file, which would
+/// otherwise be rendered as text, and removes it if `markdown.leaked_front_matter` is `strip`
+pub fn check_leaked_front_matter<'c>(
+    file_path: &Path,
+    content: &'c str,
+    config: &Config,
+) -> &'c str {
+    let rest = match find_leaked_front_matter(content) {
+        Some(rest) => rest,
+        None => return content,
+    };
+
+    match config.markdown.leaked_front_matter {
+        LeakedFrontMatter::Warn => {
+            eprintln!(
+                "Warning: the content of {} starts with a front matter block, which will be rendered as text.",
+                file_path.display()
+            );
+            content
+        }
+        LeakedFrontMatter::Strip => {
+            eprintln!(
+                "Warning: the content of {} starts with a front matter block, it has been removed.",
+                file_path.display()
+            );
+            rest
+        }
+    }
+}
+
 /// Looks into the current folder for the path and see if there's anything that is not a .md
 /// file. Those will be copied next to the rendered .html file
 /// If `recursive` is set to `true`, it will add all subdirectories assets as well. This should
@@ -130,6 +161,7 @@ mod tests {
                 id: "1".to_string(),
                 permalink: String::new(),
                 title: String::new(),
+                word_count: None,
                 children: vec![],
             },
             Heading {
@@ -137,6 +169,7 @@ mod tests {
                 id: "1-1".to_string(),
                 permalink: String::new(),
                 title: String::new(),
+                word_count: None,
                 children: vec![],
             },
             Heading {
@@ -144,6 +177,7 @@ mod tests {
                 id: "1-1-1".to_string(),
                 permalink: String::new(),
                 title: String::new(),
+                word_count: None,
                 children: vec![],
             },
             Heading {
@@ -151,6 +185,7 @@ mod tests {
                 id: "1-2".to_string(),
                 permalink: String::new(),
                 title: String::new(),
+                word_count: None,
                 children: vec![],
             },
         ];
@@ -165,12 +200,14 @@ mod tests {
             id: "1".to_string(),
             permalink: String::new(),
             title: String::new(),
+            word_count: None,
             children: vec![
                 Heading {
                     level: 2,
                     id: "1-1".to_string(),
                     permalink: String::new(),
                     title: String::new(),
+                    word_count: None,
                     children: vec![],
                 },
                 Heading {
@@ -178,6 +215,7 @@ mod tests {
                     id: "1-1-1".to_string(),
                     permalink: String::new(),
                     title: String::new(),
+                    word_count: None,
                     children: vec![],
                 },
                 Heading {
@@ -185,6 +223,7 @@ mod tests {
                     id: "1-2".to_string(),
                     permalink: String::new(),
                     title: String::new(),
+                    word_count: None,
                     children: vec![],
                 },
             ],
@@ -1,9 +1,11 @@
 /// A page, can be a blog post or a basic page
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde_derive::Serialize;
 use slotmap::DefaultKey;
 use tera::{Context as TeraContext, Tera};
 
@@ -18,7 +20,7 @@ use utils::templates::{render_template, ShortcodeDefinition};
 
 use crate::content::file_info::FileInfo;
 use crate::content::ser::SerializingPage;
-use crate::content::{find_related_assets, has_anchor};
+use crate::content::{check_leaked_front_matter, find_related_assets, has_anchor};
 use utils::fs::read_file;
 use utils::links::has_anchor_id;
 
@@ -30,6 +32,104 @@ lazy_static! {
     ).unwrap();
 
     static ref FOOTNOTES_RE: Regex = Regex::new(r"<sup\s*.*?>\s*.*?</sup>").unwrap();
+
+    /// The headings and the elements they can be nested in
+    static ref SPLIT_TAG_RE: Regex =
+        Regex::new(r"(?i)<(/?)(h[1-6]|blockquote|details|div|li|ol|section|table|ul)\b").unwrap();
+
+    /// The elements wrapping the content added by `heading_sections`, `text_direction` and
+    /// `article_microdata`, which headings are split in and which are re-opened in each part
+    static ref SPLIT_WRAPPER_RE: Regex = Regex::new(
+        r#"^(?:<section role="region" aria-labelledby="[^"]*">\n|<div dir="[a-z]+">\n|<div itemscope itemtype="https://schema.org/Article">\n(?:<meta itemprop="[^"]*" content="[^"]*">\n)*)"#
+    ).unwrap();
+}
+
+/// Where the part of a page split by `split_pages_longer_than` being rendered is
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PageSplit {
+    /// The number of the part (1-indexed)
+    pub index: usize,
+    pub number_parts: usize,
+    pub permalink: String,
+    pub path: String,
+    /// Permalink to the previous part, if any
+    pub previous: Option<String>,
+    /// Permalink to the next part, if any
+    pub next: Option<String>,
+}
+
+/// Splits the HTML before some of its headings not nested in another element, so each part is
+/// at most `max_len` bytes when possible. A part without headings is never split further.
+/// Headings are also split in the wrappers of `SPLIT_WRAPPER_RE`, which are then closed at the
+/// end of a part and re-opened at the start of the next one.
+pub fn split_at_headings(html: &str, max_len: usize) -> Vec<Cow<'_, str>> {
+    // Where the HTML can be split, with the opening tags of the wrappers open there
+    let mut cuts: Vec<(usize, Vec<&str>)> = vec![(0, Vec::new())];
+    // The elements open, with the opening tag of the wrappers
+    let mut open: Vec<Option<&str>> = Vec::new();
+    // The HTML is never split before the wrappers it starts with
+    let mut content_start = 0;
+    // The end of the last `heading_sections` wrapper, its heading is split before the wrapper
+    let mut section_end = None;
+    for caps in SPLIT_TAG_RE.captures_iter(html) {
+        let start = caps.get(0).unwrap().start();
+        let closing = !caps[1].is_empty();
+        let at_top = open.iter().all(Option::is_some);
+        let wrappers = || open.iter().flatten().copied().collect::<Vec<_>>();
+        if caps[2].to_lowercase().starts_with('h') {
+            if !closing && at_top && start > content_start && section_end != Some(start) {
+                cuts.push((start, wrappers()));
+            }
+        } else if closing {
+            open.pop();
+        } else if let Some(wrapper) = SPLIT_WRAPPER_RE.find(&html[start..]) {
+            let wrapper = wrapper.as_str();
+            if wrapper.starts_with("<section") {
+                if at_top && start > content_start {
+                    cuts.push((start, wrappers()));
+                }
+                section_end = Some(start + wrapper.len());
+            }
+            if html[content_start..start].trim().is_empty() {
+                content_start = start + wrapper.len();
+            }
+            open.push(Some(wrapper));
+        } else {
+            open.push(None);
+        }
+    }
+    cuts.push((html.len(), Vec::new()));
+
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut last = 0;
+    for (i, (boundary, _)) in cuts.iter().enumerate().skip(1) {
+        if boundary - cuts[start].0 > max_len && last > start {
+            parts.push(split_part(html, &cuts[start], &cuts[last]));
+            start = last;
+        }
+        last = i;
+    }
+    parts.push(split_part(html, &cuts[start], &cuts[cuts.len() - 1]));
+    parts
+}
+
+/// The HTML between two cuts, with the wrappers open at the cuts re-opened and closed
+fn split_part<'a>(
+    html: &'a str,
+    from: &(usize, Vec<&str>),
+    to: &(usize, Vec<&str>),
+) -> Cow<'a, str> {
+    let content = &html[from.0..to.0];
+    if from.1.is_empty() && to.1.is_empty() {
+        return Cow::Borrowed(content);
+    }
+    let mut part = from.1.concat();
+    part.push_str(content);
+    for wrapper in to.1.iter().rev() {
+        part.push_str(if wrapper.starts_with("<section") { "</section>\n" } else { "</div>\n" });
+    }
+    Cow::Owned(part)
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -79,6 +179,8 @@ pub struct Page {
     pub heavier: Option<DefaultKey>,
     /// Toc made from the headings of the markdown file
     pub toc: Vec<Heading>,
+    /// The first image of the content, to be used as a cover or thumbnail
+    pub cover_image: Option<String>,
     /// How many words in the raw content
     pub word_count: Option<usize>,
     /// How long would it take to read the raw content.
@@ -114,6 +216,7 @@ impl Page {
         base_path: &Path,
     ) -> Result<Page> {
         let (meta, content) = split_page_content(file_path, content)?;
+        let content = check_leaked_front_matter(file_path, content, config);
         let mut page = Page::new(file_path, meta, base_path);
 
         page.lang = page.file.find_language(config)?;
@@ -238,18 +341,17 @@ impl Page {
         );
         context.set_shortcode_definitions(shortcode_definitions);
         context.set_current_page_path(&self.file.relative);
+        context.set_source_path(&self.file.path);
         context.tera_context.insert("page", &SerializingPage::from_page_basic(self, None));
 
         let res = render_content(&self.raw_content, &context).map_err(|e| {
             Error::chain(format!("Failed to render content of {}", self.file.path.display()), e)
         })?;
 
-        self.summary = res
-            .summary_len
-            .map(|l| &res.body[0..l])
-            .map(|s| FOOTNOTES_RE.replace(s, "").into_owned());
+        self.summary = res.summary().map(|s| FOOTNOTES_RE.replace(&s, "").into_owned());
         self.content = res.body;
         self.toc = res.toc;
+        self.cover_image = res.cover_image;
         self.external_links = res.external_links;
         self.internal_links = res.internal_links;
 
@@ -258,17 +360,86 @@ impl Page {
 
     /// Renders the page using the default layout, unless specified in front-matter
     pub fn render_html(&self, tera: &Tera, config: &Config, library: &Library) -> Result<String> {
+        self.render_part_html(tera, config, library, &self.content, None)
+    }
+
+    /// Renders the page like `render_html`, split at its headings into parts with at most
+    /// `max_len` bytes of content when possible. The parts after the first one go in
+    /// `page/2/`, `page/3/`... under the page and their template gets a `page_split`
+    /// with the links to the previous and next parts.
+    pub fn render_html_parts(
+        &self,
+        tera: &Tera,
+        config: &Config,
+        library: &Library,
+        max_len: usize,
+    ) -> Result<Vec<String>> {
+        let parts = split_at_headings(&self.content, max_len);
+        if parts.len() == 1 {
+            return Ok(vec![self.render_html(tera, config, library)?]);
+        }
+
+        let locations = self.part_locations(parts.len());
+        parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| {
+                let split = PageSplit {
+                    index: i + 1,
+                    number_parts: parts.len(),
+                    permalink: locations[i].0.clone(),
+                    path: locations[i].1.clone(),
+                    previous: i.checked_sub(1).map(|j| locations[j].0.clone()),
+                    next: locations.get(i + 1).map(|l| l.0.clone()),
+                };
+                self.render_part_html(tera, config, library, part, Some(&split))
+            })
+            .collect()
+    }
+
+    /// The permalinks of the parts `render_html_parts` renders the page in, the first one being
+    /// the permalink of the page
+    pub fn part_permalinks(&self, max_len: usize) -> Vec<String> {
+        let number_parts = split_at_headings(&self.content, max_len).len();
+        self.part_locations(number_parts).into_iter().map(|(permalink, _)| permalink).collect()
+    }
+
+    /// The permalink and path of each part of the page when split in that many parts
+    fn part_locations(&self, number_parts: usize) -> Vec<(String, String)> {
+        (1..=number_parts)
+            .map(|i| match i {
+                1 => (self.permalink.clone(), self.path.clone()),
+                _ => {
+                    (format!("{}page/{}/", self.permalink, i), format!("{}page/{}/", self.path, i))
+                }
+            })
+            .collect()
+    }
+
+    fn render_part_html(
+        &self,
+        tera: &Tera,
+        config: &Config,
+        library: &Library,
+        content: &str,
+        split: Option<&PageSplit>,
+    ) -> Result<String> {
         let tpl_name = match self.meta.template {
             Some(ref l) => l,
             None => "page.html",
         };
 
+        let mut page = self.to_serialized(library);
+        page.set_content(content);
         let mut context = TeraContext::new();
         context.insert("config", &config.serialize(&self.lang));
-        context.insert("current_url", &self.permalink);
-        context.insert("current_path", &self.path);
-        context.insert("page", &self.to_serialized(library));
+        context.insert("current_url", split.map(|s| &s.permalink).unwrap_or(&self.permalink));
+        context.insert("current_path", split.map(|s| &s.path).unwrap_or(&self.path));
+        context.insert("page", &page);
         context.insert("lang", &self.lang);
+        if let Some(split) = split {
+            context.insert("page_split", split);
+        }
 
         render_template(tpl_name, tera, context, &config.theme).map_err(|e| {
             Error::chain(format!("Failed to render page '{}'", self.file.path.display()), e)
@@ -325,8 +496,9 @@ mod tests {
     use tempfile::tempdir;
     use tera::Tera;
 
-    use super::Page;
-    use config::{Config, LanguageOptions};
+    use super::{split_at_headings, Page};
+    use crate::Library;
+    use config::{Config, LanguageOptions, LeakedFrontMatter};
     use front_matter::InsertAnchor;
     use utils::slugs::SlugifyStrategy;
 
@@ -358,6 +530,94 @@ Hello world"#;
         assert_eq!(page.content, "<p>Hello world</p>\n".to_string());
     }
 
+    #[test]
+    fn can_split_long_pages_at_headings() {
+        let html = "<h1 id=\"a\">A</h1>\n<p>aaaaaaaaaa</p>\n<blockquote>\n<h2 id=\"q\">Q</h2>\n</blockquote>\n\
+<h2 id=\"b\">B</h2>\n<p>bbbbbbbbbb</p>\n<h2 id=\"c\">C</h2>\n<p>cc</p>\n";
+        assert_eq!(
+            split_at_headings(html, 40),
+            vec![
+                "<h1 id=\"a\">A</h1>\n<p>aaaaaaaaaa</p>\n<blockquote>\n<h2 id=\"q\">Q</h2>\n</blockquote>\n",
+                "<h2 id=\"b\">B</h2>\n<p>bbbbbbbbbb</p>\n",
+                "<h2 id=\"c\">C</h2>\n<p>cc</p>\n",
+            ]
+        );
+        assert_eq!(split_at_headings(html, 1000), vec![html]);
+
+        let config = Config::default_for_test();
+        let mut page =
+            Page::parse(Path::new("post.md"), "+++\n+++\n", &config, &PathBuf::new()).unwrap();
+        page.content = html.to_string();
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "page.html",
+            "{{ page_split.index }}/{{ page_split.number_parts }} \
+{{ page_split.previous | default(value='-') | safe }} {{ page_split.next | default(value='-') | safe }}",
+        )
+        .unwrap();
+        let library = Library::new(0, 0, false);
+
+        let parts = page.render_html_parts(&tera, &config, &library, 40).unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                "1/3 - http://a-website.com/post/page/2/",
+                "2/3 http://a-website.com/post/ http://a-website.com/post/page/3/",
+                "3/3 http://a-website.com/post/page/2/ -",
+            ]
+        );
+        assert_eq!(
+            page.part_permalinks(40),
+            vec![
+                "http://a-website.com/post/",
+                "http://a-website.com/post/page/2/",
+                "http://a-website.com/post/page/3/",
+            ]
+        );
+        assert_eq!(page.part_permalinks(1000), vec!["http://a-website.com/post/"]);
+    }
+
+    #[test]
+    fn can_split_long_pages_in_wrappers() {
+        let html = "<div dir=\"auto\">\n<section role=\"region\" aria-labelledby=\"a\">\n<h1 id=\"a\">A</h1>\n\
+<p>aaaaaaaaaa</p>\n<section role=\"region\" aria-labelledby=\"b\">\n<h2 id=\"b\">B</h2>\n<p>bbbbbbbbbb</p>\n\
+</section>\n<section role=\"region\" aria-labelledby=\"c\">\n<h2 id=\"c\">C</h2>\n<p>cc</p>\n</section>\n\
+</section>\n</div>\n";
+        assert_eq!(
+            split_at_headings(html, 80),
+            vec![
+                "<div dir=\"auto\">\n<section role=\"region\" aria-labelledby=\"a\">\n<h1 id=\"a\">A</h1>\n\
+<p>aaaaaaaaaa</p>\n</section>\n</div>\n",
+                "<div dir=\"auto\">\n<section role=\"region\" aria-labelledby=\"a\">\n\
+<section role=\"region\" aria-labelledby=\"b\">\n<h2 id=\"b\">B</h2>\n<p>bbbbbbbbbb</p>\n</section>\n\
+</section>\n</div>\n",
+                "<div dir=\"auto\">\n<section role=\"region\" aria-labelledby=\"a\">\n\
+<section role=\"region\" aria-labelledby=\"c\">\n<h2 id=\"c\">C</h2>\n<p>cc</p>\n</section>\n\
+</section>\n</div>\n",
+            ]
+        );
+        assert_eq!(split_at_headings(html, 1000), vec![html]);
+    }
+
+    #[test]
+    fn can_strip_leaked_front_matter() {
+        let mut config = Config::default_for_test();
+        let content = r#"
++++
+title = "Hello"
++++
++++
+description = "hey there"
++++
+Hello world"#;
+        let page = Page::parse(Path::new("post.md"), content, &config, &PathBuf::new()).unwrap();
+        assert!(page.raw_content.starts_with("+++\ndescription"));
+
+        config.markdown.leaked_front_matter = LeakedFrontMatter::Strip;
+        let page = Page::parse(Path::new("post.md"), content, &config, &PathBuf::new()).unwrap();
+        assert_eq!(page.raw_content, "Hello world");
+    }
+
     #[test]
     fn test_can_make_url_from_sections_and_slug() {
         let content = r#"
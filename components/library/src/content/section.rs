@@ -14,7 +14,7 @@ use utils::templates::{render_template, ShortcodeDefinition};
 
 use crate::content::file_info::FileInfo;
 use crate::content::ser::SerializingSection;
-use crate::content::{find_related_assets, has_anchor};
+use crate::content::{check_leaked_front_matter, find_related_assets, has_anchor};
 use crate::library::Library;
 
 // Default is used to create a default index section if there is no _index.md in the root content directory
@@ -48,6 +48,8 @@ pub struct Section {
     pub subsections: Vec<DefaultKey>,
     /// Toc made from the headings of the markdown file
     pub toc: Vec<Heading>,
+    /// The first image of the content, to be used as a cover or thumbnail
+    pub cover_image: Option<String>,
     /// How many words in the raw content
     pub word_count: Option<usize>,
     /// How long would it take to read the raw content.
@@ -82,6 +84,7 @@ impl Section {
         base_path: &Path,
     ) -> Result<Section> {
         let (meta, content) = split_section_content(file_path, content)?;
+        let content = check_leaked_front_matter(file_path, content, config);
         let mut section = Section::new(file_path, meta, base_path);
         section.lang = section.file.find_language(config)?;
         section.raw_content = content.to_string();
@@ -159,6 +162,7 @@ impl Section {
         );
         context.set_shortcode_definitions(shortcode_definitions);
         context.set_current_page_path(&self.file.relative);
+        context.set_source_path(&self.file.path);
         context.tera_context.insert("section", &SerializingSection::from_section_basic(self, None));
 
         let res = render_content(&self.raw_content, &context).map_err(|e| {
@@ -166,6 +170,7 @@ impl Section {
         })?;
         self.content = res.body;
         self.toc = res.toc;
+        self.cover_image = res.cover_image;
         self.external_links = res.external_links;
         self.internal_links = res.internal_links;
 
@@ -85,6 +85,7 @@ pub struct SerializingPage<'a> {
     components: &'a [String],
     summary: &'a Option<String>,
     toc: &'a [Heading],
+    cover_image: &'a Option<String>,
     word_count: Option<usize>,
     reading_time: Option<usize>,
     assets: &'a [String],
@@ -102,6 +103,11 @@ pub struct SerializingPage<'a> {
 }
 
 impl<'a> SerializingPage<'a> {
+    /// Replaces the content of the page, eg by the part of it being rendered
+    pub(crate) fn set_content(&mut self, content: &'a str) {
+        self.content = content;
+    }
+
     /// Grabs all the data from a page, including sibling pages
     pub fn from_page(page: &'a Page, library: &'a Library) -> Self {
         let mut year = None;
@@ -164,6 +170,7 @@ impl<'a> SerializingPage<'a> {
             components: &page.components,
             summary: &page.summary,
             toc: &page.toc,
+            cover_image: &page.cover_image,
             word_count: page.word_count,
             reading_time: page.reading_time,
             assets: &page.serialized_assets,
@@ -230,6 +237,7 @@ impl<'a> SerializingPage<'a> {
             components: &page.components,
             summary: &page.summary,
             toc: &page.toc,
+            cover_image: &page.cover_image,
             word_count: page.word_count,
             reading_time: page.reading_time,
             assets: &page.serialized_assets,
@@ -261,6 +269,7 @@ pub struct SerializingSection<'a> {
     path: &'a str,
     components: &'a [String],
     toc: &'a [Heading],
+    cover_image: &'a Option<String>,
     word_count: Option<usize>,
     reading_time: Option<usize>,
     lang: &'a str,
@@ -302,6 +311,7 @@ impl<'a> SerializingSection<'a> {
             path: &section.path,
             components: &section.components,
             toc: &section.toc,
+            cover_image: &section.cover_image,
             word_count: section.word_count,
             reading_time: section.reading_time,
             assets: &section.serialized_assets,
@@ -340,6 +350,7 @@ impl<'a> SerializingSection<'a> {
             path: &section.path,
             components: &section.components,
             toc: &section.toc,
+            cover_image: &section.cover_image,
             word_count: section.word_count,
             reading_time: section.reading_time,
             assets: &section.serialized_assets,
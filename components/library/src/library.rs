@@ -402,6 +402,35 @@ impl Library {
         self.pages.get(key).unwrap()
     }
 
+    /// The `(lang, permalink)` of the page and its translations, sorted by language.
+    /// Empty if the page is not translated
+    pub fn find_page_alternates(&self, page: &Page) -> Vec<(&str, &str)> {
+        self.find_alternates(&page.file.canonical, |key| {
+            let other = self.get_page_by_key(key);
+            (&other.lang, &other.permalink)
+        })
+    }
+
+    /// Same as `find_page_alternates` for sections
+    pub fn find_section_alternates(&self, section: &Section) -> Vec<(&str, &str)> {
+        self.find_alternates(&section.file.canonical, |key| {
+            let other = self.get_section_by_key(key);
+            (&other.lang, &other.permalink)
+        })
+    }
+
+    fn find_alternates<'a, F>(&'a self, canonical: &Path, get: F) -> Vec<(&'a str, &'a str)>
+    where
+        F: Fn(DefaultKey) -> (&'a str, &'a str),
+    {
+        let mut alternates: Vec<_> = match self.translations.get(canonical) {
+            Some(keys) if keys.len() > 1 => keys.iter().map(|k| get(*k)).collect(),
+            _ => return Vec::new(),
+        };
+        alternates.sort_unstable();
+        alternates
+    }
+
     pub fn remove_section<P: AsRef<Path>>(&mut self, path: P) -> Option<Section> {
         if let Some(k) = self.paths_to_sections.remove(path.as_ref()) {
             self.sections.remove(k)
@@ -443,6 +472,37 @@ impl Library {
 mod tests {
     use super::*;
 
+    #[test]
+    fn can_find_alternates_of_translated_pages() {
+        let config = Config::default();
+        let mut library = Library::new(10, 10, true);
+        library.insert_section(Section::default());
+        let mut page =
+            Page { lang: "en".to_string(), permalink: "/hello/".to_string(), ..Default::default() };
+        page.file.canonical = PathBuf::from("content/hello");
+        let mut fr_page = Page {
+            lang: "fr".to_string(),
+            permalink: "/fr/hello/".to_string(),
+            ..Default::default()
+        };
+        fr_page.file.canonical = PathBuf::from("content/hello");
+        let mut other =
+            Page { lang: "en".to_string(), permalink: "/other/".to_string(), ..Default::default() };
+        other.file.canonical = PathBuf::from("content/other");
+        library.insert_page(fr_page);
+        library.insert_page(page);
+        library.insert_page(other);
+        library.populate_sections(&config);
+
+        let page = library.pages_values().into_iter().find(|p| p.permalink == "/hello/").unwrap();
+        assert_eq!(
+            library.find_page_alternates(page),
+            vec![("en", "/hello/"), ("fr", "/fr/hello/")]
+        );
+        let other = library.pages_values().into_iter().find(|p| p.permalink == "/other/").unwrap();
+        assert!(library.find_page_alternates(other).is_empty());
+    }
+
     #[test]
     fn can_find_no_collisions() {
         let mut library = Library::new(10, 10, false);
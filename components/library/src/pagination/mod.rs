@@ -126,6 +126,14 @@ impl<'a> Paginator<'a> {
         paginator
     }
 
+    /// The section being paginated, if it is not a taxonomy
+    pub fn section(&self) -> Option<&'a Section> {
+        match self.root {
+            PaginationRoot::Section(section) => Some(section),
+            PaginationRoot::Taxonomy(..) => None,
+        }
+    }
+
     fn fill_pagers(&mut self, library: &'a Library) {
         // the list of pagers
         let mut pages = vec![];
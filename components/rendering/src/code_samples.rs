@@ -0,0 +1,53 @@
+use pulldown_cmark as cmark;
+
+use self::cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+use crate::codeblock::FenceSettings;
+
+/// A code block of some markdown, as written
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// The language of the fence, eg `rust` for ```` ```rust,linenos ````.
+    /// Indented code blocks and fences without a language have none
+    pub lang: Option<String>,
+    /// The code, not highlighted, with its lines hidden by `hide_lines` included
+    pub content: String,
+}
+
+/// Returns the code blocks of the markdown in order, eg to check that the code samples of the
+/// documentation compile. Shortcodes are not rendered so code blocks they output are not included
+pub fn extract_code_blocks(content: &str) -> Vec<CodeBlock> {
+    let mut opts = Options::empty();
+    opts.insert(Options::ENABLE_TABLES);
+    opts.insert(Options::ENABLE_FOOTNOTES);
+    opts.insert(Options::ENABLE_STRIKETHROUGH);
+    opts.insert(Options::ENABLE_TASKLISTS);
+
+    let mut blocks = Vec::new();
+    let mut current: Option<CodeBlock> = None;
+    for event in Parser::new_ext(content, opts) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(ref info) => {
+                        FenceSettings::new(info).language.map(|l| l.to_string())
+                    }
+                    CodeBlockKind::Indented => None,
+                };
+                current = Some(CodeBlock { lang, content: String::new() });
+            }
+            Event::Text(text) => {
+                if let Some(ref mut block) = current {
+                    block.content.push_str(&text);
+                }
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some(block) = current.take() {
+                    blocks.push(block);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    blocks
+}
@@ -8,6 +8,7 @@ use syntect::util::LinesWithEndings;
 use crate::codeblock::highlight::SyntaxHighlighter;
 use config::highlighting::{resolve_syntax_and_theme, HighlightSource};
 use config::Config;
+use errors::{bail, Result};
 pub(crate) use fence::FenceSettings;
 
 fn opening_html(
@@ -60,6 +61,64 @@ fn opening_html(
     html
 }
 
+/// The options of `highlight_code`, which can otherwise be set on the fence of code blocks
+#[derive(Debug, Clone, PartialEq)]
+pub struct HighlightOptions {
+    pub line_numbers: bool,
+    pub line_number_start: usize,
+    pub highlight_lines: Vec<RangeInclusive<usize>>,
+    pub hide_lines: Vec<RangeInclusive<usize>>,
+}
+
+impl Default for HighlightOptions {
+    fn default() -> Self {
+        Self {
+            line_numbers: false,
+            line_number_start: 1,
+            highlight_lines: Vec::new(),
+            hide_lines: Vec::new(),
+        }
+    }
+}
+
+/// Highlights some code outside of markdown, returning the same `<pre>` element as a code block
+/// in that language would be rendered to.
+/// `theme` is the name of a highlighting theme or `css` to use classes. The config is used
+/// to find the extra syntaxes and themes, and whether to use the default theme if `theme`
+/// cannot be found. Errors if it cannot be found otherwise.
+pub fn highlight_code(
+    code: &str,
+    language: Option<&str>,
+    theme: &str,
+    options: HighlightOptions,
+    config: &Config,
+) -> Result<String> {
+    let mut syntax_and_theme = resolve_syntax_and_theme(language, config);
+    syntax_and_theme.theme = if theme == "css" {
+        None
+    } else {
+        match config.markdown.find_highlight_theme(theme) {
+            Some(t) => Some(t),
+            None => bail!("Highlight theme {} not found", theme),
+        }
+    };
+    let highlighter = SyntaxHighlighter::new(true, syntax_and_theme);
+    let (mut block, mut html) = CodeBlock::with_highlighter(
+        highlighter,
+        language,
+        options,
+        config.markdown.copyable_line_numbers,
+    );
+
+    if code.ends_with('\n') {
+        html.push_str(&block.highlight(code));
+    } else {
+        html.push_str(&block.highlight(&format!("{}\n", code)));
+    }
+    html.push_str("</code></pre>\n");
+    Ok(html)
+}
+
 pub struct CodeBlock<'config> {
     highlighter: SyntaxHighlighter<'config>,
     // fence options
@@ -67,6 +126,8 @@ pub struct CodeBlock<'config> {
     line_number_start: usize,
     highlight_lines: Vec<RangeInclusive<usize>>,
     hide_lines: Vec<RangeInclusive<usize>>,
+    // line numbers are in a `data-line` attribute of each line rather than in a table
+    copyable_line_numbers: bool,
 }
 
 impl<'config> CodeBlock<'config> {
@@ -86,20 +147,41 @@ impl<'config> CodeBlock<'config> {
             }
         }
         let highlighter = SyntaxHighlighter::new(config.markdown.highlight_code, syntax_and_theme);
+        let options = HighlightOptions {
+            line_numbers: fence.line_numbers,
+            line_number_start: fence.line_number_start,
+            highlight_lines: fence.highlight_lines,
+            hide_lines: fence.hide_lines,
+        };
 
-        let html_start = opening_html(
+        Self::with_highlighter(
+            highlighter,
             fence.language,
+            options,
+            config.markdown.copyable_line_numbers,
+        )
+    }
+
+    fn with_highlighter(
+        highlighter: SyntaxHighlighter<'config>,
+        language: Option<&str>,
+        options: HighlightOptions,
+        copyable_line_numbers: bool,
+    ) -> (Self, String) {
+        let html_start = opening_html(
+            language,
             highlighter.pre_style(),
             highlighter.pre_class(),
-            fence.line_numbers,
+            options.line_numbers,
         );
         (
             Self {
                 highlighter,
-                line_numbers: fence.line_numbers,
-                line_number_start: fence.line_number_start,
-                highlight_lines: fence.highlight_lines,
-                hide_lines: fence.hide_lines,
+                line_numbers: options.line_numbers,
+                line_number_start: options.line_number_start,
+                highlight_lines: options.highlight_lines,
+                hide_lines: options.hide_lines,
+                copyable_line_numbers,
             },
             html_start,
         )
@@ -109,7 +191,8 @@ impl<'config> CodeBlock<'config> {
         let mut buffer = String::new();
         let mark_style = self.highlighter.mark_style();
 
-        if self.line_numbers {
+        let table = self.line_numbers && !self.copyable_line_numbers;
+        if table {
             buffer.push_str("<table><tbody>");
         }
 
@@ -153,7 +236,7 @@ impl<'config> CodeBlock<'config> {
                 }
             };
 
-            if self.line_numbers {
+            if table {
                 buffer.push_str("<tr><td>");
                 let num = format!("{}", self.line_number_start + i);
                 maybe_mark(&mut buffer, &num);
@@ -161,14 +244,21 @@ impl<'config> CodeBlock<'config> {
             }
 
             let highlighted_line = self.highlighter.highlight_line(line);
-            maybe_mark(&mut buffer, &highlighted_line);
+            if self.line_numbers && !table {
+                // The number is only shown with CSS so it is not copied with the code
+                buffer.push_str(&format!("<span data-line=\"{}\">", self.line_number_start + i));
+                maybe_mark(&mut buffer, &highlighted_line);
+                buffer.push_str("</span>");
+            } else {
+                maybe_mark(&mut buffer, &highlighted_line);
+            }
         }
 
         if let Some(rest) = self.highlighter.finalize() {
             buffer.push_str(&rest);
         }
 
-        if self.line_numbers {
+        if table {
             buffer.push_str("</tr></tbody></table>");
         }
 
@@ -1,11 +1,17 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use config::Config;
+use config::{
+    BlockquoteAttribution, Config, DuplicateFootnotes, EmptyHeadings, EmptyLinks, ListSpacing,
+    TextDirection,
+};
 use front_matter::InsertAnchor;
 use tera::{Context, Tera};
 use utils::templates::ShortcodeDefinition;
 
+use crate::transform::AutolinkRule;
+
 /// All the information from the zola site that is needed to render HTML from markdown
 #[derive(Debug)]
 pub struct RenderContext<'a> {
@@ -13,11 +19,47 @@ pub struct RenderContext<'a> {
     pub config: &'a Config,
     pub tera_context: Context,
     pub current_page_path: Option<&'a str>,
+    /// The file the content being rendered comes from
+    pub source_path: Option<&'a Path>,
     pub current_page_permalink: &'a str,
     pub permalinks: Cow<'a, HashMap<String, String>>,
     pub insert_anchor: InsertAnchor,
+    /// Headings with a lower level (eg `h1` for 2) only get an id, not an anchor link
+    pub anchor_min_level: u32,
     pub lang: &'a str,
     pub shortcode_definitions: Cow<'a, HashMap<String, ShortcodeDefinition>>,
+    /// Patterns to turn into links, eg version tags and commit hashes
+    pub autolinks: Vec<AutolinkRule>,
+    /// Whether task list checkboxes are enabled and have an id, instead of being disabled
+    pub interactive_task_lists: bool,
+    /// Whether email addresses in the text are turned into `mailto:` links
+    pub link_bare_emails: bool,
+    /// Whether the email links created from the text are encoded as HTML entities
+    pub obfuscate_emails: bool,
+    /// If set, the rendered content is wrapped in a `<div>` with that `dir` attribute
+    pub text_direction: Option<TextDirection>,
+    /// What to do with footnotes defined more than once
+    pub duplicate_footnotes: DuplicateFootnotes,
+    /// What to do with links and images without a URL
+    pub empty_links: EmptyLinks,
+    /// What to do with headings without any text
+    pub empty_headings: EmptyHeadings,
+    /// If set, the attribution line ending a blockquote is rendered in that element
+    pub blockquote_attribution: Option<BlockquoteAttribution>,
+    /// If set, the tabs indenting lines are expanded to spaces with tab stops every that many columns
+    pub expand_tabs: Option<usize>,
+    /// Whether the whitespace around the output of shortcodes without a body is removed
+    pub trim_inline_shortcodes: bool,
+    /// Whether ordered lists always start at 1 instead of the number of their first item
+    pub normalize_list_start: bool,
+    /// If set, all lists are rendered tight or loose whatever the blank lines between their items
+    pub list_spacing: Option<ListSpacing>,
+    /// If set, the first paragraph of the content that isn't only images gets that class
+    pub lead_paragraph_class: Option<&'a str>,
+    /// Whether shortcodes not found with their name are looked up lowercased
+    pub case_insensitive_shortcodes: bool,
+    /// If set, only the shortcodes with one of those names are rendered, the others are removed
+    pub shortcode_allowlist: Option<Vec<String>>,
 }
 
 impl<'a> RenderContext<'a> {
@@ -37,12 +79,30 @@ impl<'a> RenderContext<'a> {
             tera: Cow::Borrowed(tera),
             tera_context,
             current_page_path: None,
+            source_path: None,
             current_page_permalink,
             permalinks: Cow::Borrowed(permalinks),
             insert_anchor,
+            anchor_min_level: config.markdown.anchor_min_level,
             config,
             lang,
             shortcode_definitions: Cow::Owned(HashMap::new()),
+            autolinks: autolinks_from_config(config),
+            interactive_task_lists: config.markdown.interactive_task_lists,
+            link_bare_emails: config.markdown.link_bare_emails,
+            obfuscate_emails: config.markdown.obfuscate_emails,
+            text_direction: config.markdown.text_direction,
+            duplicate_footnotes: config.markdown.duplicate_footnotes,
+            empty_links: config.markdown.empty_links,
+            empty_headings: config.markdown.empty_headings,
+            blockquote_attribution: config.markdown.blockquote_attribution,
+            expand_tabs: config.markdown.expand_tabs,
+            trim_inline_shortcodes: config.markdown.trim_inline_shortcodes,
+            normalize_list_start: config.markdown.normalize_list_start,
+            list_spacing: config.markdown.list_spacing,
+            lead_paragraph_class: config.markdown.lead_paragraph_class.as_deref(),
+            case_insensitive_shortcodes: config.markdown.case_insensitive_shortcodes,
+            shortcode_allowlist: None,
         }
     }
 
@@ -57,6 +117,136 @@ impl<'a> RenderContext<'a> {
         self.current_page_path = Some(path);
     }
 
+    /// Same as above, used for the last modified note
+    pub fn set_source_path(&mut self, path: &'a Path) {
+        self.source_path = Some(path);
+    }
+
+    /// Overrides `markdown.anchor_min_level` from the config
+    pub fn set_anchor_min_level(&mut self, level: u32) {
+        self.anchor_min_level = level;
+    }
+
+    /// Overrides `markdown.interactive_task_lists` from the config
+    pub fn set_interactive_task_lists(&mut self, interactive: bool) {
+        self.interactive_task_lists = interactive;
+    }
+
+    /// Overrides `markdown.link_bare_emails` from the config
+    pub fn set_link_bare_emails(&mut self, link: bool) {
+        self.link_bare_emails = link;
+    }
+
+    /// Overrides `markdown.obfuscate_emails` from the config
+    pub fn set_obfuscate_emails(&mut self, obfuscate: bool) {
+        self.obfuscate_emails = obfuscate;
+    }
+
+    /// Overrides `markdown.text_direction` from the config
+    pub fn set_text_direction(&mut self, direction: Option<TextDirection>) {
+        self.text_direction = direction;
+    }
+
+    /// Overrides `markdown.duplicate_footnotes` from the config
+    pub fn set_duplicate_footnotes(&mut self, policy: DuplicateFootnotes) {
+        self.duplicate_footnotes = policy;
+    }
+
+    /// Overrides `markdown.empty_links` from the config
+    pub fn set_empty_links(&mut self, policy: EmptyLinks) {
+        self.empty_links = policy;
+    }
+
+    /// Overrides `markdown.empty_headings` from the config
+    pub fn set_empty_headings(&mut self, policy: EmptyHeadings) {
+        self.empty_headings = policy;
+    }
+
+    /// Overrides `markdown.blockquote_attribution` from the config
+    pub fn set_blockquote_attribution(&mut self, attribution: Option<BlockquoteAttribution>) {
+        self.blockquote_attribution = attribution;
+    }
+
+    /// Overrides `markdown.expand_tabs` from the config
+    pub fn set_expand_tabs(&mut self, tab_width: Option<usize>) {
+        self.expand_tabs = tab_width;
+    }
+
+    /// Overrides `markdown.trim_inline_shortcodes` from the config
+    pub fn set_trim_inline_shortcodes(&mut self, trim: bool) {
+        self.trim_inline_shortcodes = trim;
+    }
+
+    /// Overrides `markdown.normalize_list_start` from the config
+    pub fn set_normalize_list_start(&mut self, normalize: bool) {
+        self.normalize_list_start = normalize;
+    }
+
+    /// Overrides `markdown.list_spacing` from the config
+    pub fn set_list_spacing(&mut self, spacing: Option<ListSpacing>) {
+        self.list_spacing = spacing;
+    }
+
+    /// Overrides `markdown.lead_paragraph_class` from the config
+    pub fn set_lead_paragraph_class(&mut self, class: Option<&'a str>) {
+        self.lead_paragraph_class = class;
+    }
+
+    /// Overrides `markdown.case_insensitive_shortcodes` from the config
+    pub fn set_case_insensitive_shortcodes(&mut self, ignore_case: bool) {
+        self.case_insensitive_shortcodes = ignore_case;
+    }
+
+    /// Only renders the shortcodes with one of those names, eg for content written by visitors.
+    /// The other ones are removed, even if they are defined
+    pub fn set_shortcode_allowlist(&mut self, names: Vec<String>) {
+        self.shortcode_allowlist = Some(names);
+    }
+
+    /// Replaces the autolinks set from the config
+    pub fn set_autolinks(&mut self, autolinks: Vec<AutolinkRule>) {
+        self.autolinks = autolinks;
+    }
+
+    /// Builds the breadcrumb of the content at `path`, relative to the `content` directory
+    /// (eg `blog/2021/hello.md`), as `(title, permalink)` entries from the top-most section to
+    /// the content itself. The root section is not included.
+    /// `section_titles` maps the path of the `_index.md` of sections to their title; sections
+    /// without a title or a permalink are skipped.
+    pub fn breadcrumbs(
+        &self,
+        path: &str,
+        title: &str,
+        section_titles: &HashMap<String, String>,
+    ) -> Vec<(String, String)> {
+        let index_file = if self.lang == self.config.default_language {
+            "_index.md".to_string()
+        } else {
+            format!("_index.{}.md", self.lang)
+        };
+        let mut components: Vec<&str> = path.split('/').collect();
+        // The file itself, and the directory it is the index of for sections
+        let filename = components.pop().unwrap_or_default();
+        if filename.starts_with("_index.") {
+            components.pop();
+        }
+
+        let mut breadcrumbs = Vec::with_capacity(components.len() + 1);
+        for i in 1..=components.len() {
+            let section_path = format!("{}/{}", components[..i].join("/"), index_file);
+            if let (Some(title), Some(permalink)) =
+                (section_titles.get(&section_path), self.permalinks.get(&section_path))
+            {
+                breadcrumbs.push((title.clone(), permalink.clone()));
+            }
+        }
+        if let Some(permalink) = self.permalinks.get(path) {
+            breadcrumbs.push((title.to_string(), permalink.clone()));
+        }
+
+        breadcrumbs
+    }
+
     // In use in the markdown filter
     // NOTE: This RenderContext is not i18n-aware, see MarkdownFilter::filter for details
     // If this function is ever used outside of MarkdownFilter, take this into consideration
@@ -65,12 +255,142 @@ impl<'a> RenderContext<'a> {
             tera: Cow::Owned(Tera::default()),
             tera_context: Context::new(),
             current_page_path: None,
+            source_path: None,
             current_page_permalink: "",
             permalinks: Cow::Owned(HashMap::new()),
             insert_anchor: InsertAnchor::None,
+            anchor_min_level: config.markdown.anchor_min_level,
             config,
             lang: &config.default_language,
             shortcode_definitions: Cow::Owned(HashMap::new()),
+            autolinks: autolinks_from_config(config),
+            interactive_task_lists: config.markdown.interactive_task_lists,
+            link_bare_emails: config.markdown.link_bare_emails,
+            obfuscate_emails: config.markdown.obfuscate_emails,
+            text_direction: config.markdown.text_direction,
+            duplicate_footnotes: config.markdown.duplicate_footnotes,
+            empty_links: config.markdown.empty_links,
+            empty_headings: config.markdown.empty_headings,
+            blockquote_attribution: config.markdown.blockquote_attribution,
+            expand_tabs: config.markdown.expand_tabs,
+            trim_inline_shortcodes: config.markdown.trim_inline_shortcodes,
+            normalize_list_start: config.markdown.normalize_list_start,
+            list_spacing: config.markdown.list_spacing,
+            lead_paragraph_class: config.markdown.lead_paragraph_class.as_deref(),
+            case_insensitive_shortcodes: config.markdown.case_insensitive_shortcodes,
+            shortcode_allowlist: None,
         }
     }
 }
+
+/// A copy of a `RenderContext` owning everything it borrows, to render on another thread
+pub(crate) struct OwnedRenderContext {
+    tera: Tera,
+    config: Config,
+    tera_context: Context,
+    current_page_path: Option<String>,
+    source_path: Option<PathBuf>,
+    current_page_permalink: String,
+    permalinks: HashMap<String, String>,
+    insert_anchor: InsertAnchor,
+    anchor_min_level: u32,
+    lang: String,
+    shortcode_definitions: HashMap<String, ShortcodeDefinition>,
+    autolinks: Vec<AutolinkRule>,
+    interactive_task_lists: bool,
+    link_bare_emails: bool,
+    obfuscate_emails: bool,
+    text_direction: Option<TextDirection>,
+    duplicate_footnotes: DuplicateFootnotes,
+    empty_links: EmptyLinks,
+    empty_headings: EmptyHeadings,
+    blockquote_attribution: Option<BlockquoteAttribution>,
+    expand_tabs: Option<usize>,
+    trim_inline_shortcodes: bool,
+    normalize_list_start: bool,
+    list_spacing: Option<ListSpacing>,
+    lead_paragraph_class: Option<String>,
+    case_insensitive_shortcodes: bool,
+    shortcode_allowlist: Option<Vec<String>>,
+}
+
+impl OwnedRenderContext {
+    pub fn new(context: &RenderContext) -> OwnedRenderContext {
+        OwnedRenderContext {
+            tera: context.tera.clone().into_owned(),
+            config: context.config.clone(),
+            tera_context: context.tera_context.clone(),
+            current_page_path: context.current_page_path.map(|p| p.to_string()),
+            source_path: context.source_path.map(|p| p.to_path_buf()),
+            current_page_permalink: context.current_page_permalink.to_string(),
+            permalinks: context.permalinks.clone().into_owned(),
+            insert_anchor: context.insert_anchor,
+            anchor_min_level: context.anchor_min_level,
+            lang: context.lang.to_string(),
+            shortcode_definitions: context.shortcode_definitions.clone().into_owned(),
+            autolinks: context.autolinks.clone(),
+            interactive_task_lists: context.interactive_task_lists,
+            link_bare_emails: context.link_bare_emails,
+            obfuscate_emails: context.obfuscate_emails,
+            text_direction: context.text_direction,
+            duplicate_footnotes: context.duplicate_footnotes,
+            empty_links: context.empty_links,
+            empty_headings: context.empty_headings,
+            blockquote_attribution: context.blockquote_attribution,
+            expand_tabs: context.expand_tabs,
+            trim_inline_shortcodes: context.trim_inline_shortcodes,
+            normalize_list_start: context.normalize_list_start,
+            list_spacing: context.list_spacing,
+            lead_paragraph_class: context.lead_paragraph_class.map(|c| c.to_string()),
+            case_insensitive_shortcodes: context.case_insensitive_shortcodes,
+            shortcode_allowlist: context.shortcode_allowlist.clone(),
+        }
+    }
+
+    /// A `RenderContext` borrowing from this copy
+    pub fn context(&self) -> RenderContext<'_> {
+        RenderContext {
+            tera: Cow::Borrowed(&self.tera),
+            config: &self.config,
+            tera_context: self.tera_context.clone(),
+            current_page_path: self.current_page_path.as_deref(),
+            source_path: self.source_path.as_deref(),
+            current_page_permalink: &self.current_page_permalink,
+            permalinks: Cow::Borrowed(&self.permalinks),
+            insert_anchor: self.insert_anchor,
+            anchor_min_level: self.anchor_min_level,
+            lang: &self.lang,
+            shortcode_definitions: Cow::Borrowed(&self.shortcode_definitions),
+            autolinks: self.autolinks.clone(),
+            interactive_task_lists: self.interactive_task_lists,
+            link_bare_emails: self.link_bare_emails,
+            obfuscate_emails: self.obfuscate_emails,
+            text_direction: self.text_direction,
+            duplicate_footnotes: self.duplicate_footnotes,
+            empty_links: self.empty_links,
+            empty_headings: self.empty_headings,
+            blockquote_attribution: self.blockquote_attribution,
+            expand_tabs: self.expand_tabs,
+            trim_inline_shortcodes: self.trim_inline_shortcodes,
+            normalize_list_start: self.normalize_list_start,
+            list_spacing: self.list_spacing,
+            lead_paragraph_class: self.lead_paragraph_class.as_deref(),
+            case_insensitive_shortcodes: self.case_insensitive_shortcodes,
+            shortcode_allowlist: self.shortcode_allowlist.clone(),
+        }
+    }
+}
+
+fn autolinks_from_config(config: &Config) -> Vec<AutolinkRule> {
+    let mut autolinks = Vec::new();
+    if let Some(ref url) = config.markdown.release_base_url {
+        autolinks.push(AutolinkRule::version_tags(url));
+    }
+    if let Some(ref url) = config.markdown.commit_base_url {
+        autolinks.push(AutolinkRule::commit_hashes(url));
+    }
+    if let Some(ref url) = config.markdown.mention_base_url {
+        autolinks.push(AutolinkRule::mentions(url));
+    }
+    autolinks
+}
@@ -0,0 +1,213 @@
+//! Comparing two `Rendered`, for themes and sites wanting to check what changed between two
+//! renders of the same content in their tests.
+use std::collections::BTreeSet;
+
+use crate::markdown::Rendered;
+use crate::table_of_contents::Heading;
+
+/// A line of the body that is only in one of the two renders
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineChange {
+    /// Line only in the old render, with its line number in it (starting at 1)
+    Removed(usize, String),
+    /// Line only in the new render, with its line number in it (starting at 1)
+    Added(usize, String),
+}
+
+/// Items only found in one of the two renders
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SetDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl SetDiff {
+    fn new(old: impl Iterator<Item = String>, new: impl Iterator<Item = String>) -> SetDiff {
+        let old: BTreeSet<_> = old.collect();
+        let new: BTreeSet<_> = new.collect();
+        SetDiff {
+            added: new.difference(&old).cloned().collect(),
+            removed: old.difference(&new).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// The differences between two `Rendered`, see `diff_rendered`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderedDiff {
+    /// The lines of the body that changed, in order
+    pub body: Vec<LineChange>,
+    /// Whether the position of the summary changed
+    pub summary_changed: bool,
+    /// The headings (as `id: title`) only in one of the tables of contents
+    pub headings: SetDiff,
+    /// Internal links, as `path` or `path#anchor`
+    pub internal_links: SetDiff,
+    pub external_links: SetDiff,
+    pub images: SetDiff,
+}
+
+impl RenderedDiff {
+    /// Whether both renders were identical
+    pub fn is_empty(&self) -> bool {
+        self.body.is_empty() && self.only_body_changed()
+    }
+
+    /// Whether nothing except the body is different, including when nothing changed
+    pub fn only_body_changed(&self) -> bool {
+        !self.summary_changed
+            && self.headings.is_empty()
+            && self.internal_links.is_empty()
+            && self.external_links.is_empty()
+            && self.images.is_empty()
+    }
+}
+
+fn flatten_headings(headings: &[Heading], out: &mut Vec<String>) {
+    for h in headings {
+        out.push(format!("{}: {}", h.id, h.title));
+        flatten_headings(&h.children, out);
+    }
+}
+
+fn headings_of(rendered: &Rendered) -> impl Iterator<Item = String> {
+    let mut headings = Vec::new();
+    flatten_headings(&rendered.toc, &mut headings);
+    headings.into_iter()
+}
+
+fn internal_links_of(rendered: &Rendered) -> impl Iterator<Item = String> + '_ {
+    rendered.internal_links.iter().map(|(path, anchor)| match anchor {
+        Some(anchor) => format!("{}#{}", path, anchor),
+        None => path.clone(),
+    })
+}
+
+/// A line diff of the two texts, based on their longest common subsequence
+fn diff_lines(old: &str, new: &str) -> Vec<LineChange> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            changes.push(LineChange::Added(j + 1, new[j].to_string()));
+            j += 1;
+        } else {
+            changes.push(LineChange::Removed(i + 1, old[i].to_string()));
+            i += 1;
+        }
+    }
+
+    changes
+}
+
+/// Compares two renders of some content, eg to assert that a change only affected the body
+pub fn diff_rendered(old: &Rendered, new: &Rendered) -> RenderedDiff {
+    RenderedDiff {
+        body: diff_lines(&old.body, &new.body),
+        summary_changed: old.summary_len != new.summary_len,
+        headings: SetDiff::new(headings_of(old), headings_of(new)),
+        internal_links: SetDiff::new(internal_links_of(old), internal_links_of(new)),
+        external_links: SetDiff::new(
+            old.external_links.iter().cloned(),
+            new.external_links.iter().cloned(),
+        ),
+        images: SetDiff::new(old.images.iter().cloned(), new.images.iter().cloned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(body: &str) -> Rendered {
+        Rendered {
+            body: body.to_string(),
+            summary_len: None,
+            toc: vec![Heading {
+                level: 1,
+                id: "hello".to_string(),
+                permalink: "/#hello".to_string(),
+                title: "Hello".to_string(),
+                word_count: None,
+                children: vec![],
+            }],
+            toc_json: None,
+            internal_links: vec![("about.md".to_string(), None)],
+            external_links: vec!["https://getzola.org".to_string()],
+            images: vec![],
+            cover_image: None,
+            text_direction: None,
+            article_microdata: false,
+            heading_sections: false,
+            progress_map: None,
+        }
+    }
+
+    #[test]
+    fn can_diff_identical_renders() {
+        let diff = diff_rendered(&rendered("<p>a</p>\n"), &rendered("<p>a</p>\n"));
+        assert!(diff.is_empty());
+        assert_eq!(diff, RenderedDiff::default());
+    }
+
+    #[test]
+    fn can_diff_body_only() {
+        let old = rendered("<h1>Hello</h1>\n<p>a</p>\n<p>b</p>\n");
+        let new = rendered("<h1>Hello</h1>\n<p>c</p>\n<p>b</p>\n<p>d</p>\n");
+        let diff = diff_rendered(&old, &new);
+        assert!(!diff.is_empty());
+        assert!(diff.only_body_changed());
+        assert_eq!(
+            diff.body,
+            vec![
+                LineChange::Added(2, "<p>c</p>".to_string()),
+                LineChange::Removed(2, "<p>a</p>".to_string()),
+                LineChange::Added(4, "<p>d</p>".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_diff_headings() {
+        let old = rendered("<p>a</p>\n");
+        let mut new = rendered("<p>a</p>\n");
+        new.toc[0].children.push(Heading {
+            level: 2,
+            id: "world".to_string(),
+            permalink: "/#world".to_string(),
+            title: "World".to_string(),
+            word_count: None,
+            children: vec![],
+        });
+        let diff = diff_rendered(&old, &new);
+        assert!(!diff.only_body_changed());
+        assert_eq!(
+            diff,
+            RenderedDiff {
+                headings: SetDiff { added: vec!["world: World".to_string()], removed: vec![] },
+                ..RenderedDiff::default()
+            }
+        );
+    }
+}
@@ -1,34 +1,115 @@
+mod code_samples;
 mod codeblock;
 mod context;
+mod diff;
 mod markdown;
 mod shortcode;
+mod style;
 mod table_of_contents;
+mod transform;
 
-use shortcode::{extract_shortcodes, insert_md_shortcodes};
+use std::borrow::Cow;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
-use errors::Result;
+use shortcode::{extract_shortcodes, hide_code, insert_md_shortcodes, restore_code, Shortcode};
 
+use context::OwnedRenderContext;
+use errors::{bail, Error, Result};
+
+pub use code_samples::{extract_code_blocks, CodeBlock};
+pub use codeblock::{highlight_code, HighlightOptions};
 pub use context::RenderContext;
-use markdown::markdown_to_html;
+pub use diff::{diff_rendered, LineChange, RenderedDiff, SetDiff};
 pub use markdown::Rendered;
+use markdown::{
+    expand_leading_tabs, markdown_heading_ids, markdown_to_html, markdown_to_owned_events,
+    summary_source,
+};
+pub use pulldown_cmark::{CodeBlockKind, CowStr, Event, LinkType, Tag};
+pub use shortcode::SHORTCODE_PLACEHOLDER;
 pub use table_of_contents::Heading;
+pub use transform::AutolinkRule;
+
+/// The steps of `render_content`, to test shortcodes outside of this crate.
+/// Shortcodes can be built with `Shortcode::new` instead of being extracted from the content
+#[cfg(feature = "testing")]
+pub mod testing {
+    pub use crate::markdown::markdown_to_html;
+    pub use crate::shortcode::{insert_md_shortcodes, Shortcode, SHORTCODE_PLACEHOLDER};
+}
+
+/// Renders the MD shortcodes of the content and returns it with the HTML shortcodes,
+/// which are rendered along the markdown
+fn render_md_shortcodes(
+    content: &str,
+    context: &RenderContext,
+) -> Result<(String, Vec<Shortcode>)> {
+    let definitions = context.shortcode_definitions.as_ref();
+    let fallback = context.config.markdown.unknown_shortcode_template.as_deref();
+    let ignore_case = context.case_insensitive_shortcodes;
+    let allowlist = context.shortcode_allowlist.as_deref();
+    if !context.config.markdown.protect_code_from_shortcodes {
+        // Extract all the defined shortcodes
+        let (content, shortcodes) =
+            extract_shortcodes(content, definitions, fallback, ignore_case, allowlist)?;
+
+        // Step 1: we render the MD shortcodes before rendering the markdown so they can get processed
+        return insert_md_shortcodes(content, shortcodes, context);
+    }
+
+    // The code is hidden while looking for shortcodes so anything in it is left as written
+    let (content, code) = hide_code(content);
+    let (content, mut shortcodes) =
+        extract_shortcodes(&content, definitions, fallback, ignore_case, allowlist)?;
+    for sc in &mut shortcodes {
+        if let Some(body) = sc.body.as_mut() {
+            *body = restore_code(body, &code, &mut []);
+        }
+    }
+    let (content, mut html_shortcodes) = insert_md_shortcodes(content, shortcodes, context)?;
+    let content = restore_code(&content, &code, &mut html_shortcodes);
+    Ok((content, html_shortcodes))
+}
+
+/// Expands the tabs indenting the lines of the content if `markdown.expand_tabs` is set
+fn expand_tabs<'a>(content: &'a str, context: &RenderContext) -> Cow<'a, str> {
+    match context.expand_tabs {
+        Some(tab_width) => expand_leading_tabs(content, tab_width),
+        None => Cow::Borrowed(content),
+    }
+}
+
+/// Errors if the rendered HTML is bigger than `markdown.max_output_size`
+fn check_output_size(rendered: &markdown::Rendered, context: &RenderContext) -> Result<()> {
+    match context.config.markdown.max_output_size {
+        Some(max) if rendered.body.len() > max => bail!(
+            "Rendering {} produced {} bytes of HTML, more than the {} allowed by `markdown.max_output_size`",
+            context.current_page_path.unwrap_or("the content"),
+            rendered.body.len(),
+            max
+        ),
+        _ => Ok(()),
+    }
+}
 
 pub fn render_content(content: &str, context: &RenderContext) -> Result<markdown::Rendered> {
+    let content = expand_tabs(content, context);
+    let content = content.as_ref();
+
     // avoid parsing the content if needed
     if !content.contains("{{") && !content.contains("{%") {
-        return markdown_to_html(content, context, Vec::new());
+        let rendered = markdown_to_html(content, context, Vec::new())?;
+        check_output_size(&rendered, context)?;
+        return Ok(rendered);
     }
 
-    let definitions = context.shortcode_definitions.as_ref();
-    // Extract all the defined shortcodes
-    let (content, shortcodes) = extract_shortcodes(content, definitions)?;
-
-    // Step 1: we render the MD shortcodes before rendering the markdown so they can get processed
-    let (content, html_shortcodes) =
-        insert_md_shortcodes(content, shortcodes, &context.tera_context, &context.tera)?;
+    let (content, html_shortcodes) = render_md_shortcodes(content, context)?;
 
     // Step 2: we render the markdown and the HTML markdown at the same time
     let html_context = markdown_to_html(&content, context, html_shortcodes)?;
+    check_output_size(&html_context, context)?;
 
     // TODO: Here issue #1418 could be implemented
     // if do_warn_about_unprocessed_md {
@@ -37,3 +118,90 @@ pub fn render_content(content: &str, context: &RenderContext) -> Result<markdown
 
     Ok(html_context)
 }
+
+/// Renders only the summary of the content, for listings not needing the rest of it: the summary
+/// from the front matter if given, otherwise the part before the `<!-- more -->` marker if there
+/// is one, otherwise the first paragraph.
+/// Footnotes and link reference definitions written after the summary are not available to it.
+pub fn render_summary(
+    content: &str,
+    front_matter_summary: Option<&str>,
+    context: &RenderContext,
+) -> Result<String> {
+    if let Some(summary) = front_matter_summary {
+        return Ok(render_content(summary, context)?.body);
+    }
+
+    let content = expand_tabs(content, context);
+    match summary_source(content.as_ref()) {
+        Some(summary) => Ok(render_content(summary, context)?.body),
+        None => Ok(String::new()),
+    }
+}
+
+/// Same as `render_content` but errors if the rendering takes longer than `timeout`, eg because
+/// of a shortcode stuck in a loop. The rendering happens on another thread with a copy of the
+/// context. Threads can't be stopped so after a timeout it keeps going in the background
+/// until it is done, its result being dropped.
+pub fn render_content_with_timeout(
+    content: &str,
+    context: &RenderContext,
+    timeout: Duration,
+) -> Result<markdown::Rendered> {
+    let owned = OwnedRenderContext::new(context);
+    let content = content.to_string();
+    let (sender, receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("render_content".to_string())
+        .spawn(move || {
+            // The receiver is gone if it timed out already
+            let _ = sender.send(render_content(&content, &owned.context()));
+        })
+        .map_err(|e| Error::chain("Failed to start the rendering thread", e))?;
+
+    match receiver.recv_timeout(timeout) {
+        Ok(res) => res,
+        Err(mpsc::RecvTimeoutError::Timeout) => bail!(
+            "Rendering {} took more than {}ms",
+            context.current_page_path.unwrap_or("the content"),
+            timeout.as_millis()
+        ),
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            bail!(
+                "The thread rendering {} panicked",
+                context.current_page_path.unwrap_or("the content")
+            )
+        }
+    }
+}
+
+/// Goes through the same steps as `render_content` but returns the markdown events instead
+/// of the HTML, for people wanting to render the content in another format.
+/// Shortcodes are already rendered and show up as `Event::Html`, as do the headings
+/// with their ids and the highlighted code blocks.
+/// The `style` attributes of the HTML events are not filtered by `markdown.style_attributes`
+/// as that needs the whole HTML rendered from them.
+pub fn render_events(content: &str, context: &RenderContext) -> Result<Vec<Event<'static>>> {
+    let content = expand_tabs(content, context);
+    let content = content.as_ref();
+
+    if !content.contains("{{") && !content.contains("{%") {
+        return markdown_to_owned_events(content, context, Vec::new());
+    }
+
+    let (content, html_shortcodes) = render_md_shortcodes(content, context)?;
+    markdown_to_owned_events(&content, context, html_shortcodes)
+}
+
+/// The ids of the headings of the content, in order, as `render_content` would give them.
+/// Headings with the same title get a numbered suffix, eg `hello`, `hello-1`
+pub fn heading_ids(content: &str, context: &RenderContext) -> Result<Vec<String>> {
+    let content = expand_tabs(content, context);
+    let content = content.as_ref();
+    if !content.contains("{{") && !content.contains("{%") {
+        return markdown_heading_ids(content, context, Vec::new());
+    }
+
+    let (content, html_shortcodes) = render_md_shortcodes(content, context)?;
+    markdown_heading_ids(&content, context, html_shortcodes)
+}
@@ -1,30 +1,103 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
 use lazy_static::lazy_static;
 use pulldown_cmark as cmark;
+use regex::Regex;
+use tera::to_value;
 
 use crate::context::RenderContext;
 use crate::table_of_contents::{make_table_of_contents, Heading};
+use config::{
+    BlockquoteAttribution, DuplicateFootnotes, EmptyHeadings, EmptyLinks, ImageCaptions,
+    LastModifiedPosition, ListSpacing, MeasurementQuotes, TextDirection,
+};
 use errors::{Error, Result};
 use front_matter::InsertAnchor;
-use utils::site::resolve_internal_link;
+use utils::site::{get_reading_analytics, resolve_internal_link};
 use utils::slugs::slugify_anchors;
 use utils::vec::InsertMany;
 
-use self::cmark::{Event, LinkType, Options, Parser, Tag};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+
+use self::cmark::{CodeBlockKind, CowStr, Event, LinkType, Options, Parser, Tag};
 use crate::codeblock::{CodeBlock, FenceSettings};
-use crate::shortcode::{Shortcode, SHORTCODE_PLACEHOLDER};
+use crate::shortcode::{render_shortcode, Shortcode, SHORTCODE_PLACEHOLDER};
+use crate::style::sanitize_style_attributes;
+use crate::transform::{
+    attribute_blockquotes, autolink, caption_images, expand_table_cells, fix_measurement_quotes,
+    group_code_tabs, link_bare_emails, mark_external_links, mark_lead_paragraph,
+    mark_rtl_paragraphs, merge_adjacent_blockquotes, normalize_combined_emphasis,
+    normalize_list_starts, remove_duplicate_footnotes, remove_empty_headings, remove_empty_links,
+    truncate_autolinks, unescape_table_code_pipes, wrap_tables, GroupedCodeBlock,
+};
+
+/// The characters percent-encoded in URL fragments, from the URL standard, and `%` so the
+/// fragment can be decoded back to the id
+const FRAGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`').add(b'%');
 
 const CONTINUE_READING: &str = "<span id=\"continue-reading\"></span>";
 const ANCHOR_LINK_TEMPLATE: &str = "anchor-link.html";
 
+lazy_static! {
+    static ref IMG_SRC_RE: Regex =
+        Regex::new(r#"(?i)<img\s[^>]*?\bsrc\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    static ref HTML_TAG_RE: Regex = Regex::new(r"(?i)<(/?)([a-z][a-z0-9-]*)[^>]*?(/?)>").unwrap();
+}
+
 #[derive(Debug)]
 pub struct Rendered {
     pub body: String,
     pub summary_len: Option<usize>,
     pub toc: Vec<Heading>,
+    /// The table of contents serialized as JSON if `markdown.toc_json` is set,
+    /// for scripts building it client-side
+    pub toc_json: Option<String>,
     /// Links to site-local pages: relative path plus optional anchor target.
     pub internal_links: Vec<(String, Option<String>)>,
     /// Outgoing links to external webpages (i.e. HTTP(S) targets).
     pub external_links: Vec<String>,
+    /// The `src` of every image in the content, in order of appearance.
+    /// This includes images from raw HTML and shortcodes.
+    pub images: Vec<String>,
+    /// The `src` of the first image of the content not inside one of the elements listed in
+    /// `markdown.cover_image_ignored_containers`, meant to be used as a cover or thumbnail.
+    pub cover_image: Option<String>,
+    /// The `dir` of the `<div>` the body is wrapped in, if any
+    pub text_direction: Option<TextDirection>,
+    /// Whether the body is wrapped in a `<div>` with the schema.org `Article` microdata
+    pub article_microdata: bool,
+    /// Whether the headings and what follows them are wrapped in `<section>` elements
+    pub heading_sections: bool,
+    /// The id of every heading, in order, with where it starts in the body as a fraction of
+    /// its length, if `markdown.heading_progress_map` is set
+    pub progress_map: Option<Vec<(String, f64)>>,
+}
+
+impl Rendered {
+    /// The part of the body before the `<!-- more -->` marker if there is one,
+    /// closing the heading sections, direction and microdata wrappers if the body has them
+    pub fn summary(&self) -> Option<String> {
+        self.summary_len.map(|l| {
+            let mut summary = self.body[0..l].to_string();
+            if self.heading_sections {
+                let opened = summary.matches("<section role=\"region\"").count();
+                let closed = summary.matches("</section>").count();
+                for _ in closed..opened {
+                    summary.push_str("</section>\n");
+                }
+            }
+            if self.text_direction.is_some() {
+                summary.push_str("</div>\n");
+            }
+            if self.article_microdata {
+                summary.push_str("</div>\n");
+            }
+            summary
+        })
+    }
 }
 
 /// Tracks a heading in a slice of pulldown-cmark events
@@ -34,11 +107,13 @@ struct HeadingRef {
     end_idx: usize,
     level: u32,
     id: Option<String>,
+    /// Whether the heading is outside of any other block, like a list or a blockquote
+    top_level: bool,
 }
 
 impl HeadingRef {
-    fn new(start: usize, level: u32) -> HeadingRef {
-        HeadingRef { start_idx: start, end_idx: 0, level, id: None }
+    fn new(start: usize, level: u32, top_level: bool) -> HeadingRef {
+        HeadingRef { start_idx: start, end_idx: 0, level, id: None, top_level }
     }
 }
 
@@ -60,10 +135,23 @@ fn find_anchor(anchors: &[String], name: String, level: u16) -> String {
 }
 
 /// Returns whether a link starts with an HTTP(s) scheme.
-fn is_external_link(link: &str) -> bool {
+pub(crate) fn is_external_link(link: &str) -> bool {
     link.starts_with("http:") || link.starts_with("https:")
 }
 
+/// Adds a trailing slash to the path of the link if it looks like a page, eg `/page` or
+/// `../page#anchor` but not `/file.pdf` or `mailto:...`
+fn add_trailing_slash(link: &str) -> String {
+    let path_end = link.find(&['?', '#'][..]).unwrap_or(link.len());
+    let (path, rest) = link.split_at(path_end);
+    let last_segment = path.rsplit('/').next().unwrap_or_default();
+    if path.is_empty() || path.ends_with('/') || path.contains(':') || last_segment.contains('.') {
+        return link.to_string();
+    }
+
+    format!("{}/{}", path, rest)
+}
+
 fn fix_link(
     link_type: LinkType,
     link: &str,
@@ -101,6 +189,8 @@ fn fix_link(
             } else {
                 link.to_string()
             }
+        } else if context.config.markdown.trailing_slash_links {
+            add_trailing_slash(link)
         } else {
             link.to_string()
         }
@@ -109,6 +199,69 @@ fn fix_link(
     Ok(result)
 }
 
+/// Expands the tabs in the indentation of the lines of the content to spaces, with tab stops
+/// every `tab_width` columns. The lines of fenced code blocks are left as is.
+pub fn expand_leading_tabs(content: &str, tab_width: usize) -> Cow<'_, str> {
+    if !content.contains('\t') || tab_width == 0 {
+        return Cow::Borrowed(content);
+    }
+
+    let mut out = String::with_capacity(content.len());
+    // The character and length of the fence of the code block we are in
+    let mut fence: Option<(char, usize)> = None;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        let marker_len = marker.map(|c| trimmed.chars().take_while(|x| *x == c).count());
+
+        if let Some((c, len)) = fence {
+            if marker == Some(c)
+                && marker_len.unwrap_or(0) >= len
+                && trimmed.trim_start_matches(c).trim().is_empty()
+            {
+                fence = None;
+            } else {
+                out.push_str(line);
+                continue;
+            }
+        } else if let (Some(c), Some(len)) = (marker, marker_len) {
+            if len >= 3 {
+                fence = Some((c, len));
+            }
+        }
+
+        let indent = &line[..line.len() - trimmed.len()];
+        let mut column = 0;
+        for c in indent.chars() {
+            match c {
+                '\t' => {
+                    let spaces = tab_width - column % tab_width;
+                    out.push_str(&" ".repeat(spaces));
+                    column += spaces;
+                }
+                '\n' | '\r' => out.push(c),
+                _ => {
+                    out.push(c);
+                    column += 1;
+                }
+            }
+        }
+        out.push_str(trimmed);
+    }
+
+    Cow::Owned(out)
+}
+
+/// Finds the custom id ending the text of a heading, eg `{#hash}`, returning where the text
+/// before it ends and the id, which can be empty
+fn find_custom_id(text: &str) -> Option<(usize, &str)> {
+    if !text.ends_with('}') {
+        return None;
+    }
+    let i = text.find("{#")?;
+    Some((text[..i].trim_end_matches(' ').len(), &text[i + 2..text.len() - 1]))
+}
+
 /// get only text in a slice of events
 fn get_text(parser_slice: &[Event]) -> String {
     let mut title = String::new();
@@ -123,17 +276,151 @@ fn get_text(parser_slice: &[Event]) -> String {
     title
 }
 
+/// The number of words in the section of each heading, ie until the next heading of the same
+/// or a higher level, including its subsections
+fn get_section_word_counts(events: &[Event], heading_refs: &[HeadingRef]) -> Vec<usize> {
+    heading_refs
+        .iter()
+        .enumerate()
+        .map(|(i, heading_ref)| {
+            let end = heading_refs[i + 1..]
+                .iter()
+                .find(|next| next.level <= heading_ref.level)
+                .map(|next| next.start_idx)
+                .unwrap_or_else(|| events.len());
+            let text = events[heading_ref.end_idx + 1..end]
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            get_reading_analytics(&text).0
+        })
+        .collect()
+}
+
+/// get the `src` of all the images in a slice of events, whether they come from markdown or HTML
+fn get_image_sources(events: &[Event]) -> Vec<String> {
+    let mut sources = Vec::new();
+
+    for event in events.iter() {
+        match event {
+            Event::Start(Tag::Image(_, src, _)) => sources.push(src.to_string()),
+            Event::Html(html) => {
+                for caps in IMG_SRC_RE.captures_iter(html) {
+                    let src = caps.get(1).or_else(|| caps.get(2)).unwrap();
+                    sources.push(src.as_str().to_owned());
+                }
+            }
+            _ => continue,
+        }
+    }
+
+    sources
+}
+
+/// The name of the HTML element a markdown tag is rendered as, for the ones that can contain images
+fn html_element_name(tag: &Tag) -> Option<&'static str> {
+    match tag {
+        Tag::Paragraph => Some("p"),
+        Tag::Heading(1) => Some("h1"),
+        Tag::Heading(2) => Some("h2"),
+        Tag::Heading(3) => Some("h3"),
+        Tag::Heading(4) => Some("h4"),
+        Tag::Heading(5) => Some("h5"),
+        Tag::Heading(_) => Some("h6"),
+        Tag::BlockQuote => Some("blockquote"),
+        Tag::List(None) => Some("ul"),
+        Tag::List(Some(_)) => Some("ol"),
+        Tag::Item => Some("li"),
+        Tag::Table(_) => Some("table"),
+        Tag::Link(..) => Some("a"),
+        _ => None,
+    }
+}
+
+/// get the `src` of the first image in a slice of events that isn't in one of the `ignored`
+/// elements, whether they come from markdown or HTML
+fn get_cover_image(events: &[Event], ignored: &[String]) -> Option<String> {
+    let is_ignored = |name: &str| ignored.iter().any(|i| i.eq_ignore_ascii_case(name));
+    // How many ignored elements we are currently in
+    let mut depth = 0usize;
+
+    for event in events.iter() {
+        match event {
+            Event::Start(Tag::Image(_, src, _)) if depth == 0 => return Some(src.to_string()),
+            Event::Start(tag) if html_element_name(tag).map(is_ignored).unwrap_or(false) => {
+                depth += 1
+            }
+            Event::End(tag) if html_element_name(tag).map(is_ignored).unwrap_or(false) => {
+                depth = depth.saturating_sub(1)
+            }
+            Event::Html(html) => {
+                for caps in HTML_TAG_RE.captures_iter(html) {
+                    let name = &caps[2];
+                    if name.eq_ignore_ascii_case("img") {
+                        if depth == 0 {
+                            if let Some(src) = IMG_SRC_RE.captures(&caps[0]) {
+                                return Some(
+                                    src.get(1).or_else(|| src.get(2)).unwrap().as_str().to_owned(),
+                                );
+                            }
+                        }
+                    } else if is_ignored(name) {
+                        if !caps[1].is_empty() {
+                            depth = depth.saturating_sub(1);
+                        } else if caps[3].is_empty() {
+                            depth += 1;
+                        }
+                    }
+                }
+            }
+            _ => continue,
+        }
+    }
+
+    None
+}
+
+/// Where to insert the `<section>` elements wrapping each top level heading and what follows it
+/// until the next heading of the same or a higher level, from the index, level and id of the
+/// headings. The sections are labelled by their heading for assistive technologies.
+fn heading_sections<'a>(headings: &[(usize, u32, String)], len: usize) -> Vec<(usize, Event<'a>)> {
+    let mut inserts = Vec::new();
+    let mut open_levels: Vec<u32> = Vec::new();
+    for (idx, level, id) in headings {
+        while open_levels.last().map(|l| l >= level).unwrap_or(false) {
+            open_levels.pop();
+            inserts.push((*idx, Event::Html("</section>\n".into())));
+        }
+        let opening = format!("<section role=\"region\" aria-labelledby=\"{}\">\n", id);
+        inserts.push((*idx, Event::Html(opening.into())));
+        open_levels.push(*level);
+    }
+    for _ in open_levels {
+        inserts.push((len, Event::Html("</section>\n".into())));
+    }
+    inserts
+}
+
 fn get_heading_refs(events: &[Event]) -> Vec<HeadingRef> {
     let mut heading_refs = vec![];
+    let mut depth = 0;
 
     for (i, event) in events.iter().enumerate() {
         match event {
             Event::Start(Tag::Heading(level)) => {
-                heading_refs.push(HeadingRef::new(i, *level));
+                heading_refs.push(HeadingRef::new(i, *level, depth == 0));
+                depth += 1;
             }
             Event::End(Tag::Heading(_)) => {
                 heading_refs.last_mut().expect("Heading end before start?").end_idx = i;
+                depth -= 1;
             }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
             _ => (),
         }
     }
@@ -141,11 +428,95 @@ fn get_heading_refs(events: &[Event]) -> Vec<HeadingRef> {
     heading_refs
 }
 
-pub fn markdown_to_html(
-    content: &str,
+/// What we gathered while going through the markdown, before turning it into HTML
+struct MarkdownEvents<'a> {
+    events: Vec<Event<'a>>,
+    has_summary: bool,
+    headings: Vec<Heading>,
+    internal_links: Vec<(String, Option<String>)>,
+    external_links: Vec<String>,
+}
+
+/// Whether the event is part of the content of a paragraph
+fn is_inline(event: &Event) -> bool {
+    match event {
+        Event::Start(tag) | Event::End(tag) => matches!(
+            tag,
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..)
+        ),
+        Event::Rule => false,
+        _ => true,
+    }
+}
+
+/// The elements open while changing the spacing of lists
+#[derive(PartialEq)]
+enum OpenElement {
+    Item,
+    /// A paragraph of an item whose tags were removed
+    RemovedParagraph,
+    /// A paragraph added around the content of an item
+    AddedParagraph,
+    Other,
+}
+
+/// Renders all the lists of the parser events as tight lists, without `<p>` in their items,
+/// if `tight` is set, or as loose lists otherwise, whatever the blank lines between their items
+fn set_list_spacing<'a>(
+    events: Vec<(Event<'a>, Range<usize>)>,
+    tight: bool,
+) -> Vec<(Event<'a>, Range<usize>)> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut open: Vec<OpenElement> = Vec::new();
+    let mut events = events.into_iter().peekable();
+
+    while let Some((event, range)) = events.next() {
+        if open.last() == Some(&OpenElement::AddedParagraph) && !is_inline(&event) {
+            out.push((Event::End(Tag::Paragraph), range.start..range.start));
+            open.pop();
+        }
+
+        match event {
+            Event::Start(Tag::Paragraph) if tight && open.last() == Some(&OpenElement::Item) => {
+                open.push(OpenElement::RemovedParagraph);
+                continue;
+            }
+            Event::End(Tag::Paragraph) if open.last() == Some(&OpenElement::RemovedParagraph) => {
+                open.pop();
+                // The paragraphs of an item still need to be separated
+                if !matches!(events.peek(), Some((Event::End(Tag::Item), _))) {
+                    out.push((Event::SoftBreak, range.end..range.end));
+                }
+                continue;
+            }
+            _ => (),
+        }
+
+        if !tight && open.last() == Some(&OpenElement::Item) && is_inline(&event) {
+            out.push((Event::Start(Tag::Paragraph), range.start..range.start));
+            open.push(OpenElement::AddedParagraph);
+        }
+        match event {
+            Event::Start(Tag::Item) => open.push(OpenElement::Item),
+            Event::Start(_) => open.push(OpenElement::Other),
+            Event::End(_) => {
+                open.pop();
+            }
+            _ => (),
+        }
+        out.push((event, range));
+    }
+
+    out
+}
+
+/// Turns the markdown into the events that will be given to the HTML renderer:
+/// shortcodes are rendered, headings get their ids and code blocks are highlighted
+fn markdown_to_events<'a>(
+    content: &'a str,
     context: &RenderContext,
     html_shortcodes: Vec<Shortcode>,
-) -> Result<Rendered> {
+) -> Result<MarkdownEvents<'a>> {
     lazy_static! {
         static ref EMOJI_REPLACER: gh_emoji::Replacer = gh_emoji::Replacer::new();
     }
@@ -155,12 +526,16 @@ pub fn markdown_to_html(
         .get("page")
         .or_else(|| context.tera_context.get("section"))
         .map(|x| x.as_object().unwrap().get("relative_path").unwrap().as_str().unwrap());
-    // the rendered html
-    let mut html = String::with_capacity(content.len());
     // Set while parsing
     let mut error = None;
 
     let mut code_block: Option<CodeBlock> = None;
+    // The group and label of the current code block and the index of its first event, if it is
+    // part of a tab group
+    let mut code_block_group: Option<(String, String, usize)> = None;
+    let mut grouped_code_blocks: Vec<GroupedCodeBlock> = Vec::new();
+    // How many task list checkboxes we've seen, to give them an id in interactive mode
+    let mut task_count = 0;
 
     let mut inserted_anchors: Vec<String> = vec![];
     let mut headings: Vec<Heading> = vec![];
@@ -168,6 +543,7 @@ pub fn markdown_to_html(
     let mut external_links = Vec::new();
 
     let mut stop_next_end_p = false;
+    let mut in_table_cell = false;
 
     let mut opts = Options::empty();
     let mut has_summary = false;
@@ -185,8 +561,8 @@ pub fn markdown_to_html(
     let mut next_shortcode = html_shortcodes.pop();
     let contains_shortcode = |txt: &str| -> bool { txt.contains(SHORTCODE_PLACEHOLDER) };
 
+    let mut events = Vec::new();
     {
-        let mut events = Vec::new();
         macro_rules! render_shortcodes {
             ($is_text:expr, $text:expr, $range:expr) => {
                 let orig_range_start = $range.start;
@@ -213,7 +589,7 @@ pub fn markdown_to_html(
 
                         // Now we should be at the same idx as the shortcode
                         let shortcode = next_shortcode.take().unwrap();
-                        match shortcode.render(&context.tera, &context.tera_context) {
+                        match render_shortcode(shortcode, context) {
                             Ok(s) => {
                                 events.push(Event::Html(s.into()));
                                 $range.start += SHORTCODE_PLACEHOLDER.len();
@@ -238,7 +614,20 @@ pub fn markdown_to_html(
             };
         }
 
-        for (event, mut range) in Parser::new_ext(content, opts).into_offset_iter() {
+        let mut parsed: Vec<_> = Parser::new_ext(content, opts).into_offset_iter().collect();
+        match context.list_spacing {
+            Some(ListSpacing::Tight) => parsed = set_list_spacing(parsed, true),
+            Some(ListSpacing::Loose) => parsed = set_list_spacing(parsed, false),
+            None => (),
+        }
+
+        for (event, mut range) in parsed {
+            match event {
+                Event::Start(Tag::TableCell) => in_table_cell = true,
+                Event::End(Tag::TableCell) => in_table_cell = false,
+                _ => (),
+            }
+
             match event {
                 Event::Text(text) => {
                     if let Some(ref mut code_block) = code_block {
@@ -277,7 +666,24 @@ pub fn markdown_to_html(
                         };
 
                         if !contains_shortcode(text.as_ref()) {
-                            events.push(Event::Text(text));
+                            match context.config.markdown.table_cell_line_break {
+                                Some(ref marker)
+                                    if in_table_cell && text.contains(marker.as_str()) =>
+                                {
+                                    // An escaped character starts a new text event, right after the backslash
+                                    let escaped = content[..range.start].ends_with('\\');
+                                    for (i, line) in split_cell_lines(&text, marker, escaped)
+                                        .into_iter()
+                                        .enumerate()
+                                    {
+                                        if i > 0 {
+                                            events.push(Event::Html("<br>".into()));
+                                        }
+                                        events.push(Event::Text(line.to_owned().into()));
+                                    }
+                                }
+                                _ => events.push(Event::Text(text)),
+                            }
                             continue;
                         }
 
@@ -289,6 +695,12 @@ pub fn markdown_to_html(
                         cmark::CodeBlockKind::Fenced(fence_info) => FenceSettings::new(fence_info),
                         _ => FenceSettings::new(""),
                     };
+                    if context.config.markdown.code_tab_groups {
+                        code_block_group = fence.group.map(|group| {
+                            let label = fence.language.unwrap_or("text");
+                            (group.to_string(), label.to_string(), events.len())
+                        });
+                    }
                     let (block, begin) = CodeBlock::new(fence, context.config, path);
                     code_block = Some(block);
                     events.push(Event::Html(begin.into()));
@@ -297,10 +709,21 @@ pub fn markdown_to_html(
                     // reset highlight and close the code block
                     code_block = None;
                     events.push(Event::Html("</code></pre>\n".into()));
+                    if let Some((group, label, start)) = code_block_group.take() {
+                        grouped_code_blocks.push(GroupedCodeBlock {
+                            group,
+                            label,
+                            events: start..events.len(),
+                        });
+                    }
                 }
                 Event::Start(Tag::Link(link_type, link, title)) if link.is_empty() => {
-                    error = Some(Error::msg("There is a link that is missing a URL"));
-                    events.push(Event::Start(Tag::Link(link_type, "#".into(), title)));
+                    if context.empty_links == EmptyLinks::Error {
+                        error = Some(Error::msg("There is a link that is missing a URL"));
+                        events.push(Event::Start(Tag::Link(link_type, "#".into(), title)));
+                    } else {
+                        events.push(Event::Start(Tag::Link(link_type, link, title)));
+                    }
                 }
                 Event::Start(Tag::Link(link_type, link, title)) => {
                     let fixed_link = match fix_link(
@@ -364,6 +787,27 @@ pub fn markdown_to_html(
                         event
                     });
                 }
+                Event::TaskListMarker(checked) if context.interactive_task_lists => {
+                    task_count += 1;
+                    let checked = if checked { " checked=\"\"" } else { "" };
+                    events.push(Event::Html(
+                        format!(
+                            "<input id=\"task-{}\" type=\"checkbox\"{}/>\n",
+                            task_count, checked
+                        )
+                        .into(),
+                    ));
+                }
+                Event::Rule => {
+                    if let Some(ref class) = context.config.markdown.horizontal_rule_class {
+                        let mut escaped = String::new();
+                        cmark::escape::escape_html(&mut escaped, class)
+                            .expect("Could not write to buffer");
+                        events.push(Event::Html(format!("<hr class=\"{}\" />\n", escaped).into()));
+                    } else {
+                        events.push(event);
+                    }
+                }
                 Event::Html(text) => {
                     if text.contains("<!-- more -->") {
                         has_summary = true;
@@ -381,6 +825,10 @@ pub fn markdown_to_html(
             }
         }
 
+        if !grouped_code_blocks.is_empty() {
+            events = group_code_tabs(events, &grouped_code_blocks);
+        }
+
         // We remove all the empty things we might have pushed before so we don't get some random \n
         events = events
             .into_iter()
@@ -390,6 +838,30 @@ pub fn markdown_to_html(
             })
             .collect();
 
+        if context.empty_headings == EmptyHeadings::Skip {
+            // Headings with only blank text, or an empty `{#}`
+            let (kept, removed) = remove_empty_headings(events, |content| {
+                if !content.iter().all(|e| matches!(e, Event::Text(_))) {
+                    return false;
+                }
+                let title = get_text(content);
+                let title = match find_custom_id(&title) {
+                    Some((_, id)) if !id.is_empty() => return false,
+                    Some((end, _)) => &title[..end],
+                    None => &title,
+                };
+                title.trim().is_empty()
+            });
+            events = kept;
+            if removed > 0 {
+                eprintln!(
+                    "Warning: {} empty heading(s) removed{}",
+                    removed,
+                    context.current_page_path.map(|p| format!(" in {}", p)).unwrap_or_default()
+                );
+            }
+        }
+
         let mut heading_refs = get_heading_refs(&events);
 
         let mut anchors_to_insert = vec![];
@@ -399,47 +871,100 @@ pub fn markdown_to_html(
         for heading_ref in heading_refs.iter_mut() {
             let end_idx = heading_ref.end_idx;
             if let Event::Text(ref mut text) = events[end_idx - 1] {
-                if text.as_bytes().last() == Some(&b'}') {
-                    if let Some(mut i) = text.find("{#") {
-                        let id = text[i + 2..text.len() - 1].to_owned();
-                        inserted_anchors.push(id.clone());
-                        while i > 0 && text.as_bytes()[i - 1] == b' ' {
-                            i -= 1;
-                        }
-                        heading_ref.id = Some(id);
-                        *text = text[..i].to_owned().into();
+                if let Some((end, id)) = find_custom_id(text) {
+                    // An empty `{#}` gets an id generated like headings without one
+                    if !id.is_empty() {
+                        inserted_anchors.push(id.to_owned());
+                        heading_ref.id = Some(id.to_owned());
                     }
+                    *text = text[..end].to_owned().into();
                 }
             }
         }
 
+        let word_counts: Vec<Option<usize>> = if context.config.markdown.heading_word_counts {
+            get_section_word_counts(&events, &heading_refs).into_iter().map(Some).collect()
+        } else {
+            vec![None; heading_refs.len()]
+        };
+
+        let anchor_target_class = match context.config.markdown.anchor_target_class {
+            Some(ref class) => {
+                let mut escaped = String::new();
+                cmark::escape::escape_html(&mut escaped, class).expect("Could not write to buffer");
+                format!(" class=\"{}\"", escaped)
+            }
+            None => String::new(),
+        };
+
+        // The top level headings starting a section, with their level and id
+        let mut section_headings = Vec::new();
+
         // Second heading pass: auto-generate remaining IDs, and emit HTML
-        for heading_ref in heading_refs {
+        for (i, (heading_ref, word_count)) in heading_refs.into_iter().zip(word_counts).enumerate()
+        {
             let start_idx = heading_ref.start_idx;
             let end_idx = heading_ref.end_idx;
+            let top_level = heading_ref.top_level;
             let title = get_text(&events[start_idx + 1..end_idx]);
             let id = heading_ref.id.unwrap_or_else(|| {
-                find_anchor(
-                    &inserted_anchors,
-                    slugify_anchors(&title, context.config.slugify.anchors),
-                    0,
-                )
+                let slug = slugify_anchors(&title, context.config.slugify.anchors);
+                // Headings without text, only symbols or only an image would get an empty id
+                let slug = if slug.is_empty() { "heading".to_string() } else { slug };
+                find_anchor(&inserted_anchors, slug, 0)
             });
             inserted_anchors.push(id.clone());
+            if context.config.markdown.heading_sections && top_level {
+                section_headings.push((start_idx, heading_ref.level, id.clone()));
+            }
 
-            // insert `id` to the tag
-            let html = format!("<h{lvl} id=\"{id}\">", lvl = heading_ref.level, id = id);
+            // generate anchors and places to insert them, lower headings only get an id
+            let insert_anchor = if heading_ref.level >= context.anchor_min_level {
+                context.insert_anchor
+            } else {
+                InsertAnchor::None
+            };
+
+            // insert `id` to the tag, and the class of anchor targets if it gets an anchor
+            let html = format!(
+                "<h{lvl} id=\"{id}\"{class}{itemprop}>",
+                lvl = heading_ref.level,
+                id = id,
+                class = if insert_anchor != InsertAnchor::None { &anchor_target_class } else { "" },
+                itemprop = if i == 0 && context.config.markdown.article_microdata {
+                    " itemprop=\"headline\""
+                } else {
+                    ""
+                }
+            );
             events[start_idx] = Event::Html(html.into());
+            // what goes after the `#` in the anchor links
+            let fragment = if context.config.markdown.encode_anchor_fragments {
+                utf8_percent_encode(&id, FRAGMENT).to_string()
+            } else {
+                id.clone()
+            };
 
-            // generate anchors and places to insert them
-            if context.insert_anchor != InsertAnchor::None {
-                let anchor_idx = match context.insert_anchor {
+            if insert_anchor == InsertAnchor::Heading {
+                // Links can't be nested so we only keep the content of the ones in the heading
+                for event in &mut events[start_idx + 1..end_idx] {
+                    if let Event::Start(Tag::Link(..)) | Event::End(Tag::Link(..)) = event {
+                        *event = Event::Html("".into());
+                    }
+                }
+                let anchor_link =
+                    format!("<a class=\"zola-anchor\" href=\"#{fragment}\">", fragment = fragment);
+                anchors_to_insert.push((start_idx + 1, Event::Html(anchor_link.into())));
+                anchors_to_insert.push((end_idx, Event::Html("</a>".into())));
+            } else if insert_anchor != InsertAnchor::None {
+                let anchor_idx = match insert_anchor {
                     InsertAnchor::Left => start_idx + 1,
                     InsertAnchor::Right => end_idx,
-                    InsertAnchor::None => 0, // Not important
+                    InsertAnchor::Heading | InsertAnchor::None => 0, // Not important
                 };
                 let mut c = tera::Context::new();
                 c.insert("id", &id);
+                c.insert("fragment", &fragment);
                 c.insert("level", &heading_ref.level);
                 c.insert("lang", &context.lang);
 
@@ -455,28 +980,411 @@ pub fn markdown_to_html(
 
             // record heading to make table of contents
             let permalink = format!("{}#{}", context.current_page_permalink, id);
-            let h =
-                Heading { level: heading_ref.level, id, permalink, title, children: Vec::new() };
+            let h = Heading {
+                level: heading_ref.level,
+                id,
+                permalink,
+                title,
+                word_count,
+                children: Vec::new(),
+            };
             headings.push(h);
         }
 
-        if context.insert_anchor != InsertAnchor::None {
+        if !section_headings.is_empty() {
+            anchors_to_insert.extend(heading_sections(&section_headings, events.len()));
+            // The insertions need to be in order, keeping the order of the ones at the same index
+            anchors_to_insert.sort_by_key(|(idx, _)| *idx);
+        }
+        if !anchors_to_insert.is_empty() {
             events.insert_many(anchors_to_insert);
         }
 
-        cmark::html::push_html(&mut html, events.into_iter());
+        let (deduplicated, duplicates) = remove_duplicate_footnotes(events);
+        events = deduplicated;
+        if !duplicates.is_empty() {
+            let labels =
+                duplicates.iter().map(|l| format!("`[^{}]`", l)).collect::<Vec<_>>().join(", ");
+            let location =
+                context.current_page_path.map(|p| format!(" in {}", p)).unwrap_or_default();
+            match context.duplicate_footnotes {
+                DuplicateFootnotes::Warn => eprintln!(
+                    "Warning: footnote(s) {} defined more than once{}, only the first definition is used",
+                    labels, location
+                ),
+                DuplicateFootnotes::Error => {
+                    error = Some(Error::msg(format!(
+                        "Footnote(s) {} defined more than once{}",
+                        labels, location
+                    )));
+                }
+            }
+        }
+
+        if context.empty_links == EmptyLinks::Drop {
+            let (kept, removed) = remove_empty_links(events);
+            events = kept;
+            if removed > 0 {
+                eprintln!(
+                    "Warning: {} link(s) or image(s) without a URL removed{}",
+                    removed,
+                    context.current_page_path.map(|p| format!(" in {}", p)).unwrap_or_default()
+                );
+            }
+        }
+
+        if context.config.markdown.smart_punctuation {
+            match context.config.markdown.measurement_quotes {
+                Some(MeasurementQuotes::Straight) => fix_measurement_quotes(&mut events, false),
+                Some(MeasurementQuotes::Primes) => fix_measurement_quotes(&mut events, true),
+                None => (),
+            }
+        }
+
+        events = normalize_combined_emphasis(events, context.config.markdown.combined_emphasis);
+
+        if context.link_bare_emails {
+            events = link_bare_emails(events, context.obfuscate_emails);
+        }
+
+        if !context.autolinks.is_empty() {
+            events = autolink(events, &context.autolinks);
+        }
+
+        if let Some(max_len) = context.config.markdown.truncate_autolinks {
+            truncate_autolinks(&mut events, max_len);
+        }
+
+        if let Some(ref marker) = context.config.markdown.external_link_marker {
+            events = mark_external_links(events, marker);
+        }
+
+        unescape_table_code_pipes(&mut events);
+        if context.config.markdown.table_cell_blocks {
+            events = expand_table_cells(events);
+        }
+        if let Some(ref class) = context.config.markdown.table_wrapper_class {
+            events = wrap_tables(events, class);
+        }
+        if context.config.markdown.merge_adjacent_blockquotes {
+            events = merge_adjacent_blockquotes(events);
+        }
+        match context.blockquote_attribution {
+            Some(BlockquoteAttribution::Cite) => events = attribute_blockquotes(events, "cite"),
+            Some(BlockquoteAttribution::Footer) => events = attribute_blockquotes(events, "footer"),
+            None => (),
+        }
+        match context.config.markdown.image_captions {
+            Some(ImageCaptions::Title) => {
+                events = caption_images(events, |title, _| title.to_string())
+            }
+            Some(ImageCaptions::Alt) => {
+                events = caption_images(events, |_, alt| {
+                    alt.iter()
+                        .filter_map(|e| match e {
+                            Event::Text(t) | Event::Code(t) => Some(t.as_ref()),
+                            _ => None,
+                        })
+                        .collect()
+                })
+            }
+            None => (),
+        }
+
+        if context.normalize_list_start {
+            normalize_list_starts(&mut events);
+        }
+        let mark_rtl = context.text_direction == Some(TextDirection::Auto);
+        if let Some(class) = context.lead_paragraph_class {
+            mark_lead_paragraph(&mut events, class, mark_rtl);
+        }
+        if mark_rtl {
+            mark_rtl_paragraphs(&mut events);
+        }
     }
 
     if let Some(e) = error {
         Err(e)
     } else {
-        Ok(Rendered {
-            summary_len: if has_summary { html.find(CONTINUE_READING) } else { None },
-            body: html,
-            toc: make_table_of_contents(headings),
-            internal_links,
-            external_links,
+        Ok(MarkdownEvents { events, has_summary, headings, internal_links, external_links })
+    }
+}
+
+pub fn markdown_to_html(
+    content: &str,
+    context: &RenderContext,
+    html_shortcodes: Vec<Shortcode>,
+) -> Result<Rendered> {
+    let MarkdownEvents { events, has_summary, headings, internal_links, external_links } =
+        markdown_to_events(content, context, html_shortcodes)?;
+    let images = get_image_sources(&events);
+    let ignored_containers = &context.config.markdown.cover_image_ignored_containers;
+    let cover_image = if ignored_containers.is_empty() {
+        images.first().cloned()
+    } else {
+        get_cover_image(&events, ignored_containers)
+    };
+
+    let note = last_modified_note(context)?;
+    let note_position = context.config.markdown.last_modified_position;
+
+    // the rendered html
+    let mut html = String::with_capacity(content.len());
+    if context.config.markdown.article_microdata {
+        html.push_str(&article_microdata_start(context));
+    }
+    if let Some(dir) = context.text_direction {
+        html.push_str(&format!("<div dir=\"{}\">\n", dir.as_str()));
+    }
+    if let (Some(note), LastModifiedPosition::Start) = (&note, note_position) {
+        html.push_str(note);
+    }
+    cmark::html::push_html(&mut html, events.into_iter());
+    if let Cow::Owned(sanitized) =
+        sanitize_style_attributes(&html, context.config.markdown.style_attributes)
+    {
+        html = sanitized;
+    }
+    if context.config.markdown.trim_trailing_whitespace {
+        html = trim_trailing_whitespace(&html);
+    }
+    let summary_len = if has_summary { html.find(CONTINUE_READING) } else { None };
+    if let (Some(note), LastModifiedPosition::End) = (&note, note_position) {
+        if context.config.markdown.trim_trailing_whitespace {
+            html.push_str(&trim_trailing_whitespace(note));
+        } else {
+            html.push_str(note);
+        }
+    }
+    if context.text_direction.is_some() {
+        html.push_str("</div>\n");
+    }
+    if context.config.markdown.article_microdata {
+        html.push_str("</div>\n");
+    }
+
+    let progress_map = if context.config.markdown.heading_progress_map {
+        Some(heading_progress_map(&html, &headings))
+    } else {
+        None
+    };
+    let toc = make_table_of_contents(headings);
+    let toc_json = if context.config.markdown.toc_json {
+        Some(
+            to_value(&toc)
+                .map_err(|e| Error::chain("Failed to serialize the table of contents", e))?
+                .to_string(),
+        )
+    } else {
+        None
+    };
+
+    Ok(Rendered {
+        summary_len,
+        body: html,
+        toc,
+        toc_json,
+        internal_links,
+        external_links,
+        images,
+        cover_image,
+        text_direction: context.text_direction,
+        article_microdata: context.config.markdown.article_microdata,
+        heading_sections: context.config.markdown.heading_sections,
+        progress_map,
+    })
+}
+
+/// Splits the text of a table cell at the given line break marker, except for a marker at the
+/// start of the text if it was escaped with a backslash
+fn split_cell_lines<'a>(text: &'a str, marker: &str, escaped: bool) -> Vec<&'a str> {
+    let (literal, rest) =
+        if escaped && text.starts_with(marker) { text.split_at(marker.len()) } else { ("", text) };
+    let mut lines: Vec<&str> = rest.split(marker).collect();
+    if !literal.is_empty() {
+        // The literal marker is part of the first line
+        lines[0] = &text[..literal.len() + lines[0].len()];
+    }
+    lines
+}
+
+/// The part of the content making its summary: everything before the `<!-- more -->` marker if
+/// there is one, otherwise its first paragraph. `None` if the content has neither
+pub fn summary_source(content: &str) -> Option<&str> {
+    let mut first_paragraph = None;
+    let mut depth = 0;
+    for (event, range) in Parser::new_ext(content, Options::all()).into_offset_iter() {
+        match event {
+            Event::Html(_) => {
+                if let Some(i) = content[range.clone()].find("<!-- more -->") {
+                    return Some(&content[..range.start + i]);
+                }
+            }
+            Event::Start(Tag::Paragraph) if depth == 0 && first_paragraph.is_none() => {
+                first_paragraph = Some(range);
+                depth += 1;
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => (),
+        }
+    }
+
+    first_paragraph.map(|range| &content[range])
+}
+
+/// Where each heading starts in the HTML, as a fraction of its length
+fn heading_progress_map(html: &str, headings: &[Heading]) -> Vec<(String, f64)> {
+    let mut from = 0;
+    headings
+        .iter()
+        .filter_map(|h| {
+            let attr = format!("<h{} id=\"{}\"", h.level, h.id);
+            let start = from + html[from..].find(&attr)?;
+            from = start + attr.len();
+            Some((h.id.clone(), start as f64 / html.len() as f64))
         })
+        .collect()
+}
+
+/// Removes the spaces and tabs ending the lines of the HTML, except for the lines ending inside a
+/// `<pre>` or a `<textarea>` as the whitespace is part of their content there
+fn trim_trailing_whitespace(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut preformatted_depth = 0i32;
+    for line in html.split_inclusive('\n') {
+        for caps in HTML_TAG_RE.captures_iter(line) {
+            let name = caps[2].to_lowercase();
+            if name != "pre" && name != "textarea" {
+                continue;
+            }
+            if caps[1].is_empty() {
+                preformatted_depth += 1;
+            } else {
+                preformatted_depth = (preformatted_depth - 1).max(0);
+            }
+        }
+        if preformatted_depth > 0 {
+            out.push_str(line);
+            continue;
+        }
+        let (content, newline) = match line.strip_suffix('\n') {
+            Some(content) => (content, "\n"),
+            None => (line, ""),
+        };
+        out.push_str(content.trim_end_matches(&[' ', '\t'][..]));
+        out.push_str(newline);
+    }
+    out
+}
+
+/// The `<div>` marking the content as a schema.org `Article`, with its dates taken from the
+/// front matter of the page being rendered if there is one
+fn article_microdata_start(context: &RenderContext) -> String {
+    let mut html = "<div itemscope itemtype=\"https://schema.org/Article\">\n".to_string();
+    let page = context.tera_context.get("page");
+    for (key, itemprop) in &[("date", "datePublished"), ("updated", "dateModified")] {
+        if let Some(date) = page.and_then(|p| p.get(key)).and_then(|d| d.as_str()) {
+            let mut escaped = String::new();
+            cmark::escape::escape_html(&mut escaped, date).expect("Could not write to buffer");
+            html.push_str(&format!("<meta itemprop=\"{}\" content=\"{}\">\n", itemprop, escaped));
+        }
+    }
+    html
+}
+
+/// The `<p>` with the modification time of the source file if `markdown.last_modified_note` is set.
+/// Nothing is added if the time of the file can't be read
+fn last_modified_note(context: &RenderContext) -> Result<Option<String>> {
+    let format = match context.config.markdown.last_modified_note {
+        Some(ref format) => format,
+        None => return Ok(None),
+    };
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(Error::msg(format!(
+            "`markdown.last_modified_note` is not a valid date format: `{}`",
+            format
+        )));
+    }
+    let modified = match context.source_path.and_then(|p| p.metadata().ok()?.modified().ok()) {
+        Some(modified) => DateTime::<Local>::from(modified),
+        None => return Ok(None),
+    };
+
+    let mut note = String::new();
+    cmark::escape::escape_html(&mut note, &modified.format(format).to_string())
+        .expect("Could not write to buffer");
+    Ok(Some(format!("<p class=\"last-modified\">{}</p>\n", note)))
+}
+
+/// Same as `markdown_to_html` but returns the events that would be turned into HTML.
+/// The events are converted to owned ones so they can outlive the content.
+pub fn markdown_to_owned_events(
+    content: &str,
+    context: &RenderContext,
+    html_shortcodes: Vec<Shortcode>,
+) -> Result<Vec<Event<'static>>> {
+    let parsed = markdown_to_events(content, context, html_shortcodes)?;
+    Ok(parsed.events.into_iter().map(into_owned_event).collect())
+}
+
+/// The ids the headings of the markdown get, in order, without rendering it to HTML
+pub fn markdown_heading_ids(
+    content: &str,
+    context: &RenderContext,
+    html_shortcodes: Vec<Shortcode>,
+) -> Result<Vec<String>> {
+    let parsed = markdown_to_events(content, context, html_shortcodes)?;
+    Ok(parsed.headings.into_iter().map(|h| h.id).collect())
+}
+
+fn into_owned_str(s: CowStr) -> CowStr<'static> {
+    match s {
+        CowStr::Inlined(s) => CowStr::Inlined(s),
+        s => CowStr::Boxed(s.into_string().into_boxed_str()),
+    }
+}
+
+fn into_owned_tag(tag: Tag) -> Tag<'static> {
+    match tag {
+        Tag::Paragraph => Tag::Paragraph,
+        Tag::Heading(level) => Tag::Heading(level),
+        Tag::BlockQuote => Tag::BlockQuote,
+        Tag::CodeBlock(CodeBlockKind::Indented) => Tag::CodeBlock(CodeBlockKind::Indented),
+        Tag::CodeBlock(CodeBlockKind::Fenced(info)) => {
+            Tag::CodeBlock(CodeBlockKind::Fenced(into_owned_str(info)))
+        }
+        Tag::List(start) => Tag::List(start),
+        Tag::Item => Tag::Item,
+        Tag::FootnoteDefinition(label) => Tag::FootnoteDefinition(into_owned_str(label)),
+        Tag::Table(alignments) => Tag::Table(alignments),
+        Tag::TableHead => Tag::TableHead,
+        Tag::TableRow => Tag::TableRow,
+        Tag::TableCell => Tag::TableCell,
+        Tag::Emphasis => Tag::Emphasis,
+        Tag::Strong => Tag::Strong,
+        Tag::Strikethrough => Tag::Strikethrough,
+        Tag::Link(link_type, url, title) => {
+            Tag::Link(link_type, into_owned_str(url), into_owned_str(title))
+        }
+        Tag::Image(link_type, url, title) => {
+            Tag::Image(link_type, into_owned_str(url), into_owned_str(title))
+        }
+    }
+}
+
+fn into_owned_event(event: Event) -> Event<'static> {
+    match event {
+        Event::Start(tag) => Event::Start(into_owned_tag(tag)),
+        Event::End(tag) => Event::End(into_owned_tag(tag)),
+        Event::Text(text) => Event::Text(into_owned_str(text)),
+        Event::Code(code) => Event::Code(into_owned_str(code)),
+        Event::Html(html) => Event::Html(into_owned_str(html)),
+        Event::FootnoteReference(label) => Event::FootnoteReference(into_owned_str(label)),
+        Event::SoftBreak => Event::SoftBreak,
+        Event::HardBreak => Event::HardBreak,
+        Event::Rule => Event::Rule,
+        Event::TaskListMarker(checked) => Event::TaskListMarker(checked),
     }
 }
 
@@ -497,4 +1405,16 @@ mod tests {
 
         assert!(!is_external_link("http.jpg"))
     }
+
+    #[test]
+    fn test_expand_leading_tabs() {
+        // Tab stops, and only the indentation is changed
+        assert_eq!(expand_leading_tabs("\ta\tb\n \tc\n\t\n", 4), "    a\tb\n    c\n    \n");
+        // Fenced code is left alone until the closing fence, which needs to be as long
+        assert_eq!(
+            expand_leading_tabs("\t~~~~\n\tcode\n~~~\n\t~~~~\n\tafter\n", 2),
+            "  ~~~~\n\tcode\n~~~\n  ~~~~\n  after\n"
+        );
+        assert!(matches!(expand_leading_tabs("no tabs", 2), Cow::Borrowed(_)));
+    }
 }
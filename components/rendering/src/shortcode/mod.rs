@@ -1,22 +1,56 @@
 use std::collections::HashMap;
 
-use errors::{Error, Result};
-use utils::templates::{ShortcodeDefinition, ShortcodeFileType};
+use errors::{bail, Error, Result};
+use lazy_static::lazy_static;
+use pulldown_cmark::{Event, Parser, Tag};
+use regex::Regex;
+use tera::{Map, Value};
+use utils::templates::{ShortcodeArg, ShortcodeArgType, ShortcodeDefinition, ShortcodeFileType};
+
+use crate::context::RenderContext;
 
 mod parser;
 
-pub(crate) use parser::{parse_for_shortcodes, Shortcode, SHORTCODE_PLACEHOLDER};
+pub use parser::{parse_for_shortcodes, Shortcode, SHORTCODE_PLACEHOLDER};
 
-/// Extracts the shortcodes present in the source, check if we know them and errors otherwise
+/// Extracts the shortcodes present in the source, check if we know them and errors otherwise.
+/// If a `fallback` template is given, unknown shortcodes are rendered with it instead, getting
+/// their `name` and their arguments as `args`.
+/// With `ignore_case`, shortcodes not found with their name are looked up lowercased, so
+/// `YouTube` finds `youtube`.
 pub fn extract_shortcodes(
     source: &str,
     definitions: &HashMap<String, ShortcodeDefinition>,
+    fallback: Option<&str>,
+    ignore_case: bool,
+    allowlist: Option<&[String]>,
 ) -> Result<(String, Vec<Shortcode>)> {
-    let (out, mut shortcodes) = parse_for_shortcodes(source)?;
+    let (mut out, mut shortcodes) = parse_for_shortcodes(source)?;
+    if let Some(allowlist) = allowlist {
+        shortcodes = remove_disallowed_shortcodes(&mut out, shortcodes, allowlist, ignore_case);
+    }
 
     for sc in &mut shortcodes {
-        if let Some(def) = definitions.get(&sc.name) {
+        let definition = match definitions.get(&sc.name) {
+            None if ignore_case => definitions.get(&sc.name.to_lowercase()),
+            def => def,
+        };
+        if let Some(def) = definition {
+            check_arg_types(&sc.name, &sc.args, &def.arg_types)?;
             sc.tera_name = def.tera_name.clone();
+            if def.decode_entities {
+                decode_string_args(&mut sc.args);
+            }
+        } else if let Some(fallback) = fallback {
+            eprintln!(
+                "Warning: found usage of an unknown shortcode named `{}`, rendering it with `{}`",
+                sc.name, fallback
+            );
+            let mut args = Map::new();
+            args.insert("name".to_string(), Value::String(sc.name.clone()));
+            args.insert("args".to_string(), sc.args.take());
+            sc.args = Value::Object(args);
+            sc.tera_name = fallback.to_string();
         } else {
             return Err(Error::msg(format!("Found usage of a shortcode named `{}` but we do not know about. Make sure it's not a typo and that a field name `{}.{{html,md}} exists in the `templates/shortcodes` directory.", sc.name, sc.name)));
         }
@@ -25,11 +59,280 @@ pub fn extract_shortcodes(
     Ok((out, shortcodes))
 }
 
+/// Errors if one of the arguments doesn't have the type set for it in
+/// `markdown.shortcode_arg_types` or if a required one is missing, before the shortcode is rendered
+fn check_arg_types(
+    name: &str,
+    args: &Value,
+    arg_types: &HashMap<String, ShortcodeArg>,
+) -> Result<()> {
+    let empty = Map::new();
+    let args = args.as_object().unwrap_or(&empty);
+    // Sorted so the same argument is reported every time
+    let mut missing: Vec<_> = arg_types
+        .iter()
+        .filter(|(arg_name, arg)| arg.required && !args.contains_key(*arg_name))
+        .map(|(arg_name, _)| arg_name)
+        .collect();
+    missing.sort();
+    if let Some(arg_name) = missing.first() {
+        bail!("The shortcode `{}` is missing its required `{}` argument", name, arg_name);
+    }
+
+    let mut arg_names: Vec<_> = args.keys().filter(|k| arg_types.contains_key(*k)).collect();
+    arg_names.sort();
+    for arg_name in arg_names {
+        let expected = arg_types[arg_name].arg_type;
+        let actual = ShortcodeArgType::of(&args[arg_name]);
+        if actual != Some(expected) {
+            bail!(
+                "The `{}` argument of the shortcode `{}` should be {} but is {}",
+                arg_name,
+                name,
+                expected.description(),
+                actual.map(|t| t.description()).unwrap_or("something else")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Removes the shortcodes whose name is not in the allowlist from the content, with a warning,
+/// returning the other ones with their span moved accordingly
+fn remove_disallowed_shortcodes(
+    out: &mut String,
+    shortcodes: Vec<Shortcode>,
+    allowlist: &[String],
+    ignore_case: bool,
+) -> Vec<Shortcode> {
+    let mut kept = Vec::with_capacity(shortcodes.len());
+    let mut removed_len = 0;
+    for mut sc in shortcodes {
+        let span = sc.span.start - removed_len..sc.span.end - removed_len;
+        let allowed = allowlist
+            .iter()
+            .any(|name| *name == sc.name || (ignore_case && name.eq_ignore_ascii_case(&sc.name)));
+        if allowed {
+            sc.span = span;
+            kept.push(sc);
+        } else {
+            eprintln!("Warning: the shortcode `{}` is not allowed here, removing it", sc.name);
+            removed_len += span.len();
+            out.replace_range(span, "");
+        }
+    }
+    kept
+}
+
+lazy_static! {
+    static ref SHORTCODE_CALL_RE: Regex = Regex::new(r"(?s)\{\{.*?\}\}|\{%.*?%\}").unwrap();
+}
+
+const CODE_PLACEHOLDER: &str = "@@zola-code-placeholder-";
+
+/// Replaces the fenced/indented code blocks and the inline code of the source by placeholders
+/// so they are not scanned for shortcodes, returning the code that was replaced in order.
+/// Code spans inside a shortcode call, eg a backquoted string argument, are left alone.
+pub fn hide_code(source: &str) -> (String, Vec<String>) {
+    let calls: Vec<_> = SHORTCODE_CALL_RE.find_iter(source).map(|m| m.range()).collect();
+    let mut ranges = Vec::new();
+    for (event, range) in Parser::new(source).into_offset_iter() {
+        let is_code = matches!(event, Event::Start(Tag::CodeBlock(_)) | Event::Code(_));
+        if is_code && !calls.iter().any(|c| c.start <= range.start && range.end <= c.end) {
+            ranges.push(range);
+        }
+    }
+
+    let mut out = String::with_capacity(source.len());
+    let mut code = Vec::with_capacity(ranges.len());
+    let mut last = 0;
+    for range in ranges {
+        // Code blocks in block quotes and lists can be reported more than once
+        if range.start < last {
+            continue;
+        }
+        out.push_str(&source[last..range.start]);
+        out.push_str(&format!("{}{}@@", CODE_PLACEHOLDER, code.len()));
+        code.push(source[range.clone()].to_string());
+        last = range.end;
+    }
+    out.push_str(&source[last..]);
+
+    (out, code)
+}
+
+/// Puts back the code hidden by `hide_code`, moving the spans of the given shortcodes found
+/// after each of them accordingly
+pub fn restore_code(content: &str, code: &[String], shortcodes: &mut [Shortcode]) -> String {
+    if code.is_empty() || !content.contains(CODE_PLACEHOLDER) {
+        return content.to_string();
+    }
+
+    let mut out = String::with_capacity(content.len());
+    // Where each restored placeholder ended in the content and how much longer the code is
+    let mut shifts: Vec<(usize, isize)> = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find(CODE_PLACEHOLDER) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + CODE_PLACEHOLDER.len()..];
+        let restored = after.find("@@").and_then(|end| {
+            after[..end].parse::<usize>().ok().and_then(|i| code.get(i)).map(|c| (c, end + 2))
+        });
+        match restored {
+            Some((c, len)) => {
+                out.push_str(c);
+                let placeholder_len = CODE_PLACEHOLDER.len() + len;
+                rest = &after[len..];
+                shifts.push((
+                    content.len() - rest.len(),
+                    c.len() as isize - placeholder_len as isize,
+                ));
+            }
+            None => {
+                out.push_str(CODE_PLACEHOLDER);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+
+    for sc in shortcodes {
+        let shift: isize =
+            shifts.iter().filter(|(end, _)| *end <= sc.span.start).map(|(_, shift)| shift).sum();
+        sc.span =
+            (sc.span.start as isize + shift) as usize..(sc.span.end as isize + shift) as usize;
+    }
+
+    out
+}
+
+/// Decodes the named HTML entities most commonly found in text as well as numeric ones.
+/// Anything that isn't a known entity is kept as is.
+fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                    u32::from_str_radix(&entity[2..], 16).ok().and_then(std::char::from_u32)
+                }
+                _ if entity.starts_with('#') => {
+                    entity[1..].parse::<u32>().ok().and_then(std::char::from_u32)
+                }
+                _ => None,
+            };
+            c.map(|c| (c, end + 1))
+        });
+
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out
+}
+
+/// Decodes the HTML entities of all the strings in the arguments, including in arrays
+fn decode_string_args(value: &mut Value) {
+    match value {
+        Value::String(s) => *s = decode_entities(s),
+        Value::Array(values) => values.iter_mut().for_each(decode_string_args),
+        Value::Object(map) => map.values_mut().for_each(decode_string_args),
+        _ => (),
+    }
+}
+
+/// Renders a shortcode. If `markdown.shortcode_max_depth` is set, the output is scanned for
+/// shortcodes again and those get expanded as well, up to that many levels deep.
+/// The output of shortcodes without a body is trimmed if `trim_inline_shortcodes` is set.
+pub fn render_shortcode(sc: Shortcode, context: &RenderContext) -> Result<String> {
+    let trim = context.trim_inline_shortcodes && sc.body.is_none();
+    let max_depth = context.config.markdown.shortcode_max_depth;
+    let res = if max_depth == 0 {
+        sc.render(&context.tera, &context.tera_context)?
+    } else {
+        render_nested_shortcode(sc, context, 0, max_depth)?
+    };
+
+    if trim {
+        Ok(res.trim().to_string())
+    } else {
+        Ok(res)
+    }
+}
+
+fn render_nested_shortcode(
+    sc: Shortcode,
+    context: &RenderContext,
+    depth: usize,
+    max_depth: usize,
+) -> Result<String> {
+    let name = sc.name.clone();
+    let res = sc.render(&context.tera, &context.tera_context)?;
+    if !res.contains("{{") && !res.contains("{%") {
+        return Ok(res);
+    }
+
+    let (mut out, nested) = extract_shortcodes(
+        &res,
+        &context.shortcode_definitions,
+        context.config.markdown.unknown_shortcode_template.as_deref(),
+        context.case_insensitive_shortcodes,
+        context.shortcode_allowlist.as_deref(),
+    )?;
+    if nested.is_empty() {
+        return Ok(res);
+    }
+    if depth >= max_depth {
+        bail!(
+            "The output of the `{}` shortcode still contains shortcodes after {} levels of expansion. \
+            Make sure the shortcodes are not calling each other in a loop or increase `shortcode_max_depth`.",
+            name,
+            max_depth
+        );
+    }
+
+    // Going in reverse so the spans of the shortcodes we haven't rendered yet stay valid
+    for nested_sc in nested.into_iter().rev() {
+        let span = nested_sc.span.clone();
+        let rendered = render_nested_shortcode(nested_sc, context, depth + 1, max_depth)?;
+        out.replace_range(span, &rendered);
+    }
+
+    Ok(out)
+}
+
+/// Whether the definition of the shortcode says its output is HTML, for markdown shortcodes
+fn outputs_html(sc: &Shortcode, context: &RenderContext) -> bool {
+    context
+        .shortcode_definitions
+        .values()
+        .any(|def| def.html_output && def.tera_name == sc.tera_name)
+}
+
 pub fn insert_md_shortcodes(
     mut content: String,
     shortcodes: Vec<Shortcode>,
-    tera_context: &tera::Context,
-    tera: &tera::Tera,
+    context: &RenderContext,
 ) -> Result<(String, Vec<Shortcode>)> {
     // (span, len transformed)
     let mut transforms = Vec::new();
@@ -40,13 +343,15 @@ pub fn insert_md_shortcodes(
             sc.update_range(md_sc_span, *rendered_length);
         }
 
-        if sc.file_type() == ShortcodeFileType::Html {
+        // Markdown shortcodes outputting HTML are inserted after the markdown is rendered,
+        // like HTML shortcodes, so their output isn't changed
+        if sc.file_type() == ShortcodeFileType::Html || outputs_html(&sc, context) {
             html_shortcodes.push(sc);
             continue;
         }
 
         let span = sc.span.clone();
-        let res = sc.render(tera, tera_context)?;
+        let res = render_shortcode(sc, context)?;
         transforms.push((span.clone(), res.len()));
         content.replace_range(span, &res);
     }
@@ -58,6 +363,8 @@ pub fn insert_md_shortcodes(
 mod tests {
     use super::*;
     use crate::shortcode::SHORTCODE_PLACEHOLDER;
+    use config::Config;
+    use front_matter::InsertAnchor;
     use tera::to_value;
 
     #[test]
@@ -67,7 +374,9 @@ mod tests {
         tera.add_raw_template("shortcodes/a.md", "{{ nth }}").unwrap();
         tera.add_raw_template("shortcodes/bodied.md", "{{ body }}").unwrap();
 
-        let tera_context = tera::Context::new();
+        let config = Config::default_for_test();
+        let permalinks = HashMap::new();
+        let context = RenderContext::new(&tera, &config, "en", "", &permalinks, InsertAnchor::None);
         assert_eq!(
             insert_md_shortcodes(
                 format!("{}{}", SHORTCODE_PLACEHOLDER, SHORTCODE_PLACEHOLDER),
@@ -89,8 +398,7 @@ mod tests {
                         tera_name: "shortcodes/a.md".to_owned(),
                     }
                 ],
-                &tera_context,
-                &tera
+                &context
             )
             .unwrap()
             .0,
@@ -109,12 +417,18 @@ mod tests {
 
                     tera_name: "shortcodes/bodied.md".to_owned(),
                 },],
-                &tera_context,
-                &tera
+                &context
             )
             .unwrap()
             .0,
             "Much wow Content of the body".to_string()
         );
     }
+
+    #[test]
+    fn can_decode_html_entities() {
+        assert_eq!(decode_entities("Tom &amp; Jerry"), "Tom & Jerry");
+        assert_eq!(decode_entities("&lt;b&gt; &#39;&#x27;"), "<b> ''");
+        assert_eq!(decode_entities("AT&T &unknown; &amp"), "AT&T &unknown; &amp");
+    }
 }
@@ -21,6 +21,39 @@ pub struct Shortcode {
     pub(crate) tera_name: String,
 }
 
+/// Building shortcodes by hand, for the tests of crates using the rendering pipeline
+#[cfg(feature = "testing")]
+impl Shortcode {
+    /// A shortcode without arguments nor body, as `extract_shortcodes` would find it at `span`.
+    /// `tera_name` is the template to render, eg `shortcodes/youtube.html`
+    pub fn new(name: &str, tera_name: &str, span: Range<usize>) -> Shortcode {
+        Shortcode {
+            name: name.to_string(),
+            args: Value::Object(Map::new()),
+            span,
+            body: None,
+            nth: 1,
+            tera_name: tera_name.to_string(),
+        }
+    }
+
+    pub fn with_args(mut self, args: Value) -> Shortcode {
+        self.args = args;
+        self
+    }
+
+    pub fn with_body(mut self, body: &str) -> Shortcode {
+        self.body = Some(body.to_string());
+        self
+    }
+
+    /// How many times this shortcode was used in the content so far, including this one
+    pub fn with_nth(mut self, nth: usize) -> Shortcode {
+        self.nth = nth;
+        self
+    }
+}
+
 impl Shortcode {
     pub fn file_type(&self) -> ShortcodeFileType {
         if self.tera_name.ends_with("md") {
@@ -477,6 +510,21 @@ mod tests {
         assert_eq!(shortcodes[2].nth, 2);
     }
 
+    #[test]
+    fn can_extract_adjacent_inline_shortcodes() {
+        let (out, shortcodes) = parse_for_shortcodes("{{ a() }}{{ b(n=1) }}").unwrap();
+        assert_eq!(out, format!("{}{}", SHORTCODE_PLACEHOLDER, SHORTCODE_PLACEHOLDER));
+        assert_eq!(shortcodes.len(), 2);
+        assert_eq!(shortcodes[0].name, "a");
+        assert_eq!(shortcodes[0].span, 0..SHORTCODE_PLACEHOLDER.len());
+        assert_eq!(shortcodes[1].name, "b");
+        assert_eq!(
+            shortcodes[1].span,
+            SHORTCODE_PLACEHOLDER.len()..(2 * SHORTCODE_PLACEHOLDER.len())
+        );
+        assert_eq!(shortcodes[1].args["n"], to_value(1).unwrap());
+    }
+
     #[test]
     fn can_handle_multiple_shortcodes() {
         let (_, shortcodes) = parse_for_shortcodes(
@@ -0,0 +1,289 @@
+//! Filtering of the declarations of the `style` attributes found in the HTML of the content
+use std::borrow::Cow;
+use std::ops::Range;
+
+use config::StyleAttributes;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref URL_RE: Regex = Regex::new(r#"url\(\s*["']?([^"')]*)"#).unwrap();
+    static ref COMMENT_RE: Regex = Regex::new(r"/\*.*?\*/").unwrap();
+}
+
+/// Properties that can run code in some browsers whatever their value
+const UNSAFE_PROPERTIES: &[&str] = &["behavior", "-ms-behavior", "-moz-binding"];
+
+/// Values that can run code or load other stylesheets
+const UNSAFE_VALUES: &[&str] = &["expression(", "javascript:", "vbscript:", "@import"];
+
+/// Elements whose content is text for browsers up to their end tag. Their content is still
+/// filtered as HTML as `<style>` is not text inside `<svg>` and `<noscript>` depends on scripting
+const RAW_TEXT_ELEMENTS: &[&str] =
+    &["iframe", "noembed", "noframes", "noscript", "script", "style", "textarea", "title", "xmp"];
+
+/// The character references allowed in a `style` attribute, with the character they stand for
+const ALLOWED_ENTITIES: &[(&str, char)] =
+    &[("&amp;", '&'), ("&quot;", '"'), ("&apos;", '\''), ("&#39;", '\'')];
+
+/// Whether the URL of a `url()` is allowed by the policy: relative URLs always are, absolute
+/// http(s) ones only with `StyleAttributes::Lenient`
+fn is_allowed_url(url: &str, policy: StyleAttributes) -> bool {
+    let url = url.trim();
+    let has_scheme = url
+        .find(':')
+        .map(|i| url[..i].chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)))
+        .unwrap_or(false);
+    if !has_scheme && !url.starts_with("//") {
+        return true;
+    }
+
+    policy == StyleAttributes::Lenient
+        && (url.starts_with("https:") || url.starts_with("http:") || url.starts_with("//"))
+}
+
+/// Whether a `property: value` declaration is kept
+fn is_safe_declaration(declaration: &str, policy: StyleAttributes) -> bool {
+    // Escapes can hide any of the unsafe properties or values, eg `expr\65 ssion(`
+    if declaration.contains('\\') {
+        return false;
+    }
+    let (property, value) = match declaration.split_once(':') {
+        Some((p, v)) => (p.trim().to_lowercase(), v),
+        None => return false,
+    };
+    if property.is_empty() || UNSAFE_PROPERTIES.contains(&property.as_str()) {
+        return false;
+    }
+
+    // And so can comments, eg `expr/**/ession(`
+    let value: String = COMMENT_RE
+        .replace_all(value, "")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+    if UNSAFE_VALUES.iter().any(|v| value.contains(v)) {
+        return false;
+    }
+
+    URL_RE.captures_iter(&value).all(|caps| is_allowed_url(&caps[1], policy))
+}
+
+/// Decodes the character references of the value of an attribute, `None` if it has one
+/// which is not in `ALLOWED_ENTITIES` as it could hide an unsafe value
+fn decode_entities(value: &str) -> Option<String> {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(i) = rest.find('&') {
+        decoded.push_str(&rest[..i]);
+        let (entity, c) = ALLOWED_ENTITIES.iter().find(|(e, _)| rest[i..].starts_with(e))?;
+        decoded.push(*c);
+        rest = &rest[i + entity.len()..];
+    }
+    decoded.push_str(rest);
+    Some(decoded)
+}
+
+/// What a `style` attribute with that value becomes: `None` if all its declarations are kept,
+/// an empty string if none of them are, otherwise the attribute with the kept ones
+fn filter_style(value: &str, policy: StyleAttributes) -> Option<String> {
+    let decoded = match decode_entities(value) {
+        Some(d) => d,
+        None => return Some(String::new()),
+    };
+    let declarations: Vec<_> =
+        decoded.split(';').map(str::trim).filter(|d| !d.is_empty()).collect();
+    let kept: Vec<_> =
+        declarations.iter().copied().filter(|d| is_safe_declaration(d, policy)).collect();
+    if kept.len() == declarations.len() {
+        None
+    } else if kept.is_empty() {
+        Some(String::new())
+    } else {
+        Some(format!("style=\"{}\"", kept.join("; ").replace('&', "&amp;").replace('"', "&quot;")))
+    }
+}
+
+fn is_html_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r' | b'\x0c')
+}
+
+/// A start or end tag and its `style` attributes
+struct Tag<'a> {
+    len: usize,
+    /// The lowercased name of the element
+    name: String,
+    is_end: bool,
+    /// The position of each `style` attribute in the tag, with its value or `None` if the
+    /// value is not closed
+    styles: Vec<(Range<usize>, Option<&'a str>)>,
+}
+
+/// Parses the tag starting the HTML the way browsers do, so a `style` attribute can't be hidden
+/// from the filter, eg in the value of another attribute
+fn parse_tag(html: &str) -> Tag<'_> {
+    let bytes = html.as_bytes();
+    let is_end = bytes[1] == b'/';
+    let mut i = if is_end { 2 } else { 1 };
+    let name_start = i;
+    while i < bytes.len() && !is_html_whitespace(bytes[i]) && !matches!(bytes[i], b'/' | b'>') {
+        i += 1;
+    }
+    let name = html[name_start..i].to_ascii_lowercase();
+
+    let mut styles = Vec::new();
+    loop {
+        while i < bytes.len() && (is_html_whitespace(bytes[i]) || bytes[i] == b'/') {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            break;
+        }
+        if bytes[i] == b'>' {
+            i += 1;
+            break;
+        }
+
+        // The first character is part of the name even if it's a `=`
+        let attr_start = i;
+        i += 1;
+        while i < bytes.len()
+            && !is_html_whitespace(bytes[i])
+            && !matches!(bytes[i], b'/' | b'>' | b'=')
+        {
+            i += 1;
+        }
+        let is_style = html[attr_start..i].eq_ignore_ascii_case("style");
+
+        let mut value = Some("");
+        let mut j = i;
+        while j < bytes.len() && is_html_whitespace(bytes[j]) {
+            j += 1;
+        }
+        if j < bytes.len() && bytes[j] == b'=' {
+            j += 1;
+            while j < bytes.len() && is_html_whitespace(bytes[j]) {
+                j += 1;
+            }
+            i = j;
+            match bytes.get(i) {
+                Some(&quote) if quote == b'"' || quote == b'\'' => {
+                    match html[i + 1..].find(quote as char) {
+                        Some(len) => {
+                            value = Some(&html[i + 1..i + 1 + len]);
+                            i += len + 2;
+                        }
+                        None => {
+                            value = None;
+                            i = bytes.len();
+                        }
+                    }
+                }
+                Some(b'>') | None => (),
+                Some(_) => {
+                    while i < bytes.len() && !is_html_whitespace(bytes[i]) && bytes[i] != b'>' {
+                        i += 1;
+                    }
+                    value = Some(&html[j..i]);
+                }
+            }
+        }
+        if is_style {
+            styles.push((attr_start..i, value));
+        }
+    }
+
+    Tag { len: i, name, is_end, styles }
+}
+
+/// The length of the comment starting the HTML, up to where browsers end it
+fn comment_len(html: &str) -> usize {
+    if html[4..].starts_with('>') {
+        return 5;
+    }
+    if html[4..].starts_with("->") {
+        return 6;
+    }
+    [("-->", 3), ("--!>", 4)]
+        .iter()
+        .filter_map(|(end, len)| html[4..].find(end).map(|i| 4 + i + len))
+        .min()
+        .unwrap_or(html.len())
+}
+
+/// Copies the HTML to `out` with the `style` attributes of its tags filtered
+fn sanitize_markup(html: &str, policy: StyleAttributes, out: &mut String) {
+    let mut copied = 0;
+    let mut i = 0;
+    while let Some(offset) = html[i..].find('<') {
+        let start = i + offset;
+        let rest = &html[start..];
+        let bytes = rest.as_bytes();
+        let name_start = if bytes.get(1) == Some(&b'/') { 2 } else { 1 };
+        let starts_tag = matches!(bytes.get(name_start), Some(b) if b.is_ascii_alphabetic());
+
+        if rest.starts_with("<!--") {
+            i = start + comment_len(rest);
+        } else if starts_tag {
+            out.push_str(&html[copied..start]);
+            let tag = write_tag(rest, policy, out);
+            i = start + tag.len;
+            copied = i;
+            if !tag.is_end && RAW_TEXT_ELEMENTS.contains(&tag.name.as_str()) {
+                let end_tag = format!("</{}", tag.name);
+                let content_end =
+                    html[i..].to_ascii_lowercase().find(&end_tag).map_or(html.len(), |end| i + end);
+                sanitize_markup(&html[i..content_end], policy, out);
+                copied = content_end;
+                i = content_end;
+            }
+        } else if rest.starts_with("<!") || rest.starts_with("<?") || rest.starts_with("</") {
+            // Doctypes, CDATA sections and the like are comments ending at the first `>`
+            i = start + rest.find('>').map_or(rest.len(), |end| end + 1);
+        } else {
+            i = start + 1;
+        }
+    }
+    out.push_str(&html[copied..]);
+}
+
+/// Writes the tag starting the HTML to `out` with its `style` attributes filtered
+fn write_tag<'a>(html: &'a str, policy: StyleAttributes, out: &mut String) -> Tag<'a> {
+    let tag = parse_tag(html);
+    let mut copied = 0;
+    for (range, value) in &tag.styles {
+        let replacement = match value {
+            Some(value) => filter_style(value, policy),
+            None => Some(String::new()),
+        };
+        if let Some(replacement) = replacement {
+            out.push_str(&html[copied..range.start]);
+            if replacement.is_empty() {
+                out.truncate(out.trim_end_matches(|c: char| c.is_ascii_whitespace()).len());
+            } else {
+                out.push_str(&replacement);
+            }
+            copied = range.end;
+        }
+    }
+    out.push_str(&html[copied..tag.len]);
+    tag
+}
+
+/// Removes the unsafe declarations of the `style` attributes of the HTML tags, and the
+/// attributes left empty. The tags are parsed the way browsers do, so it is meant to be run
+/// on the whole HTML rendered rather than on pieces of it
+pub fn sanitize_style_attributes(html: &str, policy: StyleAttributes) -> Cow<'_, str> {
+    if policy == StyleAttributes::Keep || !html.to_ascii_lowercase().contains("style") {
+        return Cow::Borrowed(html);
+    }
+
+    let mut out = String::with_capacity(html.len());
+    sanitize_markup(html, policy, &mut out);
+    if out == html {
+        Cow::Borrowed(html)
+    } else {
+        Cow::Owned(out)
+    }
+}
@@ -7,6 +7,10 @@ pub struct Heading {
     pub id: String,
     pub permalink: String,
     pub title: String,
+    /// The number of words until the next heading of the same or a higher level, only set
+    /// with `markdown.heading_word_counts`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub word_count: Option<usize>,
     pub children: Vec<Heading>,
 }
 
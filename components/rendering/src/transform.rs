@@ -0,0 +1,901 @@
+//! Transformations applied to the markdown events once shortcodes, headings and links
+//! have been processed, right before the events are turned into HTML.
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Range;
+
+use errors::{Error, Result};
+use lazy_static::lazy_static;
+use pulldown_cmark::{escape::escape_html, CowStr, Event, LinkType, Tag};
+use regex::Regex;
+
+use crate::markdown::is_external_link;
+
+lazy_static! {
+    static ref EMAIL_RE: Regex =
+        Regex::new(r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b").unwrap();
+    static ref PROTECTED_TAG_RE: Regex =
+        Regex::new(r"(?i)<(/)?(?:a|code|pre)(?:[\s/>]|$)").unwrap();
+}
+
+/// Encodes every character of the string as a HTML entity so it is still readable by browsers
+/// but not by naive scrapers looking for email addresses
+fn encode_entities(s: &str) -> String {
+    s.chars().map(|c| format!("&#{};", c as u32)).collect()
+}
+
+fn email_link(email: &str, obfuscate: bool) -> String {
+    if obfuscate {
+        let encoded = encode_entities(email);
+        format!("<a href=\"{}{}\">{}</a>", encode_entities("mailto:"), encoded, encoded)
+    } else {
+        let mut escaped = String::new();
+        escape_html(&mut escaped, email).expect("Could not write to buffer");
+        format!("<a href=\"mailto:{}\">{}</a>", escaped, escaped)
+    }
+}
+
+/// How many elements in which we shouldn't touch the text a raw HTML event opens, minus the
+/// number it closes, so an event holding a whole `<a …>…</a>` doesn't change anything
+fn html_protected_depth_change(html: &str) -> i32 {
+    PROTECTED_TAG_RE
+        .captures_iter(html)
+        .map(|caps| if caps.get(1).is_some() { -1 } else { 1 })
+        .sum()
+}
+
+/// Calls `replace` on the text of the events, which can then push the events it should be
+/// replaced with. Text in links, images, inline code and code blocks is left untouched.
+fn replace_text<'a, F>(events: Vec<Event<'a>>, mut replace: F) -> Vec<Event<'a>>
+where
+    F: FnMut(&str, &mut Vec<Event<'a>>),
+{
+    let mut out = Vec::with_capacity(events.len());
+    // How many links/images/code elements we are currently in
+    let mut protected_depth = 0i32;
+    // pulldown-cmark can split a single piece of text in several events so we group them
+    let mut text = String::new();
+
+    for event in events {
+        match event {
+            Event::Text(t) if protected_depth == 0 => {
+                text.push_str(&t);
+                continue;
+            }
+            Event::Start(Tag::Link(..)) | Event::Start(Tag::Image(..)) => protected_depth += 1,
+            Event::End(Tag::Link(..)) | Event::End(Tag::Image(..)) => protected_depth -= 1,
+            Event::Html(ref html) => {
+                protected_depth = (protected_depth + html_protected_depth_change(html)).max(0)
+            }
+            _ => (),
+        }
+        if !text.is_empty() {
+            replace(&text, &mut out);
+            text.clear();
+        }
+        out.push(event);
+    }
+    if !text.is_empty() {
+        replace(&text, &mut out);
+    }
+
+    out
+}
+
+/// Turns the email addresses found in text into `mailto:` links.
+/// Text in links, images, inline code and code blocks is left untouched.
+pub fn link_bare_emails(events: Vec<Event>, obfuscate: bool) -> Vec<Event> {
+    replace_text(events, |text, out| {
+        let mut last = 0;
+        for m in EMAIL_RE.find_iter(text) {
+            if m.start() > last {
+                out.push(Event::Text(text[last..m.start()].to_owned().into()));
+            }
+            out.push(Event::Html(email_link(m.as_str(), obfuscate).into()));
+            last = m.end();
+        }
+        if last < text.len() {
+            out.push(Event::Text(CowStr::from(text[last..].to_owned())));
+        }
+    })
+}
+
+/// A pattern to turn into a link whenever it is found in the text of the content,
+/// eg version tags linking to their release page
+#[derive(Debug, Clone)]
+pub struct AutolinkRule {
+    pattern: Regex,
+    base_url: String,
+    /// Only link matches with both digits and letters, to avoid linking words like `defaced`
+    mixed_hex_only: bool,
+    /// Only link matches not preceded by a word character, like mentions but not emails,
+    /// and append them to `base_url` without their first character, the `@`
+    mention: bool,
+}
+
+impl AutolinkRule {
+    /// The matched text is appended to `base_url` to build the link.
+    /// The pattern is only matched on whole words.
+    pub fn new(pattern: &str, base_url: &str) -> Result<AutolinkRule> {
+        let pattern = Regex::new(&format!(r"\b(?:{})\b", pattern))
+            .map_err(|e| Error::chain(format!("Invalid autolink pattern `{}`", pattern), e))?;
+        Ok(AutolinkRule {
+            pattern,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            mixed_hex_only: false,
+            mention: false,
+        })
+    }
+
+    /// Version tags like `v1.2.3` or `v1.0.0-beta.1`
+    pub fn version_tags(base_url: &str) -> AutolinkRule {
+        AutolinkRule::new(r"v\d+\.\d+\.\d+(?:-[0-9A-Za-z]+(?:\.[0-9A-Za-z]+)*)?", base_url)
+            .expect("Invalid version tag pattern")
+    }
+
+    /// Commit hashes, between 7 and 40 lowercase hexadecimal characters
+    pub fn commit_hashes(base_url: &str) -> AutolinkRule {
+        let mut rule =
+            AutolinkRule::new("[0-9a-f]{7,40}", base_url).expect("Invalid commit hash pattern");
+        rule.mixed_hex_only = true;
+        rule
+    }
+
+    /// Mentions like `@username`, linking to `base_url` followed by the username.
+    /// The `@` of email addresses is left alone.
+    pub fn mentions(base_url: &str) -> AutolinkRule {
+        AutolinkRule {
+            pattern: Regex::new(r"@[A-Za-z0-9_][A-Za-z0-9_-]*\b").expect("Invalid mention pattern"),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            mixed_hex_only: false,
+            mention: true,
+        }
+    }
+
+    /// The URL the matched text links to
+    fn url(&self, matched: &str) -> String {
+        if self.mention {
+            format!("{}/{}", self.base_url, &matched[1..])
+        } else {
+            format!("{}/{}", self.base_url, matched)
+        }
+    }
+
+    /// `before` is the text preceding the match
+    fn is_false_positive(&self, before: &str, matched: &str) -> bool {
+        if self.mention {
+            // An email address like `jane@example.com` or a word like `a@b`
+            return before
+                .chars()
+                .last()
+                .map(|c| c.is_alphanumeric() || "_.+-@/".contains(c))
+                .unwrap_or(false);
+        }
+        // Words like `defaced` or numbers like `1234567` are hexadecimal as well
+        self.mixed_hex_only
+            && (!matched.chars().any(|c| c.is_ascii_digit())
+                || !matched.chars().any(|c| c.is_ascii_alphabetic()))
+    }
+}
+
+/// Turns the text matching one of the rules into links.
+/// Text in links, images, inline code and code blocks is left untouched.
+pub fn autolink<'a>(events: Vec<Event<'a>>, rules: &[AutolinkRule]) -> Vec<Event<'a>> {
+    replace_text(events, |text, out| {
+        let mut matches: Vec<_> = rules
+            .iter()
+            .flat_map(|rule| rule.pattern.find_iter(text).map(move |m| (m, rule)))
+            .filter(|(m, rule)| !rule.is_false_positive(&text[..m.start()], m.as_str()))
+            .collect();
+        matches.sort_by_key(|(m, _)| m.start());
+
+        let mut last = 0;
+        for (m, rule) in matches {
+            // Overlapping matches, the first rule wins
+            if m.start() < last {
+                continue;
+            }
+            if m.start() > last {
+                out.push(Event::Text(text[last..m.start()].to_owned().into()));
+            }
+            let link = Tag::Link(LinkType::Inline, rule.url(m.as_str()).into(), "".into());
+            out.push(Event::Start(link.clone()));
+            out.push(Event::Text(m.as_str().to_owned().into()));
+            out.push(Event::End(link));
+            last = m.end();
+        }
+        if last < text.len() {
+            out.push(Event::Text(text[last..].to_owned().into()));
+        }
+    })
+}
+
+/// GFM removes the backslash of escaped pipes in the code spans of tables, as they need to be
+/// escaped there to not end the cell, but pulldown-cmark keeps it.
+pub fn unescape_table_code_pipes(events: &mut [Event]) {
+    let mut in_table = false;
+    for event in events.iter_mut() {
+        match event {
+            Event::Start(Tag::Table(_)) => in_table = true,
+            Event::End(Tag::Table(_)) => in_table = false,
+            Event::Code(code) if in_table && code.contains("\\|") => {
+                *code = code.replace("\\|", "|").into();
+            }
+            _ => (),
+        }
+    }
+}
+
+fn is_line_break(event: &Event) -> bool {
+    matches!(event, Event::Html(html) if matches!(html.trim(), "<br>" | "<br/>" | "<br />"))
+}
+
+fn is_empty_line(line: &[Event]) -> bool {
+    line.iter().all(|e| matches!(e, Event::Text(t) if t.trim().is_empty()))
+}
+
+fn is_list_item(line: &[Event]) -> bool {
+    matches!(line.first(), Some(Event::Text(t)) if t.starts_with("- ") || t.starts_with("* "))
+}
+
+/// Turns the content of a table cell into a list if all its lines (separated by `<br>`) start
+/// with `- ` or `* `, or into paragraphs if it has empty lines. Otherwise it's left as is.
+fn expand_cell(content: Vec<Event>) -> Vec<Event> {
+    let mut lines = vec![Vec::new()];
+    for event in content.iter() {
+        if is_line_break(event) {
+            lines.push(Vec::new());
+        } else {
+            lines.last_mut().unwrap().push(event.clone());
+        }
+    }
+
+    let mut out = Vec::with_capacity(content.len());
+    if lines.len() > 1 && lines.iter().all(|l| is_list_item(l)) {
+        out.push(Event::Html("<ul>".into()));
+        for mut line in lines {
+            if let Some(Event::Text(t)) = line.first_mut() {
+                *t = t[2..].to_owned().into();
+            }
+            out.push(Event::Html("<li>".into()));
+            out.extend(line);
+            out.push(Event::Html("</li>".into()));
+        }
+        out.push(Event::Html("</ul>".into()));
+    } else if lines.iter().any(|l| is_empty_line(l)) {
+        for paragraph in lines.split(|l| is_empty_line(l)).filter(|p| !p.is_empty()) {
+            out.push(Event::Html("<p>".into()));
+            for (i, line) in paragraph.iter().enumerate() {
+                if i > 0 {
+                    out.push(Event::Html("<br>".into()));
+                }
+                out.extend(line.iter().cloned());
+            }
+            out.push(Event::Html("</p>".into()));
+        }
+    } else {
+        return content;
+    }
+
+    out
+}
+
+/// Renders block content written in table cells using `<br>` to separate lines: lists and
+/// paragraphs separated by empty lines
+pub fn expand_table_cells(events: Vec<Event>) -> Vec<Event> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut cell = None;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::TableCell) => {
+                out.push(event);
+                cell = Some(Vec::new());
+            }
+            Event::End(Tag::TableCell) => {
+                if let Some(content) = cell.take() {
+                    out.extend(expand_cell(content));
+                }
+                out.push(event);
+            }
+            _ => match cell {
+                Some(ref mut content) => content.push(event),
+                None => out.push(event),
+            },
+        }
+    }
+
+    out
+}
+
+/// Removes the definitions of footnotes already defined earlier in the content, returning the
+/// labels of the ones removed
+pub fn remove_duplicate_footnotes(events: Vec<Event>) -> (Vec<Event>, Vec<String>) {
+    let mut defined = HashSet::new();
+    let mut duplicates = Vec::new();
+    let mut out = Vec::with_capacity(events.len());
+    let mut skipping = false;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::FootnoteDefinition(ref label)) => {
+                if defined.insert(label.to_string()) {
+                    out.push(event);
+                } else {
+                    duplicates.push(label.to_string());
+                    skipping = true;
+                }
+            }
+            Event::End(Tag::FootnoteDefinition(_)) if skipping => skipping = false,
+            _ if skipping => (),
+            _ => out.push(event),
+        }
+    }
+
+    (out, duplicates)
+}
+
+/// Removes the links and images without a URL, keeping the text of the links.
+/// Returns how many were removed
+pub fn remove_empty_links(events: Vec<Event>) -> (Vec<Event>, usize) {
+    let mut out = Vec::with_capacity(events.len());
+    let mut removed = 0;
+    let mut in_empty_image = false;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::Link(_, ref url, _)) if url.is_empty() => removed += 1,
+            Event::End(Tag::Link(_, ref url, _)) if url.is_empty() => (),
+            Event::Start(Tag::Image(_, ref url, _)) if url.is_empty() => {
+                removed += 1;
+                in_empty_image = true;
+            }
+            Event::End(Tag::Image(_, ref url, _)) if url.is_empty() => in_empty_image = false,
+            _ if in_empty_image => (),
+            _ => out.push(event),
+        }
+    }
+
+    (out, removed)
+}
+
+/// Removes the headings for which `is_empty` returns true when given their content,
+/// returning how many were removed
+pub fn remove_empty_headings<F>(events: Vec<Event>, is_empty: F) -> (Vec<Event>, usize)
+where
+    F: Fn(&[Event]) -> bool,
+{
+    let mut out: Vec<Event> = Vec::with_capacity(events.len());
+    let mut heading_start = 0;
+    let mut removed = 0;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::Heading(_)) => heading_start = out.len(),
+            Event::End(Tag::Heading(_)) if is_empty(&out[heading_start + 1..]) => {
+                out.truncate(heading_start);
+                removed += 1;
+                continue;
+            }
+            _ => (),
+        }
+        out.push(event);
+    }
+
+    (out, removed)
+}
+
+/// Adds the marker, some HTML, after every link to another website.
+/// Links to the site itself, anchors and email addresses are left as is.
+pub fn mark_external_links<'a>(events: Vec<Event<'a>>, marker: &str) -> Vec<Event<'a>> {
+    let mut out = Vec::with_capacity(events.len());
+
+    for event in events {
+        match event {
+            Event::End(Tag::Link(_, ref url, _)) if is_external_link(url) => {
+                out.push(event);
+                out.push(Event::Html(marker.to_string().into()));
+            }
+            _ => out.push(event),
+        }
+    }
+
+    out
+}
+
+/// Wraps every table in a `<div>` with the given class, eg to let wide tables scroll horizontally
+pub fn wrap_tables<'a>(events: Vec<Event<'a>>, class: &str) -> Vec<Event<'a>> {
+    let mut escaped = String::new();
+    escape_html(&mut escaped, class).expect("Could not write to buffer");
+    let opening = format!("<div class=\"{}\">\n", escaped);
+    let mut out = Vec::with_capacity(events.len());
+
+    for event in events {
+        match event {
+            Event::Start(Tag::Table(_)) => {
+                out.push(Event::Html(opening.clone().into()));
+                out.push(event);
+            }
+            Event::End(Tag::Table(_)) => {
+                out.push(event);
+                out.push(Event::Html("</div>\n".into()));
+            }
+            _ => out.push(event),
+        }
+    }
+
+    out
+}
+
+/// A fenced code block with a `group` in its fence
+#[derive(Debug)]
+pub struct GroupedCodeBlock {
+    pub group: String,
+    /// The language of the code block, shown in the label of its tab
+    pub label: String,
+    /// The range of the events rendering the code block
+    pub events: Range<usize>,
+}
+
+/// Renders the runs of code blocks following each other with the same group as a tab group.
+/// A code block alone in its group is left untouched.
+pub fn group_code_tabs<'a>(events: Vec<Event<'a>>, blocks: &[GroupedCodeBlock]) -> Vec<Event<'a>> {
+    // The HTML to insert before the event at that index
+    let mut inserts: BTreeMap<usize, String> = BTreeMap::new();
+    let mut start = 0;
+    while start < blocks.len() {
+        let mut end = start + 1;
+        while end < blocks.len()
+            && blocks[end].group == blocks[start].group
+            && blocks[end].events.start == blocks[end - 1].events.end
+        {
+            end += 1;
+        }
+
+        let run = &blocks[start..end];
+        start = end;
+        if run.len() < 2 {
+            continue;
+        }
+
+        let mut group = String::new();
+        escape_html(&mut group, &run[0].group).expect("Could not write to buffer");
+        let labels: Vec<String> = run
+            .iter()
+            .map(|block| {
+                let mut label = String::new();
+                escape_html(&mut label, &block.label).expect("Could not write to buffer");
+                label
+            })
+            .collect();
+        let mut opening = format!(
+            "<div class=\"code-tabs\" data-group=\"{}\">\n<div class=\"code-tab-labels\">",
+            group
+        );
+        for label in &labels {
+            opening.push_str(&format!("<span class=\"code-tab-label\">{}</span>", label));
+        }
+        opening.push_str("</div>\n");
+        inserts.entry(run[0].events.start).or_default().push_str(&opening);
+
+        for (block, label) in run.iter().zip(&labels) {
+            inserts
+                .entry(block.events.start)
+                .or_default()
+                .push_str(&format!("<div class=\"code-tab\" data-label=\"{}\">\n", label));
+            inserts.entry(block.events.end).or_default().push_str("</div>\n");
+        }
+        inserts.entry(run[run.len() - 1].events.end).or_default().push_str("</div>\n");
+    }
+
+    if inserts.is_empty() {
+        return events;
+    }
+
+    let len = events.len();
+    let mut out = Vec::with_capacity(len + inserts.len());
+    for (i, event) in events.into_iter().enumerate() {
+        if let Some(html) = inserts.remove(&i) {
+            out.push(Event::Html(html.into()));
+        }
+        out.push(event);
+    }
+    if let Some(html) = inserts.remove(&len) {
+        out.push(Event::Html(html.into()));
+    }
+
+    out
+}
+
+/// Renders the images alone in their paragraph in a `<figure>` instead, with the given
+/// caption taken from the image in a `<figcaption>`. Images without a caption and images
+/// within text are left untouched.
+pub fn caption_images<'a, F>(events: Vec<Event<'a>>, caption: F) -> Vec<Event<'a>>
+where
+    F: Fn(&CowStr, &[Event]) -> String,
+{
+    let mut out = Vec::with_capacity(events.len());
+    let mut i = 0;
+
+    while i < events.len() {
+        let is_standalone = matches!(events[i], Event::Start(Tag::Paragraph))
+            && matches!(events.get(i + 1), Some(Event::Start(Tag::Image(..))));
+        if is_standalone {
+            // The paragraph has to end right after the image
+            let image_end = events[i + 1..]
+                .iter()
+                .position(|e| matches!(e, Event::End(Tag::Image(..))))
+                .map(|p| i + 1 + p);
+            if let Some(end) = image_end {
+                if matches!(events.get(end + 1), Some(Event::End(Tag::Paragraph))) {
+                    let title = match events[end] {
+                        Event::End(Tag::Image(_, _, ref title)) => title,
+                        _ => unreachable!(),
+                    };
+                    let text = caption(title, &events[i + 2..end]);
+                    if !text.trim().is_empty() {
+                        let mut escaped = String::new();
+                        escape_html(&mut escaped, text.trim()).expect("Could not write to buffer");
+                        out.push(Event::Html("<figure>".into()));
+                        out.extend(events[i + 1..=end].iter().cloned());
+                        out.push(Event::Html(
+                            format!("<figcaption>{}</figcaption></figure>\n", escaped).into(),
+                        ));
+                        i = end + 2;
+                        continue;
+                    }
+                }
+            }
+        }
+        out.push(events[i].clone());
+        i += 1;
+    }
+
+    out
+}
+
+/// Merges the blockquotes only separated by blank lines into a single one, nested
+/// blockquotes included
+pub fn merge_adjacent_blockquotes(events: Vec<Event>) -> Vec<Event> {
+    let mut out = Vec::with_capacity(events.len());
+
+    for event in events {
+        if matches!(event, Event::Start(Tag::BlockQuote))
+            && matches!(out.last(), Some(Event::End(Tag::BlockQuote)))
+        {
+            out.pop();
+            continue;
+        }
+        out.push(event);
+    }
+
+    out
+}
+
+/// The markers starting the attribution line of a blockquote. The en dash is what `--` becomes
+/// with smart punctuation
+const ATTRIBUTION_MARKERS: &[&str] = &["—", "–", "--"];
+
+/// Removes the attribution marker from the events of a line, returning `None` if the line
+/// doesn't start with one or has nothing after it
+fn strip_attribution_marker<'a>(line: &[Event<'a>]) -> Option<Vec<Event<'a>>> {
+    let text = match line.first() {
+        Some(Event::Text(text)) => text.trim_start(),
+        _ => return None,
+    };
+    let rest = ATTRIBUTION_MARKERS.iter().find_map(|m| text.strip_prefix(m))?.trim_start();
+
+    let mut stripped = Vec::with_capacity(line.len());
+    let mut remaining = line[1..].iter().cloned();
+    if rest.is_empty() {
+        // With smart punctuation the dash is its own text event
+        match remaining.next() {
+            Some(Event::Text(next)) if !next.trim().is_empty() => {
+                stripped.push(Event::Text(next.trim_start().to_string().into()))
+            }
+            Some(event) => stripped.push(event),
+            None => return None,
+        }
+    } else {
+        stripped.push(Event::Text(rest.to_string().into()));
+    }
+    stripped.extend(remaining);
+    Some(stripped)
+}
+
+/// Renders the last line of a blockquote starting with `—` or `--`, eg `— Ada Lovelace`, as its
+/// attribution in the given element at the end of the blockquote, without the dash.
+/// Blockquotes without such a line are left untouched.
+pub fn attribute_blockquotes<'a>(events: Vec<Event<'a>>, element: &str) -> Vec<Event<'a>> {
+    let mut out = Vec::with_capacity(events.len());
+
+    for event in events {
+        if !matches!(event, Event::End(Tag::BlockQuote)) {
+            out.push(event);
+            continue;
+        }
+        // The attribution can only be in a paragraph ending the blockquote
+        if !matches!(out.last(), Some(Event::End(Tag::Paragraph))) {
+            out.push(event);
+            continue;
+        }
+        let end = out.len() - 1;
+        let start = out.iter().rposition(|e| matches!(e, Event::Start(Tag::Paragraph))).unwrap();
+        let line_start = out[start..end]
+            .iter()
+            .rposition(|e| matches!(e, Event::SoftBreak | Event::HardBreak))
+            .map(|i| start + i + 1)
+            .unwrap_or(start + 1);
+
+        if let Some(attribution) = strip_attribution_marker(&out[line_start..end]) {
+            if line_start == start + 1 {
+                // The attribution is the whole paragraph
+                out.truncate(start);
+            } else {
+                // Removes the line break before the attribution as well
+                out.truncate(line_start - 1);
+                out.push(Event::End(Tag::Paragraph));
+            }
+            out.push(Event::Html(format!("<{}>", element).into()));
+            out.extend(attribution);
+            out.push(Event::Html(format!("</{}>\n", element).into()));
+        }
+        out.push(event);
+    }
+
+    out
+}
+
+/// Turns back the curly quotes smart punctuation put right after a digit, like `6"` or `5'`,
+/// into straight quotes, or into primes if `primes` is set, as they are measurement marks.
+/// Quotes closing a quote opened earlier in the same block, like in `"1984"`, are left as is.
+pub fn fix_measurement_quotes(events: &mut [Event], primes: bool) {
+    // How many double and single quotes are open in the current block
+    let mut open_double = 0;
+    let mut open_single = 0;
+    for i in 0..events.len() {
+        let quote = match events[i] {
+            Event::Text(ref text) if matches!(text.as_ref(), "“" | "”" | "‘" | "’") => {
+                text.chars().next().unwrap()
+            }
+            Event::End(Tag::Paragraph | Tag::Heading(_) | Tag::Item | Tag::TableCell) => {
+                open_double = 0;
+                open_single = 0;
+                continue;
+            }
+            _ => continue,
+        };
+        let after_digit = match i.checked_sub(1).map(|j| &events[j]) {
+            Some(Event::Text(text)) => text.ends_with(|c: char| c.is_ascii_digit()),
+            _ => false,
+        };
+        // An apostrophe in a word, like `don’t`, doesn't close anything
+        let before_letter = match events.get(i + 1) {
+            Some(Event::Text(text)) => text.starts_with(char::is_alphanumeric),
+            _ => false,
+        };
+
+        let open = if matches!(quote, '“' | '”') { &mut open_double } else { &mut open_single };
+        if !after_digit {
+            match quote {
+                '“' | '‘' => *open += 1,
+                '’' if before_letter => (),
+                _ => *open = (*open - 1).max(0),
+            }
+            continue;
+        }
+        if matches!(quote, '”' | '’') && *open > 0 {
+            *open -= 1;
+            continue;
+        }
+
+        let mark = match (quote, primes) {
+            ('“' | '”', false) => "\"",
+            ('“' | '”', true) => "″",
+            (_, false) => "'",
+            (_, true) => "′",
+        };
+        events[i] = Event::Text(mark.into());
+    }
+}
+
+/// Renders the text that is both bold and italic, like `***x***` or `**_x_**`, consistently in
+/// `<strong><em>` whichever way it was written, or only in `<strong>` if `keep_italic` is false.
+/// Emphasis only partly overlapping, like `***a** b*`, is left as is.
+pub fn normalize_combined_emphasis(events: Vec<Event>, keep_italic: bool) -> Vec<Event> {
+    let mut events = events;
+    let mut removed = vec![false; events.len()];
+
+    for i in 0..events.len().saturating_sub(1) {
+        let (outer, inner) = match (&events[i], &events[i + 1]) {
+            (Event::Start(Tag::Emphasis), Event::Start(Tag::Strong)) => {
+                (Tag::Emphasis, Tag::Strong)
+            }
+            (Event::Start(Tag::Strong), Event::Start(Tag::Emphasis)) => {
+                (Tag::Strong, Tag::Emphasis)
+            }
+            _ => continue,
+        };
+        // The end of the inner element, which has to be followed by the end of the outer one
+        let mut depth = 0;
+        let mut inner_end = None;
+        for (j, event) in events.iter().enumerate().skip(i + 1) {
+            match event {
+                Event::Start(tag) if *tag == inner => depth += 1,
+                Event::End(tag) if *tag == inner => {
+                    depth -= 1;
+                    if depth == 0 {
+                        inner_end = Some(j);
+                        break;
+                    }
+                }
+                _ => (),
+            }
+        }
+        let j = match inner_end {
+            Some(j) if matches!(events.get(j + 1), Some(Event::End(tag)) if *tag == outer) => j,
+            _ => continue,
+        };
+
+        events[i] = Event::Start(Tag::Strong);
+        events[i + 1] = Event::Start(Tag::Emphasis);
+        events[j] = Event::End(Tag::Emphasis);
+        events[j + 1] = Event::End(Tag::Strong);
+        if !keep_italic {
+            removed[i + 1] = true;
+            removed[j] = true;
+        }
+    }
+
+    events.into_iter().zip(removed).filter(|(_, removed)| !removed).map(|(e, _)| e).collect()
+}
+
+/// Makes every ordered list start at 1, whatever the number of its first item
+pub fn normalize_list_starts(events: &mut [Event]) {
+    for event in events.iter_mut() {
+        if let Event::Start(Tag::List(Some(ref mut start))) = event {
+            *start = 1;
+        }
+    }
+}
+
+/// Shortens the text of the autolinks, like `<https://example.com/a/long/path>`, longer than
+/// `max_len` characters once the scheme is removed to their first `max_len` characters followed
+/// by `…`, eg `example.com/…`. The URL of the link is left as is.
+pub fn truncate_autolinks(events: &mut [Event], max_len: usize) {
+    let mut in_autolink = false;
+    for event in events.iter_mut() {
+        match event {
+            Event::Start(Tag::Link(LinkType::Autolink, ..)) => in_autolink = true,
+            Event::End(Tag::Link(LinkType::Autolink, ..)) => in_autolink = false,
+            Event::Text(text) if in_autolink => {
+                let display = text.split_once("://").map(|(_, rest)| rest).unwrap_or(text);
+                if display.chars().count() > max_len {
+                    let mut truncated: String = display.chars().take(max_len).collect();
+                    truncated.push('…');
+                    *text = truncated.into();
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Whether the content of a paragraph is only images, and the whitespace between them
+fn is_only_images(content: &[Event]) -> bool {
+    let mut image_depth = 0;
+    content.iter().all(|event| match event {
+        Event::Start(Tag::Image(..)) => {
+            image_depth += 1;
+            true
+        }
+        Event::End(Tag::Image(..)) => {
+            image_depth -= 1;
+            true
+        }
+        _ if image_depth > 0 => true,
+        Event::SoftBreak | Event::HardBreak => true,
+        Event::Text(text) => text.trim().is_empty(),
+        _ => false,
+    })
+}
+
+/// Adds the class to the first paragraph outside of any other element that isn't only images,
+/// like the lead paragraph of an article. With `mark_rtl`, it also gets `dir="rtl"` if its first
+/// letter is from a right-to-left script, as `mark_rtl_paragraphs` won't find it afterwards
+pub fn mark_lead_paragraph(events: &mut [Event], class: &str, mark_rtl: bool) {
+    let mut depth = 0;
+    for i in 0..events.len() {
+        match events[i] {
+            Event::Start(Tag::Paragraph) if depth == 0 => {
+                let len = events[i + 1..]
+                    .iter()
+                    .position(|e| matches!(e, Event::End(Tag::Paragraph)))
+                    .unwrap_or(events.len() - i - 1);
+                let content = &events[i + 1..i + 1 + len];
+                if !is_only_images(content) {
+                    let mut escaped = String::new();
+                    escape_html(&mut escaped, class).expect("Could not write to buffer");
+                    let dir = if mark_rtl && starts_with_rtl_letter(content) {
+                        " dir=\"rtl\""
+                    } else {
+                        ""
+                    };
+                    events[i] = Event::Html(format!("<p class=\"{}\"{}>", escaped, dir).into());
+                    return;
+                }
+                depth += 1;
+            }
+            // The start of headings has already been replaced by their HTML, but not their end
+            Event::Start(Tag::Heading(_)) | Event::End(Tag::Heading(_)) => (),
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => (),
+        }
+    }
+}
+
+/// Whether the character is from a right-to-left script, eg Hebrew or Arabic
+fn is_rtl_char(c: char) -> bool {
+    matches!(c as u32, 0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF | 0x10800..=0x10FFF | 0x1E800..=0x1EFFF)
+}
+
+/// Whether the first letter of the paragraph starting the events is from a right-to-left script
+fn starts_with_rtl_letter(events: &[Event]) -> bool {
+    events
+        .iter()
+        .take_while(|e| !matches!(e, Event::End(Tag::Paragraph)))
+        .filter_map(|e| match e {
+            Event::Text(t) | Event::Code(t) => Some(t),
+            _ => None,
+        })
+        .flat_map(|t| t.chars())
+        .find(|c| c.is_alphabetic())
+        .map(is_rtl_char)
+        .unwrap_or(false)
+}
+
+/// Sets `dir="rtl"` on the paragraphs whose first letter is from a right-to-left script
+pub fn mark_rtl_paragraphs(events: &mut [Event]) {
+    for i in 0..events.len() {
+        if matches!(events[i], Event::Start(Tag::Paragraph))
+            && starts_with_rtl_letter(&events[i + 1..])
+        {
+            events[i] = Event::Html("<p dir=\"rtl\">".into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_find_protected_html_elements() {
+        assert_eq!(html_protected_depth_change("<a href=\"/\">"), 1);
+        assert_eq!(html_protected_depth_change("<code>"), 1);
+        assert_eq!(html_protected_depth_change("</pre>"), -1);
+        assert_eq!(html_protected_depth_change("<abbr>"), 0);
+        assert_eq!(html_protected_depth_change("<div>"), 0);
+        assert_eq!(html_protected_depth_change("<a href=\"mailto:a@b.fr\">a@b.fr</a>"), 0);
+        assert_eq!(html_protected_depth_change("<code>x</code> <pre>"), 1);
+        assert_eq!(html_protected_depth_change("</code></pre>"), -2);
+    }
+
+    #[test]
+    fn can_obfuscate_email_links() {
+        assert_eq!(
+            email_link("a@b.fr", true),
+            "<a href=\"&#109;&#97;&#105;&#108;&#116;&#111;&#58;&#97;&#64;&#98;&#46;&#102;&#114;\">&#97;&#64;&#98;&#46;&#102;&#114;</a>"
+        );
+    }
+
+    #[test]
+    fn doesnt_autolink_hex_words_or_numbers() {
+        let rule = AutolinkRule::commit_hashes("https://example.com/commit");
+        assert!(rule.is_false_positive("", "defaced"));
+        assert!(rule.is_false_positive("", "1234567"));
+        assert!(!rule.is_false_positive("", "1a2b3c4"));
+        let rule = AutolinkRule::new(r"\d+", "https://example.com/issues").unwrap();
+        assert!(!rule.is_false_positive("", "1234567"));
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use config::Config;
+use front_matter::InsertAnchor;
+use rendering::RenderContext;
+use templates::ZOLA_TERA;
+
+fn permalinks() -> HashMap<String, String> {
+    let mut permalinks = HashMap::new();
+    for (path, permalink) in &[
+        ("_index.md", "https://example.com/"),
+        ("about.md", "https://example.com/about/"),
+        ("blog/_index.md", "https://example.com/blog/"),
+        ("blog/2021/_index.md", "https://example.com/blog/2021/"),
+        ("blog/2021/hello.md", "https://example.com/blog/2021/hello/"),
+    ] {
+        permalinks.insert(path.to_string(), permalink.to_string());
+    }
+    permalinks
+}
+
+fn section_titles() -> HashMap<String, String> {
+    let mut titles = HashMap::new();
+    titles.insert("_index.md".to_string(), "Home".to_string());
+    titles.insert("blog/_index.md".to_string(), "Blog".to_string());
+    titles.insert("blog/2021/_index.md".to_string(), "2021".to_string());
+    titles
+}
+
+#[test]
+fn can_make_breadcrumbs_of_nested_page() {
+    let config = Config::default_for_test();
+    let permalinks = permalinks();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks,
+        InsertAnchor::None,
+    );
+
+    assert_eq!(
+        context.breadcrumbs("blog/2021/hello.md", "Hello", &section_titles()),
+        vec![
+            ("Blog".to_string(), "https://example.com/blog/".to_string()),
+            ("2021".to_string(), "https://example.com/blog/2021/".to_string()),
+            ("Hello".to_string(), "https://example.com/blog/2021/hello/".to_string()),
+        ]
+    );
+    assert_eq!(
+        context.breadcrumbs("blog/2021/_index.md", "2021", &section_titles()),
+        vec![
+            ("Blog".to_string(), "https://example.com/blog/".to_string()),
+            ("2021".to_string(), "https://example.com/blog/2021/".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn can_make_breadcrumbs_of_top_level_page() {
+    let config = Config::default_for_test();
+    let permalinks = permalinks();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks,
+        InsertAnchor::None,
+    );
+
+    assert_eq!(
+        context.breadcrumbs("about.md", "About", &section_titles()),
+        vec![("About".to_string(), "https://example.com/about/".to_string())]
+    );
+}
@@ -0,0 +1,44 @@
+use rendering::{extract_code_blocks, CodeBlock};
+
+#[test]
+fn can_extract_code_blocks() {
+    let content = r#"
+# Samples
+
+```rust,linenos,hl_lines=2
+fn main() {
+    println!("Hello");
+}
+```
+
+Some `inline` code and a list:
+
+- item
+
+    ```py
+    print("Hello")
+    ```
+"#;
+    assert_eq!(
+        extract_code_blocks(content),
+        vec![
+            CodeBlock {
+                lang: Some("rust".to_string()),
+                content: "fn main() {\n    println!(\"Hello\");\n}\n".to_string(),
+            },
+            CodeBlock { lang: Some("py".to_string()), content: "print(\"Hello\")\n".to_string() },
+        ]
+    );
+}
+
+#[test]
+fn can_extract_code_blocks_without_language() {
+    let content = "```\nplain\n```\n\n    indented\n";
+    assert_eq!(
+        extract_code_blocks(content),
+        vec![
+            CodeBlock { lang: None, content: "plain\n".to_string() },
+            CodeBlock { lang: None, content: "indented\n".to_string() },
+        ]
+    );
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use tera::Tera;
+
+use config::Config;
+use front_matter::InsertAnchor;
+use rendering::{highlight_code, render_content, HighlightOptions, RenderContext};
+
+/// Renders the code in a fenced code block with highlighting enabled
+fn render_fenced(fence: &str, code: &str, theme: &str) -> String {
+    let tera_ctx = Tera::default();
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.highlight_code = true;
+    config.markdown.highlight_theme = theme.to_string();
+    let context = RenderContext::new(
+        &tera_ctx,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    render_content(&format!("```{}\n{}```", fence, code), &context).unwrap().body
+}
+
+#[test]
+fn can_highlight_code_like_a_code_block() {
+    let config = Config::default_for_test();
+    let code = "fn main() {\n    println!(\"Hello\");\n}\n";
+    let res = highlight_code(
+        code,
+        Some("rust"),
+        "base16-ocean-dark",
+        HighlightOptions::default(),
+        &config,
+    )
+    .unwrap();
+    assert!(res.starts_with(
+        "<pre data-lang=\"rust\" style=\"background-color:#2b303b;color:#c0c5ce;\" class=\"language-rust \">"
+    ));
+    assert_eq!(res, render_fenced("rust", code, "base16-ocean-dark"));
+
+    // The trailing newline is optional
+    let res = highlight_code(
+        "x = 1",
+        Some("py"),
+        "base16-ocean-dark",
+        HighlightOptions::default(),
+        &config,
+    )
+    .unwrap();
+    assert_eq!(res, render_fenced("py", "x = 1\n", "base16-ocean-dark"));
+}
+
+#[test]
+fn can_highlight_code_with_options() {
+    let config = Config::default_for_test();
+    let code = "x = 1\ny = 2\nz = 3\n";
+    let options = HighlightOptions {
+        line_numbers: true,
+        line_number_start: 3,
+        highlight_lines: vec![2..=2],
+        hide_lines: vec![3..=3],
+    };
+    let res = highlight_code(code, Some("py"), "css", options, &config).unwrap();
+    assert_eq!(res, render_fenced("py,linenos,linenostart=3,hl_lines=2,hide_lines=3", code, "css"));
+    assert!(res.starts_with("<pre data-linenos data-lang=\"py\" class=\"language-py z-code\">"));
+    assert!(res.contains("<tr><td><mark>4</mark></td>"));
+    assert_eq!(res.matches("<tr>").count(), 2);
+}
+
+#[test]
+fn errors_on_unknown_highlight_theme() {
+    let mut config = Config::default_for_test();
+    let res =
+        highlight_code("x = 1", Some("py"), "no-such-theme", HighlightOptions::default(), &config);
+    assert_eq!(res.unwrap_err().to_string(), "Highlight theme no-such-theme not found");
+
+    config.markdown.highlight_theme_fallback = true;
+    let res =
+        highlight_code("x = 1", Some("py"), "no-such-theme", HighlightOptions::default(), &config);
+    assert_eq!(res.unwrap(), render_fenced("py", "x = 1\n", "base16-ocean-dark"));
+}
@@ -36,6 +36,38 @@ bar
     );
 }
 
+#[test]
+fn can_add_copyable_line_numbers() {
+    let tera_ctx = Tera::default();
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.highlight_code = true;
+    config.markdown.copyable_line_numbers = true;
+    let context = RenderContext::new(
+        &tera_ctx,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content(
+        r#"
+```linenos, linenostart=3, hl_lines=2
+foo
+bar
+```
+    "#,
+        &context,
+    )
+    .unwrap();
+    // The numbers are only in attributes, not in the text of the code
+    assert_eq!(
+        res.body,
+        "<pre data-linenos style=\"background-color:#2b303b;color:#c0c5ce;\"><code><span data-line=\"3\"><span>foo\n</span></span><span data-line=\"4\"><mark style=\"background-color:#65737e30;\"><span>bar\n</span></mark></span></code></pre>\n"
+    );
+}
+
 #[test]
 fn can_add_line_numbers_with_linenostart() {
     let tera_ctx = Tera::default();
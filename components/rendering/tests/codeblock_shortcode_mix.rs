@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use tera::Tera;
+
 use config::Config;
 use front_matter::InsertAnchor;
 use templates::ZOLA_TERA;
@@ -268,4 +270,60 @@ r#"<pre data-linenos data-lang="html" style="background-color:#2b303b;color:#c0c
 </span></tr></tbody></table></code></pre>
 "#
     );
-}
\ No newline at end of file
+}
+
+#[test]
+fn can_protect_code_from_shortcodes() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.protect_code_from_shortcodes = true;
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("shortcodes/real.html", "<b>{{ text | default(value='real') }}</b>")
+        .unwrap();
+    tera.add_raw_template("shortcodes/real_md.md", "**real**").unwrap();
+    tera.add_raw_template("shortcodes/note.html", "<aside>{{ body }}</aside>").unwrap();
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let res = render_content(
+        r#"```
+{{ real() }} {{ real_md() }}
+{% note() %}hey{% end %}
+```"#,
+        &context,
+    )
+    .unwrap();
+    assert_eq!(
+        res.body,
+        "<pre><code>{{ real() }} {{ real_md() }}\n{% note() %}hey{% end %}\n</code></pre>\n"
+    );
+
+    let res = render_content(
+        "Use `{{ real() }}` to get {{ real() }} and {{ real(text=`a`) }} and {{ real_md() }}",
+        &context,
+    )
+    .unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Use <code>{{ real() }}</code> to get <b>real</b> and <b>a</b> and <strong>real</strong></p>\n"
+    );
+
+    // Code in the body of a shortcode is protected as well
+    let res = render_content("{% note() %}`{{ real() }}`{% end %}", &context).unwrap();
+    assert_eq!(res.body, "<aside>`{{ real() }}`</aside>");
+
+    // The existing behaviour is kept when the option is not set
+    let default_config = Config::default_for_test();
+    context.config = &default_config;
+    let res = render_content("```\n{{ real() }}\n```", &context).unwrap();
+    assert_eq!(res.body, "<pre><code>&lt;b&gt;real&lt;&#x2F;b&gt;\n</code></pre>\n");
+}
@@ -60,3 +60,89 @@ fn can_detect_links() {
     let res = render_content("[abc](@/def/123.md)", HashMap::new());
     assert!(res.is_err());
 }
+
+#[test]
+fn can_collect_image_sources() {
+    let rendered = render_content("Hello World!", HashMap::new()).unwrap();
+    assert_eq!(rendered.images.len(), 0);
+
+    let rendered = render_content(
+        r#"![one](one.png) and ![two](https://example.com/two.jpg "Two")
+
+<figure><img class="big" src="three.webp"></figure>
+
+[not an image](four.png)"#,
+        HashMap::new(),
+    )
+    .unwrap();
+    assert_eq!(rendered.images, vec!["one.png", "https://example.com/two.jpg", "three.webp"]);
+}
+
+#[test]
+fn can_find_cover_image() {
+    let rendered = render_content("Hello World!", HashMap::new()).unwrap();
+    assert_eq!(rendered.cover_image, None);
+
+    let rendered =
+        render_content("Intro\n\n![first](first.png)\n\n![second](second.png)", HashMap::new())
+            .unwrap();
+    assert_eq!(rendered.cover_image, Some("first.png".to_string()));
+}
+
+#[test]
+fn can_skip_images_in_ignored_containers_for_cover_image() {
+    let mut config = config::Config::default_for_test();
+    config.markdown.cover_image_ignored_containers = vec!["blockquote".into(), "aside".into()];
+    let tera = tera::Tera::default();
+    let permalinks = HashMap::new();
+    let context = rendering::RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "http://mypage.com",
+        &permalinks,
+        front_matter::InsertAnchor::None,
+    );
+
+    let rendered = rendering::render_content(
+        r#"> ![quoted](quoted.png)
+
+<aside><img src="aside.png"></aside>
+
+<p><img src="cover.png"></p>
+
+![other](other.png)"#,
+        &context,
+    )
+    .unwrap();
+    assert_eq!(rendered.images, vec!["quoted.png", "aside.png", "cover.png", "other.png"]);
+    assert_eq!(rendered.cover_image, Some("cover.png".to_string()));
+
+    let rendered = rendering::render_content("> ![quoted](quoted.png)", &context).unwrap();
+    assert_eq!(rendered.cover_image, None);
+}
+
+#[test]
+fn can_add_trailing_slash_to_links() {
+    let mut config = config::Config::default_for_test();
+    config.markdown.trailing_slash_links = true;
+    let tera = tera::Tera::default();
+    let permalinks = HashMap::new();
+    let context = rendering::RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "http://mypage.com",
+        &permalinks,
+        front_matter::InsertAnchor::None,
+    );
+    let body = |content: &str| rendering::render_content(content, &context).unwrap().body;
+
+    assert_eq!(body("[a](/blog/hello)"), "<p><a href=\"/blog/hello/\">a</a></p>\n");
+    assert_eq!(body("[a](../hello#part)"), "<p><a href=\"../hello/#part\">a</a></p>\n");
+    assert_eq!(body("[a](/search?q=zola)"), "<p><a href=\"/search/?q=zola\">a</a></p>\n");
+    // Left untouched
+    for link in &["/blog/", "/file.pdf", "#part", "https://example.com/page", "mailto:a@b.c"] {
+        assert_eq!(body(&format!("[a]({})", link)), format!("<p><a href=\"{}\">a</a></p>\n", link));
+    }
+}
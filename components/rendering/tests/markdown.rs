@@ -1,12 +1,21 @@
 use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
 
 use tera::Tera;
 
-use config::Config;
+use config::{
+    BlockquoteAttribution, Config, DuplicateFootnotes, EmptyHeadings, EmptyLinks, ImageCaptions,
+    LastModifiedPosition, ListSpacing, MeasurementQuotes, StyleAttributes, TextDirection,
+};
 use front_matter::InsertAnchor;
-use rendering::{render_content, RenderContext};
+use rendering::{
+    heading_ids, render_content, render_content_with_timeout, render_events, render_summary,
+    AutolinkRule, Event, RenderContext, Tag,
+};
 use templates::ZOLA_TERA;
 use utils::slugs::SlugifyStrategy;
+use utils::templates::{ShortcodeArg, ShortcodeArgType};
 
 #[test]
 fn can_do_render_content_simple() {
@@ -442,102 +451,178 @@ A quote
 }
 
 #[test]
-fn errors_rendering_unknown_shortcode() {
-    let tera_ctx = Tera::default();
+fn can_trim_output_of_inline_shortcodes() {
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("shortcodes/icon.html", "\n  <i>{{ name }}</i>\n").unwrap();
+    tera.add_raw_template("shortcodes/icon_md.md", " *{{ name }}* ").unwrap();
+    tera.add_raw_template("shortcodes/note.html", "\n<aside>{{ body }}</aside>\n").unwrap();
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
-    let context = RenderContext::new(
-        &tera_ctx,
+    let mut context = RenderContext::new(
+        &tera,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("{{ hello(flash=true) }}", &context);
-    assert!(res.is_err());
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+    let content =
+        "A {{ icon(name=\"star\") }} and {{ icon_md(name=\"md\") }} B\n\n{% note() %}Hi{% end %}";
+
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(res.body, "<p>A \n  <i>star</i>\n and  <em>md</em>  B</p>\n\n<aside>Hi</aside>\n");
+
+    // Shortcodes with a body keep their whitespace
+    context.set_trim_inline_shortcodes(true);
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(res.body, "<p>A <i>star</i> and <em>md</em> B</p>\n\n<aside>Hi</aside>\n");
 }
 
 #[test]
-fn can_make_valid_relative_link() {
-    let mut permalinks = HashMap::new();
-    permalinks.insert("pages/about.md".to_string(), "https://vincent.is/about".to_string());
-    let tera_ctx = Tera::default();
+fn can_match_shortcode_names_ignoring_case() {
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("shortcodes/video.html", "<video src=\"{{ id }}\"></video>").unwrap();
+    let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
-    let context = RenderContext::new(
-        &tera_ctx,
+    let mut context = RenderContext::new(
+        &tera,
         &config,
         &config.default_language,
         "",
-        &permalinks,
+        &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content(
-        r#"[rel link](@/pages/about.md), [abs link](https://vincent.is/about)"#,
-        &context,
-    )
-    .unwrap();
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+    let content = "{{ Video(id=\"abc\") }}";
 
-    assert!(
-        res.body.contains(r#"<p><a href="https://vincent.is/about">rel link</a>, <a href="https://vincent.is/about">abs link</a></p>"#)
-    );
+    let res = render_content(content, &context);
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .starts_with("Found usage of a shortcode named `Video` but we do not know about."));
+
+    context.set_case_insensitive_shortcodes(true);
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(res.body, "<video src=\"abc\"></video>");
 }
 
 #[test]
-fn can_make_relative_links_with_anchors() {
-    let mut permalinks = HashMap::new();
-    permalinks.insert("pages/about.md".to_string(), "https://vincent.is/about".to_string());
-    let tera_ctx = Tera::default();
+fn only_renders_allowlisted_shortcodes() {
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("shortcodes/video.html", "<video src=\"{{ id }}\"></video>").unwrap();
+    tera.add_raw_template("shortcodes/script.html", "<script>{{ body }}</script>").unwrap();
+    let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
-    let context = RenderContext::new(
-        &tera_ctx,
+    let mut context = RenderContext::new(
+        &tera,
         &config,
         &config.default_language,
         "",
-        &permalinks,
+        &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content(r#"[rel link](@/pages/about.md#cv)"#, &context).unwrap();
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+    context.set_shortcode_allowlist(vec!["video".to_string()]);
+    let content =
+        "Nice {% script() %}alert(1){% end %} video {{ unknown() }}\n\n{{ video(id=\"abc\") }}";
 
-    assert!(res.body.contains(r#"<p><a href="https://vincent.is/about#cv">rel link</a></p>"#));
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(res.body, "<p>Nice  video </p>\n<video src=\"abc\"></video>");
 }
 
 #[test]
-fn errors_relative_link_inexistant() {
-    let tera_ctx = Tera::default();
+fn can_insert_output_of_md_shortcodes_as_html() {
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template(
+        "shortcodes/card.md",
+        "<div class=\"card\">\n\n    <p>{{ body }}</p>\n</div>",
+    )
+    .unwrap();
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
-    let context = RenderContext::new(
-        &tera_ctx,
+    let mut context = RenderContext::new(
+        &tera,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("[rel link](@/pages/about.md)", &context);
-    assert!(res.is_err());
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+    let content = "{% card() %}Some *text*{% end %}";
+
+    // The output is rendered as markdown: the indented line becomes a code block
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<div class=\"card\">\n<pre><code>&lt;p&gt;Some *text*&lt;&#x2F;p&gt;\n</code></pre>\n</div>"
+    );
+
+    let mut html_output_def = shortcode_def.clone();
+    utils::templates::set_shortcodes_with_html_output(&mut html_output_def, &["card".to_string()]);
+    context.set_shortcode_definitions(&html_output_def);
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(res.body, "<div class=\"card\">\n\n    <p>Some *text*</p>\n</div>");
 }
 
 #[test]
-fn can_add_id_to_headings() {
-    let tera_ctx = Tera::default();
+fn checks_the_types_of_shortcode_arguments() {
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("shortcodes/video.html", "<video src=\"{{ id }}\"></video>").unwrap();
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
-    let context = RenderContext::new(
-        &tera_ctx,
+    let mut context = RenderContext::new(
+        &tera,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content(r#"# Hello"#, &context).unwrap();
-    assert_eq!(res.body, "<h1 id=\"hello\">Hello</h1>\n");
+    let mut shortcode_def = utils::templates::get_shortcodes(&tera);
+    let mut types = HashMap::new();
+    types.insert("id".to_string(), ShortcodeArg::required(ShortcodeArgType::String));
+    types.insert("autoplay".to_string(), ShortcodeArg::optional(ShortcodeArgType::Boolean));
+    types.insert("width".to_string(), ShortcodeArg::optional(ShortcodeArgType::Number));
+    let mut arg_types = HashMap::new();
+    arg_types.insert("video".to_string(), types);
+    utils::templates::set_shortcode_arg_types(&mut shortcode_def, &arg_types);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let res = render_content("{{ video(id=\"abc\", autoplay=true, width=640) }}", &context);
+    assert_eq!(res.unwrap().body, "<video src=\"abc\"></video>");
+
+    let res = render_content("{{ video(id=\"abc\", autoplay=\"yes\") }}", &context);
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "The `autoplay` argument of the shortcode `video` should be a boolean but is a string"
+    );
+
+    let res = render_content("{{ video(id=\"abc\", width=\"wide\") }}", &context);
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "The `width` argument of the shortcode `video` should be a number but is a string"
+    );
+
+    let res = render_content("{{ video(autoplay=true) }}", &context);
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "The shortcode `video` is missing its required `id` argument"
+    );
 }
 
 #[test]
-fn can_add_id_to_headings_same_slug() {
+fn errors_rendering_unknown_shortcode() {
     let tera_ctx = Tera::default();
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
@@ -549,16 +634,22 @@ fn can_add_id_to_headings_same_slug() {
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("# Hello\n# Hello", &context).unwrap();
-    assert_eq!(res.body, "<h1 id=\"hello\">Hello</h1>\n<h1 id=\"hello-1\">Hello</h1>\n");
+    let res = render_content("{{ hello(flash=true) }}", &context);
+    assert!(res.is_err());
 }
 
 #[test]
-fn can_add_non_slug_id_to_headings() {
-    let tera_ctx = Tera::default();
+fn can_render_unknown_shortcode_with_fallback_template() {
+    let mut tera_ctx = Tera::default();
+    tera_ctx
+        .add_raw_template(
+            "unknown-shortcode.html",
+            "<div class=\"error\">Unknown shortcode: {{ name }} {{ args | json_encode() | safe }}{% if body %} {{ body }}{% endif %}</div>",
+        )
+        .unwrap();
     let permalinks_ctx = HashMap::new();
     let mut config = Config::default_for_test();
-    config.slugify.anchors = SlugifyStrategy::Safe;
+    config.markdown.unknown_shortcode_template = Some("unknown-shortcode.html".to_string());
     let context = RenderContext::new(
         &tera_ctx,
         &config,
@@ -567,538 +658,2599 @@ fn can_add_non_slug_id_to_headings() {
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content(r#"# L'écologie et vous"#, &context).unwrap();
-    assert_eq!(res.body, "<h1 id=\"L'écologie_et_vous\">L'écologie et vous</h1>\n");
+    let res =
+        render_content("{{ hello(flash=true) }}\n\n{% quote() %}Hi{% end %}", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<div class=\"error\">Unknown shortcode: hello {\"flash\":true}</div><div class=\"error\">Unknown shortcode: quote {} Hi</div>"
+    );
 }
 
 #[test]
-fn can_handle_manual_ids_on_headings() {
-    let tera_ctx = Tera::default();
+fn can_expand_tabs_before_parsing() {
     let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
-    let context = RenderContext::new(
-        &tera_ctx,
+    let mut config = Config::default_for_test();
+    config.markdown.expand_tabs = Some(2);
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    // Tested things: manual IDs; whitespace flexibility; that automatic IDs avoid collision with
-    // manual IDs; that duplicates are in fact permitted among manual IDs; that any non-plain-text
-    // in the middle of `{#…}` will disrupt it from being acknowledged as a manual ID (that last
-    // one could reasonably be considered a bug rather than a feature, but test it either way); one
-    // workaround for the improbable case where you actually want `{#…}` at the end of a heading.
-    let res = render_content(
-        "\
-         # Hello\n\
-         # Hello{#hello}\n\
-         # Hello {#hello}\n\
-         # Hello     {#Something_else} \n\
-         # Workaround for literal {#…&#125;\n\
-         # Hello\n\
-         # Auto {#*matic*}",
-        &context,
-    )
-    .unwrap();
+    // A tab indents like the 2 spaces of the other items: both are nested in the first one
+    let content = "- a\n  - b\n\t- c\n\n```\n\tcode\n```\n";
+    let res = render_content(content, &context).unwrap();
     assert_eq!(
         res.body,
-        "\
-         <h1 id=\"hello-1\">Hello</h1>\n\
-         <h1 id=\"hello\">Hello</h1>\n\
-         <h1 id=\"hello\">Hello</h1>\n\
-         <h1 id=\"Something_else\">Hello</h1>\n\
-         <h1 id=\"workaround-for-literal\">Workaround for literal {#…}</h1>\n\
-         <h1 id=\"hello-2\">Hello</h1>\n\
-         <h1 id=\"auto-matic\">Auto {#<em>matic</em>}</h1>\n\
-         "
+        "<ul>\n<li>a\n<ul>\n<li>b</li>\n<li>c</li>\n</ul>\n</li>\n</ul>\n<pre><code>\tcode\n</code></pre>\n"
+    );
+
+    // Without expanding, a tab is 4 columns wide so `c` is nested in `b`
+    context.set_expand_tabs(None);
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<ul>\n<li>a\n<ul>\n<li>b\n<ul>\n<li>c</li>\n</ul>\n</li>\n</ul>\n</li>\n</ul>\n<pre><code>\tcode\n</code></pre>\n"
     );
 }
 
 #[test]
-fn blank_headings() {
-    let tera_ctx = Tera::default();
-    let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
+fn can_mark_external_links() {
+    let mut permalinks_ctx = HashMap::new();
+    permalinks_ctx.insert("pages/about.md".to_string(), "https://vincent.is/about".to_string());
+    let mut config = Config::default_for_test();
+    config.markdown.external_link_marker =
+        Some("<span class=\"external-icon\"></span>".to_string());
     let context = RenderContext::new(
-        &tera_ctx,
+        &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("# \n#\n# {#hmm} \n# {#}", &context).unwrap();
+    let res = render_content(
+        "[ext](https://getzola.org) [page](@/pages/about.md) [anchor](#top) [mail](mailto:a@b.com) <https://example.com> [rel](/blog/)",
+        &context,
+    )
+    .unwrap();
     assert_eq!(
         res.body,
-        "<h1 id=\"-1\"></h1>\n<h1 id=\"-2\"></h1>\n<h1 id=\"hmm\"></h1>\n<h1 id=\"\"></h1>\n"
+        "<p><a href=\"https://getzola.org\">ext</a><span class=\"external-icon\"></span> <a href=\"https://vincent.is/about\">page</a> <a href=\"#top\">anchor</a> <a href=\"mailto:a@b.com\">mail</a> <a href=\"https://example.com\">https://example.com</a><span class=\"external-icon\"></span> <a href=\"/blog/\">rel</a></p>\n"
     );
 }
 
 #[test]
-fn can_insert_anchor_left() {
+fn can_truncate_long_autolinks() {
     let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
+    let mut config = Config::default_for_test();
+    config.markdown.truncate_autolinks = Some(12);
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
-        InsertAnchor::Left,
+        InsertAnchor::None,
     );
-    let res = render_content("# Hello", &context).unwrap();
+    let res = render_content(
+        "<https://example.com/a/long/path> <https://a.io> [https://example.com/a/long/path](https://example.com/a/long/path)",
+        &context,
+    )
+    .unwrap();
     assert_eq!(
         res.body,
-        "<h1 id=\"hello\"><a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a>Hello</h1>\n"
+        "<p><a href=\"https://example.com/a/long/path\">example.com/…</a> <a href=\"https://a.io\">https://a.io</a> <a href=\"https://example.com/a/long/path\">https://example.com/a/long/path</a></p>\n"
     );
 }
 
 #[test]
-fn can_insert_anchor_right() {
+fn can_add_article_microdata() {
     let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
-    let context = RenderContext::new(
+    let mut config = Config::default_for_test();
+    config.markdown.article_microdata = true;
+    let mut context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
-        InsertAnchor::Right,
+        InsertAnchor::None,
     );
-    let res = render_content("# Hello", &context).unwrap();
+    let mut page = HashMap::new();
+    page.insert("relative_path", "blog/post.md");
+    page.insert("date", "2021-05-01");
+    context.tera_context.insert("page", &page);
+    let res = render_content("# Title\n\nIntro\n<!-- more -->\n## Other", &context).unwrap();
     assert_eq!(
         res.body,
-        "<h1 id=\"hello\">Hello<a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a></h1>\n"
+        "<div itemscope itemtype=\"https://schema.org/Article\">\n<meta itemprop=\"datePublished\" content=\"2021-05-01\">\n<h1 id=\"title\" itemprop=\"headline\">Title</h1>\n<p>Intro</p>\n<span id=\"continue-reading\"></span><h2 id=\"other\">Other</h2>\n</div>\n"
+    );
+    assert_eq!(
+        res.summary().unwrap(),
+        "<div itemscope itemtype=\"https://schema.org/Article\">\n<meta itemprop=\"datePublished\" content=\"2021-05-01\">\n<h1 id=\"title\" itemprop=\"headline\">Title</h1>\n<p>Intro</p>\n</div>\n"
     );
 }
 
 #[test]
-fn can_insert_anchor_for_multi_heading() {
+fn can_trim_trailing_whitespace() {
     let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
+    let mut config = Config::default_for_test();
+    config.markdown.trim_trailing_whitespace = true;
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
-        InsertAnchor::Right,
+        InsertAnchor::None,
     );
-    let res = render_content("# Hello\n# World", &context).unwrap();
+    let res = render_content(
+        "<div>  \n  <span>hi</span>\t\n</div>\n\n```\ncode  \n```\n\n<div><textarea>a  \nb  \n</textarea></div>  \n",
+        &context,
+    )
+    .unwrap();
     assert_eq!(
         res.body,
-        "<h1 id=\"hello\">Hello<a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a></h1>\n\
-<h1 id=\"world\">World<a class=\"zola-anchor\" href=\"#world\" aria-label=\"Anchor link for: world\">🔗</a></h1>\n"
+        "<div>\n  <span>hi</span>\n</div>\n<pre><code>code  \n</code></pre>\n<div><textarea>a  \nb  \n</textarea></div>\n"
     );
 }
 
-// See https://github.com/Keats/gutenberg/issues/42
 #[test]
-fn can_insert_anchor_with_exclamation_mark() {
+fn can_make_heading_progress_map() {
     let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
+    let mut config = Config::default_for_test();
+    config.markdown.heading_progress_map = true;
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
-        InsertAnchor::Left,
-    );
-    let res = render_content("# Hello!", &context).unwrap();
-    assert_eq!(
-        res.body,
-        "<h1 id=\"hello\"><a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a>Hello!</h1>\n"
+        InsertAnchor::None,
     );
+    let content = "Intro\n\n# First\n\nSome text\n\n## Nested\n\nMore text\n\n# First\n\nThe end";
+    let res = render_content(content, &context).unwrap();
+    let map = res.progress_map.unwrap();
+
+    let ids: Vec<&str> = map.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, vec!["first", "nested", "first-1"]);
+    assert!(map[0].1 > 0.0);
+    assert!(map.windows(2).all(|w| w[0].1 < w[1].1));
+    assert!(map[2].1 < 1.0);
+    assert_eq!(map[0].1, res.body.find("<h1 id=\"first\"").unwrap() as f64 / res.body.len() as f64);
 }
 
-// See https://github.com/Keats/gutenberg/issues/53
 #[test]
-fn can_insert_anchor_with_link() {
+fn can_add_class_to_lead_paragraph() {
     let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
-    let context = RenderContext::new(
+    let mut config = Config::default_for_test();
+    config.markdown.lead_paragraph_class = Some("lead".to_owned());
+    let mut context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
-        InsertAnchor::Left,
+        InsertAnchor::None,
     );
-    let res = render_content("## [Rust](https://rust-lang.org)", &context).unwrap();
+    let content = "![cover](cover.png)\n\n> Quote\n\nFirst *paragraph*\n\nSecond paragraph";
+    let res = render_content(content, &context).unwrap();
     assert_eq!(
         res.body,
-        "<h2 id=\"rust\"><a class=\"zola-anchor\" href=\"#rust\" aria-label=\"Anchor link for: rust\">🔗</a><a href=\"https://rust-lang.org\">Rust</a></h2>\n"
+        "<p><img src=\"cover.png\" alt=\"cover\" /></p>\n<blockquote>\n<p>Quote</p>\n</blockquote>\n<p class=\"lead\">First <em>paragraph</em></p>\n<p>Second paragraph</p>\n"
+    );
+
+    let res = render_content("# Title\n\nFirst\n\nSecond", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"title\">Title</h1>\n<p class=\"lead\">First</p>\n<p>Second</p>\n"
     );
+
+    context.set_lead_paragraph_class(None);
+    let res = render_content(content, &context).unwrap();
+    assert!(!res.body.contains("class=\"lead\""));
 }
 
 #[test]
-fn can_insert_anchor_with_other_special_chars() {
+fn can_handle_empty_headings() {
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
-    let context = RenderContext::new(
+    let mut context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
-        InsertAnchor::Left,
+        InsertAnchor::None,
     );
-    let res = render_content("# Hello*_()", &context).unwrap();
+    let content = "## \n\nText\n\n#\n\n# Title";
+
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(res.body, "<p>Text</p>\n<h1 id=\"title\">Title</h1>\n");
+    assert_eq!(res.toc.len(), 1);
+
+    context.set_empty_headings(EmptyHeadings::Fallback);
+    let res = render_content(content, &context).unwrap();
     assert_eq!(
         res.body,
-        "<h1 id=\"hello\"><a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a>Hello*_()</h1>\n"
+        "<h2 id=\"heading\"></h2>\n<p>Text</p>\n<h1 id=\"heading-1\"></h1>\n<h1 id=\"title\">Title</h1>\n"
     );
 }
 
 #[test]
-fn can_insert_anchor_with_lang() {
-    let mut tera = Tera::default();
-    tera.extend(&ZOLA_TERA).unwrap();
-    tera.add_raw_template("anchor-link.html", "({{ lang }})").unwrap();
+fn can_handle_headings_with_empty_ids() {
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
-    let context = RenderContext::new(
-        &tera,
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
-        InsertAnchor::Right,
+        InsertAnchor::None,
+    );
+    let content = "# {#}\n\n## !!!\n\n## Title {#}";
+
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(res.body, "<h2 id=\"heading\">!!!</h2>\n<h2 id=\"title\">Title</h2>\n");
+
+    context.set_empty_headings(EmptyHeadings::Fallback);
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"heading\"></h1>\n<h2 id=\"heading-1\">!!!</h2>\n<h2 id=\"title\">Title</h2>\n"
     );
-    let res = render_content("# Hello", &context).unwrap();
-    assert_eq!(res.body, "<h1 id=\"hello\">Hello(en)</h1>\n");
 }
 
 #[test]
-fn can_make_toc() {
+fn can_add_class_to_anchor_targets() {
     let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
+    let mut config = Config::default_for_test();
+    config.markdown.anchor_target_class = Some("anchor-target".to_owned());
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
-        "https://mysite.com/something",
+        "",
         &permalinks_ctx,
         InsertAnchor::Left,
     );
-
-    let res = render_content(
-        r#"
-# Heading 1
-
-## Heading 2
-
-## Another Heading 2
-
-### Last one
-    "#,
-        &context,
-    )
-    .unwrap();
-
-    let toc = res.toc;
-    assert_eq!(toc.len(), 1);
-    assert_eq!(toc[0].children.len(), 2);
-    assert_eq!(toc[0].children[1].children.len(), 1);
+    let res = render_content("# Hello\n\n## World {#custom}", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"hello\" class=\"anchor-target\"><a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a>Hello</h1>\n<h2 id=\"custom\" class=\"anchor-target\"><a class=\"zola-anchor\" href=\"#custom\" aria-label=\"Anchor link for: custom\">🔗</a>World</h2>\n"
+    );
 }
 
 #[test]
-fn can_ignore_tags_in_toc() {
+fn only_adds_class_to_headings_with_anchors() {
     let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
+    let mut config = Config::default_for_test();
+    config.markdown.anchor_target_class = Some("anchor-target".to_owned());
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
-        "https://mysite.com/something",
+        "",
         &permalinks_ctx,
-        InsertAnchor::Left,
+        InsertAnchor::None,
     );
+    let res = render_content("# Hello\n\n## World", &context).unwrap();
+    assert_eq!(res.body, "<h1 id=\"hello\">Hello</h1>\n<h2 id=\"world\">World</h2>\n");
 
-    let res = render_content(
-        r#"
-## heading with `code`
-
-## [anchor](https://duckduckgo.com/) in heading
-
-## **bold** and *italics*
-    "#,
-        &context,
-    )
-    .unwrap();
-
-    let toc = res.toc;
-
-    assert_eq!(toc[0].id, "heading-with-code");
-    assert_eq!(toc[0].title, "heading with code");
-
-    assert_eq!(toc[1].id, "anchor-in-heading");
-    assert_eq!(toc[1].title, "anchor in heading");
-
-    assert_eq!(toc[2].id, "bold-and-italics");
-    assert_eq!(toc[2].title, "bold and italics");
+    // Headings below `anchor_min_level` don't get an anchor either
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::Left,
+    );
+    context.set_anchor_min_level(2);
+    let res = render_content("# Hello\n\n## World", &context).unwrap();
+    assert!(res.body.starts_with("<h1 id=\"hello\">Hello</h1>"));
+    assert!(res.body.contains("<h2 id=\"world\" class=\"anchor-target\">"));
 }
 
 #[test]
-fn can_understand_backtick_in_titles() {
+fn can_percent_encode_anchor_link_fragments() {
     let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
+    let mut config = Config::default_for_test();
+    config.slugify.anchors = SlugifyStrategy::Safe;
+    config.markdown.encode_anchor_fragments = true;
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
-        InsertAnchor::None,
+        InsertAnchor::Left,
+    );
+    let res = render_content("# Écologie 100%", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"Écologie_100%\"><a class=\"zola-anchor\" href=\"#%C3%89cologie_100%25\" aria-label=\"Anchor link for: Écologie_100%\">🔗</a>Écologie 100%</h1>\n"
+    );
+
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::Heading,
+    );
+    let res = render_content("## Überblick", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h2 id=\"Überblick\"><a class=\"zola-anchor\" href=\"#%C3%9Cberblick\">Überblick</a></h2>\n"
     );
-    let res = render_content("# `Hello`", &context).unwrap();
-    assert_eq!(res.body, "<h1 id=\"hello\"><code>Hello</code></h1>\n");
 }
 
 #[test]
-fn can_understand_backtick_in_paragraphs() {
-    let permalinks_ctx = HashMap::new();
+fn can_make_valid_relative_link() {
+    let mut permalinks = HashMap::new();
+    permalinks.insert("pages/about.md".to_string(), "https://vincent.is/about".to_string());
+    let tera_ctx = Tera::default();
     let config = Config::default_for_test();
     let context = RenderContext::new(
-        &ZOLA_TERA,
+        &tera_ctx,
         &config,
         &config.default_language,
         "",
-        &permalinks_ctx,
+        &permalinks,
         InsertAnchor::None,
     );
-    let res = render_content("Hello `world`", &context).unwrap();
-    assert_eq!(res.body, "<p>Hello <code>world</code></p>\n");
+    let res = render_content(
+        r#"[rel link](@/pages/about.md), [abs link](https://vincent.is/about)"#,
+        &context,
+    )
+    .unwrap();
+
+    assert!(
+        res.body.contains(r#"<p><a href="https://vincent.is/about">rel link</a>, <a href="https://vincent.is/about">abs link</a></p>"#)
+    );
 }
 
-// https://github.com/Keats/gutenberg/issues/297
 #[test]
-fn can_understand_links_in_heading() {
-    let permalinks_ctx = HashMap::new();
+fn can_make_relative_links_with_anchors() {
+    let mut permalinks = HashMap::new();
+    permalinks.insert("pages/about.md".to_string(), "https://vincent.is/about".to_string());
+    let tera_ctx = Tera::default();
     let config = Config::default_for_test();
     let context = RenderContext::new(
-        &ZOLA_TERA,
+        &tera_ctx,
         &config,
         &config.default_language,
         "",
-        &permalinks_ctx,
+        &permalinks,
         InsertAnchor::None,
     );
-    let res = render_content("# [Rust](https://rust-lang.org)", &context).unwrap();
-    assert_eq!(res.body, "<h1 id=\"rust\"><a href=\"https://rust-lang.org\">Rust</a></h1>\n");
+    let res = render_content(r#"[rel link](@/pages/about.md#cv)"#, &context).unwrap();
+
+    assert!(res.body.contains(r#"<p><a href="https://vincent.is/about#cv">rel link</a></p>"#));
 }
 
 #[test]
-fn can_understand_link_with_title_in_heading() {
+fn errors_relative_link_inexistant() {
+    let tera_ctx = Tera::default();
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
     let context = RenderContext::new(
-        &ZOLA_TERA,
+        &tera_ctx,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res =
-        render_content("# [Rust](https://rust-lang.org \"Rust homepage\")", &context).unwrap();
-    assert_eq!(
-        res.body,
-        "<h1 id=\"rust\"><a href=\"https://rust-lang.org\" title=\"Rust homepage\">Rust</a></h1>\n"
-    );
+    let res = render_content("[rel link](@/pages/about.md)", &context);
+    assert!(res.is_err());
 }
 
 #[test]
-fn can_understand_emphasis_in_heading() {
+fn can_add_id_to_headings() {
+    let tera_ctx = Tera::default();
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
     let context = RenderContext::new(
-        &ZOLA_TERA,
+        &tera_ctx,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("# *Emphasis* text", &context).unwrap();
-    assert_eq!(res.body, "<h1 id=\"emphasis-text\"><em>Emphasis</em> text</h1>\n");
+    let res = render_content(r#"# Hello"#, &context).unwrap();
+    assert_eq!(res.body, "<h1 id=\"hello\">Hello</h1>\n");
 }
 
 #[test]
-fn can_understand_strong_in_heading() {
+fn can_add_id_to_headings_same_slug() {
+    let tera_ctx = Tera::default();
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
     let context = RenderContext::new(
-        &ZOLA_TERA,
+        &tera_ctx,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("# **Strong** text", &context).unwrap();
-    assert_eq!(res.body, "<h1 id=\"strong-text\"><strong>Strong</strong> text</h1>\n");
+    let res = render_content("# Hello\n# Hello", &context).unwrap();
+    assert_eq!(res.body, "<h1 id=\"hello\">Hello</h1>\n<h1 id=\"hello-1\">Hello</h1>\n");
 }
 
 #[test]
-fn can_understand_code_in_heading() {
+fn can_add_non_slug_id_to_headings() {
+    let tera_ctx = Tera::default();
     let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
+    let mut config = Config::default_for_test();
+    config.slugify.anchors = SlugifyStrategy::Safe;
     let context = RenderContext::new(
-        &ZOLA_TERA,
+        &tera_ctx,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("# `Code` text", &context).unwrap();
-    assert_eq!(res.body, "<h1 id=\"code-text\"><code>Code</code> text</h1>\n");
+    let res = render_content(r#"# L'écologie et vous"#, &context).unwrap();
+    assert_eq!(res.body, "<h1 id=\"L'écologie_et_vous\">L'écologie et vous</h1>\n");
 }
 
-// See https://github.com/getzola/zola/issues/569
 #[test]
-fn can_understand_footnote_in_heading() {
+fn can_handle_manual_ids_on_headings() {
+    let tera_ctx = Tera::default();
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
     let context = RenderContext::new(
-        &ZOLA_TERA,
+        &tera_ctx,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("# text [^1] there\n[^1]: footnote", &context).unwrap();
+    // Tested things: manual IDs; whitespace flexibility; that automatic IDs avoid collision with
+    // manual IDs; that duplicates are in fact permitted among manual IDs; that any non-plain-text
+    // in the middle of `{#…}` will disrupt it from being acknowledged as a manual ID (that last
+    // one could reasonably be considered a bug rather than a feature, but test it either way); one
+    // workaround for the improbable case where you actually want `{#…}` at the end of a heading.
+    let res = render_content(
+        "\
+         # Hello\n\
+         # Hello{#hello}\n\
+         # Hello {#hello}\n\
+         # Hello     {#Something_else} \n\
+         # Workaround for literal {#…&#125;\n\
+         # Hello\n\
+         # Auto {#*matic*}",
+        &context,
+    )
+    .unwrap();
     assert_eq!(
         res.body,
-        r##"<h1 id="text-there">text <sup class="footnote-reference"><a href="#1">1</a></sup> there</h1>
-<div class="footnote-definition" id="1"><sup class="footnote-definition-label">1</sup>
-<p>footnote</p>
-</div>
-"##
+        "\
+         <h1 id=\"hello-1\">Hello</h1>\n\
+         <h1 id=\"hello\">Hello</h1>\n\
+         <h1 id=\"hello\">Hello</h1>\n\
+         <h1 id=\"Something_else\">Hello</h1>\n\
+         <h1 id=\"workaround-for-literal\">Workaround for literal {#…}</h1>\n\
+         <h1 id=\"hello-2\">Hello</h1>\n\
+         <h1 id=\"auto-matic\">Auto {#<em>matic</em>}</h1>\n\
+         "
     );
 }
 
 #[test]
-fn can_make_valid_relative_link_in_heading() {
-    let mut permalinks = HashMap::new();
-    permalinks.insert("pages/about.md".to_string(), "https://vincent.is/about/".to_string());
+fn blank_headings() {
     let tera_ctx = Tera::default();
+    let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
-    let context = RenderContext::new(
+    let mut context = RenderContext::new(
         &tera_ctx,
         &config,
         &config.default_language,
         "",
-        &permalinks,
+        &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content(r#" # [rel link](@/pages/about.md)"#, &context).unwrap();
+    let res = render_content("# \n#\n# {#hmm} \n# {#}", &context).unwrap();
+    assert_eq!(res.body, "<h1 id=\"hmm\"></h1>\n");
 
+    context.set_empty_headings(EmptyHeadings::Fallback);
+    let res = render_content("# \n#\n# {#hmm} \n# {#}", &context).unwrap();
     assert_eq!(
         res.body,
-        "<h1 id=\"rel-link\"><a href=\"https://vincent.is/about/\">rel link</a></h1>\n"
+        "<h1 id=\"heading\"></h1>\n<h1 id=\"heading-1\"></h1>\n<h1 id=\"hmm\"></h1>\n<h1 id=\"heading-2\"></h1>\n"
     );
 }
 
 #[test]
-fn can_make_permalinks_with_colocated_assets_for_link() {
+fn can_insert_anchor_left() {
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
-        "https://vincent.is/about/",
+        "",
         &permalinks_ctx,
-        InsertAnchor::None,
+        InsertAnchor::Left,
+    );
+    let res = render_content("# Hello", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"hello\"><a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a>Hello</h1>\n"
     );
-    let res = render_content("[an image](image.jpg)", &context).unwrap();
-    assert_eq!(res.body, "<p><a href=\"image.jpg\">an image</a></p>\n");
 }
 
 #[test]
-fn can_make_permalinks_with_colocated_assets_for_image() {
+fn can_skip_anchors_below_min_level() {
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
-    let context = RenderContext::new(
+    let mut context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
-        "https://vincent.is/about/",
+        "",
         &permalinks_ctx,
-        InsertAnchor::None,
+        InsertAnchor::Left,
     );
-    let res = render_content("![alt text](image.jpg)", &context).unwrap();
-    assert_eq!(res.body, "<p><img src=\"image.jpg\" alt=\"alt text\" /></p>\n");
+    context.set_anchor_min_level(2);
+    let res = render_content("# Title\n## Hello\n### World", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"title\">Title</h1>\n<h2 id=\"hello\"><a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a>Hello</h2>\n<h3 id=\"world\"><a class=\"zola-anchor\" href=\"#world\" aria-label=\"Anchor link for: world\">🔗</a>World</h3>\n"
+    );
+    assert_eq!(res.toc[0].id, "title");
 }
 
 #[test]
-fn markdown_doesnt_wrap_html_in_paragraph() {
+fn can_insert_anchor_right() {
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
-        "https://vincent.is/about/",
+        "",
         &permalinks_ctx,
-        InsertAnchor::None,
+        InsertAnchor::Right,
+    );
+    let res = render_content("# Hello", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"hello\">Hello<a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a></h1>\n"
+    );
+}
+
+#[test]
+fn can_insert_anchor_as_heading_wrapper() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::Heading,
+    );
+    let res = render_content("# Hello *big* `world`", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"hello-big-world\"><a class=\"zola-anchor\" href=\"#hello-big-world\">Hello <em>big</em> <code>world</code></a></h1>\n"
+    );
+
+    let res = render_content("## [Rust](https://rust-lang.org) {#lang}", &context).unwrap();
+    assert_eq!(res.body, "<h2 id=\"lang\"><a class=\"zola-anchor\" href=\"#lang\">Rust</a></h2>\n");
+}
+
+#[test]
+fn can_insert_anchor_for_multi_heading() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::Right,
+    );
+    let res = render_content("# Hello\n# World", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"hello\">Hello<a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a></h1>\n\
+<h1 id=\"world\">World<a class=\"zola-anchor\" href=\"#world\" aria-label=\"Anchor link for: world\">🔗</a></h1>\n"
+    );
+}
+
+// See https://github.com/Keats/gutenberg/issues/42
+#[test]
+fn can_insert_anchor_with_exclamation_mark() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::Left,
+    );
+    let res = render_content("# Hello!", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"hello\"><a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a>Hello!</h1>\n"
+    );
+}
+
+// See https://github.com/Keats/gutenberg/issues/53
+#[test]
+fn can_insert_anchor_with_link() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::Left,
+    );
+    let res = render_content("## [Rust](https://rust-lang.org)", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h2 id=\"rust\"><a class=\"zola-anchor\" href=\"#rust\" aria-label=\"Anchor link for: rust\">🔗</a><a href=\"https://rust-lang.org\">Rust</a></h2>\n"
+    );
+}
+
+#[test]
+fn can_insert_anchor_with_other_special_chars() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::Left,
+    );
+    let res = render_content("# Hello*_()", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"hello\"><a class=\"zola-anchor\" href=\"#hello\" aria-label=\"Anchor link for: hello\">🔗</a>Hello*_()</h1>\n"
+    );
+}
+
+#[test]
+fn can_insert_anchor_with_lang() {
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("anchor-link.html", "({{ lang }})").unwrap();
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::Right,
+    );
+    let res = render_content("# Hello", &context).unwrap();
+    assert_eq!(res.body, "<h1 id=\"hello\">Hello(en)</h1>\n");
+}
+
+#[test]
+fn can_make_toc() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "https://mysite.com/something",
+        &permalinks_ctx,
+        InsertAnchor::Left,
     );
+
     let res = render_content(
         r#"
-Some text
+# Heading 1
 
-<h1>Helo</h1>
+## Heading 2
 
-<div>
-<a href="mobx-flow.png">
-        <img src="mobx-flow.png" alt="MobX flow">
-    </a>
-</div>
+## Another Heading 2
+
+### Last one
     "#,
         &context,
     )
     .unwrap();
+
+    let toc = res.toc;
+    assert_eq!(toc.len(), 1);
+    assert_eq!(toc[0].children.len(), 2);
+    assert_eq!(toc[0].children[1].children.len(), 1);
+}
+
+#[test]
+fn can_make_toc_json() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.toc_json = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "https://mysite.com/something",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+
+    let res = render_content("# Heading 1\n\n## Heading 2\n\n### Last one\n\n## Another", &context)
+        .unwrap();
+    let toc: tera::Value = serde_json::from_str(&res.toc_json.unwrap()).unwrap();
     assert_eq!(
-        res.body,
-        "<p>Some text</p>\n<h1>Helo</h1>\n<div>\n<a href=\"mobx-flow.png\">\n        <img src=\"mobx-flow.png\" alt=\"MobX flow\">\n    </a>\n</div>\n"
+        toc,
+        serde_json::json!([{
+            "level": 1,
+            "id": "heading-1",
+            "permalink": "https://mysite.com/something#heading-1",
+            "title": "Heading 1",
+            "children": [
+                {
+                    "level": 2,
+                    "id": "heading-2",
+                    "permalink": "https://mysite.com/something#heading-2",
+                    "title": "Heading 2",
+                    "children": [{
+                        "level": 3,
+                        "id": "last-one",
+                        "permalink": "https://mysite.com/something#last-one",
+                        "title": "Last one",
+                        "children": [],
+                    }],
+                },
+                {
+                    "level": 2,
+                    "id": "another",
+                    "permalink": "https://mysite.com/something#another",
+                    "title": "Another",
+                    "children": [],
+                },
+            ],
+        }])
+    );
+
+    // Not serialized unless asked for
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
     );
+    assert_eq!(render_content("# Heading 1", &context).unwrap().toc_json, None);
 }
 
 #[test]
-fn correctly_captures_external_links() {
+fn can_count_words_of_heading_sections() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.heading_word_counts = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+
+    let content = "Intro, not counted.\n\n# First\n\nOne two `three`.\n\n## Sub\n\nFour *five*.\n\n# Second\n\n- six\n- seven";
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(res.toc.len(), 2);
+    // The section of a heading includes its subsections, and their title
+    assert_eq!(res.toc[0].word_count, Some(6));
+    assert_eq!(res.toc[0].children[0].word_count, Some(2));
+    assert_eq!(res.toc[1].word_count, Some(2));
+
+    // Not counted unless asked for
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(res.toc[0].word_count, None);
+}
+
+#[test]
+fn can_ignore_tags_in_toc() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "https://mysite.com/something",
+        &permalinks_ctx,
+        InsertAnchor::Left,
+    );
+
+    let res = render_content(
+        r#"
+## heading with `code`
+
+## [anchor](https://duckduckgo.com/) in heading
+
+## **bold** and *italics*
+    "#,
+        &context,
+    )
+    .unwrap();
+
+    let toc = res.toc;
+
+    assert_eq!(toc[0].id, "heading-with-code");
+    assert_eq!(toc[0].title, "heading with code");
+
+    assert_eq!(toc[1].id, "anchor-in-heading");
+    assert_eq!(toc[1].title, "anchor in heading");
+
+    assert_eq!(toc[2].id, "bold-and-italics");
+    assert_eq!(toc[2].title, "bold and italics");
+}
+
+#[test]
+fn can_understand_backtick_in_titles() {
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
-        "https://vincent.is/about/",
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("# `Hello`", &context).unwrap();
+    assert_eq!(res.body, "<h1 id=\"hello\"><code>Hello</code></h1>\n");
+}
+
+#[test]
+fn can_understand_backtick_in_paragraphs() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("Hello `world`", &context).unwrap();
+    assert_eq!(res.body, "<p>Hello <code>world</code></p>\n");
+}
+
+// https://github.com/Keats/gutenberg/issues/297
+#[test]
+fn can_understand_links_in_heading() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("# [Rust](https://rust-lang.org)", &context).unwrap();
+    assert_eq!(res.body, "<h1 id=\"rust\"><a href=\"https://rust-lang.org\">Rust</a></h1>\n");
+}
+
+#[test]
+fn can_understand_link_with_title_in_heading() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res =
+        render_content("# [Rust](https://rust-lang.org \"Rust homepage\")", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<h1 id=\"rust\"><a href=\"https://rust-lang.org\" title=\"Rust homepage\">Rust</a></h1>\n"
+    );
+}
+
+#[test]
+fn can_understand_emphasis_in_heading() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("# *Emphasis* text", &context).unwrap();
+    assert_eq!(res.body, "<h1 id=\"emphasis-text\"><em>Emphasis</em> text</h1>\n");
+}
+
+#[test]
+fn can_understand_strong_in_heading() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("# **Strong** text", &context).unwrap();
+    assert_eq!(res.body, "<h1 id=\"strong-text\"><strong>Strong</strong> text</h1>\n");
+}
+
+#[test]
+fn can_understand_code_in_heading() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("# `Code` text", &context).unwrap();
+    assert_eq!(res.body, "<h1 id=\"code-text\"><code>Code</code> text</h1>\n");
+}
+
+// See https://github.com/getzola/zola/issues/569
+#[test]
+fn can_understand_footnote_in_heading() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("# text [^1] there\n[^1]: footnote", &context).unwrap();
+    assert_eq!(
+        res.body,
+        r##"<h1 id="text-there">text <sup class="footnote-reference"><a href="#1">1</a></sup> there</h1>
+<div class="footnote-definition" id="1"><sup class="footnote-definition-label">1</sup>
+<p>footnote</p>
+</div>
+"##
+    );
+}
+
+#[test]
+fn can_make_valid_relative_link_in_heading() {
+    let mut permalinks = HashMap::new();
+    permalinks.insert("pages/about.md".to_string(), "https://vincent.is/about/".to_string());
+    let tera_ctx = Tera::default();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &tera_ctx,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks,
+        InsertAnchor::None,
+    );
+    let res = render_content(r#" # [rel link](@/pages/about.md)"#, &context).unwrap();
+
+    assert_eq!(
+        res.body,
+        "<h1 id=\"rel-link\"><a href=\"https://vincent.is/about/\">rel link</a></h1>\n"
+    );
+}
+
+#[test]
+fn can_make_permalinks_with_colocated_assets_for_link() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "https://vincent.is/about/",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("[an image](image.jpg)", &context).unwrap();
+    assert_eq!(res.body, "<p><a href=\"image.jpg\">an image</a></p>\n");
+}
+
+#[test]
+fn can_make_permalinks_with_colocated_assets_for_image() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "https://vincent.is/about/",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("![alt text](image.jpg)", &context).unwrap();
+    assert_eq!(res.body, "<p><img src=\"image.jpg\" alt=\"alt text\" /></p>\n");
+}
+
+#[test]
+fn markdown_doesnt_wrap_html_in_paragraph() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "https://vincent.is/about/",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content(
+        r#"
+Some text
+
+<h1>Helo</h1>
+
+<div>
+<a href="mobx-flow.png">
+        <img src="mobx-flow.png" alt="MobX flow">
+    </a>
+</div>
+    "#,
+        &context,
+    )
+    .unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Some text</p>\n<h1>Helo</h1>\n<div>\n<a href=\"mobx-flow.png\">\n        <img src=\"mobx-flow.png\" alt=\"MobX flow\">\n    </a>\n</div>\n"
+    );
+}
+
+#[test]
+fn correctly_captures_external_links() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "https://vincent.is/about/",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let content = "
+[a link](http://google.com)
+[a link](http://google.comy)
+Email: [foo@bar.baz](mailto:foo@bar.baz)
+Email: <foo@bar.baz>
+    ";
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.external_links,
+        &["http://google.com".to_owned(), "http://google.comy".to_owned()]
+    );
+}
+
+#[test]
+fn can_handle_summaries() {
+    let tera_ctx = Tera::default();
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &tera_ctx,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content(
+        r#"
+Hello [My site][world]
+
+<!-- more -->
+
+Bla bla
+
+[world]: https://vincentprouillet.com
+"#,
+        &context,
+    )
+    .unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Hello <a href=\"https://vincentprouillet.com\">My site</a></p>\n<span id=\"continue-reading\"></span>\n<p>Bla bla</p>\n"
+    );
+    assert_eq!(
+        res.summary_len,
+        Some("<p>Hello <a href=\"https://vincentprouillet.com/\">My site</a></p>".len())
+    );
+}
+
+// https://github.com/Keats/gutenberg/issues/522
+#[test]
+fn doesnt_try_to_highlight_content_from_shortcode() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+
+    let shortcode = r#"<figure>
+     {% if width %}
+     <img src="/images/{{ src }}" alt="{{ caption }}" width="{{ width }}" />
+     {% else %}
+     <img src="/images/{{ src }}" alt="{{ caption }}" />
+     {% endif %}
+
+     <figcaption>{{ caption }}</figcaption>
+</figure>"#;
+
+    let markdown_string = r#"{{ figure(src="spherecluster.png", caption="Some spheres.") }}"#;
+
+    let expected = "<figure>\n     \n     <img src=\"/images/spherecluster.png\" alt=\"Some spheres.\" />\n     \n\n     <figcaption>Some spheres.</figcaption>\n</figure>";
+
+    tera.add_raw_template("shortcodes/figure.html", shortcode).unwrap();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, expected);
+}
+
+#[test]
+fn can_emit_newlines_and_whitespace_with_shortcode() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+
+    let shortcode = r#"<pre>
+{{ body }}
+</pre>"#;
+
+    let markdown_string = "{% preformatted() %}\nHello\n    \n    Zola\n   \n  !\n{% end %}";
+
+    let expected = "<pre>\nHello\n    \n    Zola\n   \n  !\n</pre>";
+
+    tera.add_raw_template(&format!("shortcodes/{}.html", "preformatted"), shortcode).unwrap();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, expected);
+}
+
+// TODO: re-enable once it's fixed in Tera
+// https://github.com/Keats/tera/issues/373
+//#[test]
+//fn can_split_lines_shortcode_body() {
+//    let permalinks_ctx = HashMap::new();
+//    let mut tera = Tera::default();
+//    tera.extend(&ZOLA_TERA).unwrap();
+//
+//    let shortcode = r#"{{ body | split(pat="\n") }}"#;
+//
+//    let markdown_string = r#"
+//{% alert() %}
+//multi
+//ple
+//lines
+//{% end %}
+//    "#;
+//
+//    let expected = r#"<p>["multi", "ple", "lines"]</p>"#;
+//
+//    tera.add_raw_template(&format!("shortcodes/{}.html", "alert"), shortcode).unwrap();
+//    let config = Config::default_for_test();
+//    let context = RenderContext::new(&tera, &config, &config.default_language, "", &permalinks_ctx, InsertAnchor::None);
+//
+//    let res = render_content(markdown_string, &context).unwrap();
+//    assert_eq!(res.body, expected);
+//}
+
+// https://github.com/getzola/zola/issues/747
+// https://github.com/getzola/zola/issues/816
+#[test]
+fn leaves_custom_url_scheme_untouched() {
+    let content = r#"[foo@bar.tld](xmpp:foo@bar.tld)
+
+[(123) 456-7890](tel:+11234567890)
+
+[blank page](about:blank)
+"#;
+
+    let tera_ctx = Tera::default();
+    let config = Config::default_for_test();
+    let permalinks_ctx = HashMap::new();
+
+    let context = RenderContext::new(
+        &tera_ctx,
+        &config,
+        &config.default_language,
+        "https://vincent.is/",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+
+    let res = render_content(content, &context).unwrap();
+
+    let expected = r#"<p><a href="xmpp:foo@bar.tld">foo@bar.tld</a></p>
+<p><a href="tel:+11234567890">(123) 456-7890</a></p>
+<p><a href="about:blank">blank page</a></p>
+"#;
+
+    assert_eq!(res.body, expected);
+}
+
+#[test]
+fn stops_with_an_error_on_an_empty_link() {
+    let content = r#"[some link]()"#;
+
+    let tera_ctx = Tera::default();
+    let config = Config::default_for_test();
+    let permalinks_ctx = HashMap::new();
+
+    let context = RenderContext::new(
+        &tera_ctx,
+        &config,
+        &config.default_language,
+        "https://vincent.is/",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+
+    let res = render_content(content, &context);
+
+    let expected = "There is a link that is missing a URL";
+
+    assert!(res.is_err());
+    assert_eq!(res.unwrap_err().to_string(), expected);
+}
+
+#[test]
+fn can_drop_empty_links_and_images() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    context.set_empty_links(EmptyLinks::Drop);
+    let res =
+        render_content("a [](), [text]() and ![alt]() [b](/b) ![c](c.png)", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p>a , text and  <a href=\"/b\">b</a> <img src=\"c.png\" alt=\"c\" /></p>\n"
+    );
+    assert_eq!(res.images, vec!["c.png".to_string()]);
+}
+
+#[test]
+fn can_keep_empty_links_and_images() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    context.set_empty_links(EmptyLinks::Keep);
+    let res = render_content("a [](), [text]() and ![alt]()", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p>a <a href=\"\"></a>, <a href=\"\">text</a> and <img src=\"\" alt=\"alt\" /></p>\n"
+    );
+}
+
+#[test]
+fn renders_combined_emphasis_in_strong_then_em() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("***bold italic*** and **_mixed_**", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p><strong><em>bold italic</em></strong> and <strong><em>mixed</em></strong></p>\n"
+    );
+    // A single character, and emphasis only partly overlapping
+    let res = render_content("***a*** ***b** c* ***d* e**", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p><strong><em>a</em></strong> <em><strong>b</strong> c</em> <strong><em>d</em> e</strong></p>\n"
+    );
+}
+
+#[test]
+fn can_disable_combined_emphasis() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.combined_emphasis = false;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("***x*** and **_y_** and *z*", &context).unwrap();
+    assert_eq!(res.body, "<p><strong>x</strong> and <strong>y</strong> and <em>z</em></p>\n");
+}
+
+#[test]
+fn can_keep_measurement_quotes_straight() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.smart_punctuation = true;
+    config.markdown.measurement_quotes = Some(MeasurementQuotes::Straight);
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("A 6\" board, 5' tall, \"quoted\" and 'single'", &context).unwrap();
+    assert_eq!(res.body, "<p>A 6&quot; board, 5' tall, “quoted” and ‘single’</p>\n");
+
+    let res = render_content("The \"1984\" edition", &context).unwrap();
+    assert_eq!(res.body, "<p>The “1984” edition</p>\n");
+}
+
+#[test]
+fn can_render_measurement_quotes_as_primes() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.smart_punctuation = true;
+    config.markdown.measurement_quotes = Some(MeasurementQuotes::Primes);
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("A 6\" board, 5' tall, \"quoted\"", &context).unwrap();
+    assert_eq!(res.body, "<p>A 6″ board, 5′ tall, “quoted”</p>\n");
+
+    let res = render_content("In \"1984\", a 6\" board and 'the 80' with Bob's 5' pole", &context)
+        .unwrap();
+    assert_eq!(res.body, "<p>In “1984”, a 6″ board and ‘the 80’ with Bob’s 5′ pole</p>\n");
+}
+
+#[test]
+fn can_force_tight_lists() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let content = "- One\n\n- Two *2*\n\n  More\n- Three\n  1. Nested";
+    let res = render_content(content, &context).unwrap();
+    assert!(res.body.starts_with("<ul>\n<li>\n<p>One</p>\n</li>"));
+
+    context.set_list_spacing(Some(ListSpacing::Tight));
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<ul>\n<li>One</li>\n<li>Two <em>2</em>\nMore</li>\n<li>Three\n<ol>\n<li>Nested</li>\n</ol>\n</li>\n</ul>\n"
+    );
+}
+
+#[test]
+fn can_force_loose_lists() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let content = "- One\n- Two *2*\n  1. Nested\n\nAfter";
+    let res = render_content(content, &context).unwrap();
+    assert!(res.body.starts_with("<ul>\n<li>One</li>"));
+
+    context.set_list_spacing(Some(ListSpacing::Loose));
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<ul>\n<li>\n<p>One</p>\n</li>\n<li>\n<p>Two <em>2</em></p>\n<ol>\n<li>\n<p>Nested</p>\n</li>\n</ol>\n</li>\n</ul>\n<p>After</p>\n"
+    );
+}
+
+#[test]
+fn can_normalize_list_start() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let content = "3. Three\n4. Four\n\n- Bullet";
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<ol start=\"3\">\n<li>Three</li>\n<li>Four</li>\n</ol>\n<ul>\n<li>Bullet</li>\n</ul>\n"
+    );
+
+    context.set_normalize_list_start(true);
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<ol>\n<li>Three</li>\n<li>Four</li>\n</ol>\n<ul>\n<li>Bullet</li>\n</ul>\n"
+    );
+}
+
+#[test]
+fn can_merge_adjacent_blockquotes() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    let content = "> Hello\n\n> world\n\nBetween\n\n> Alone";
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<blockquote>\n<p>Hello</p>\n</blockquote>\n<blockquote>\n<p>world</p>\n</blockquote>\n<p>Between</p>\n<blockquote>\n<p>Alone</p>\n</blockquote>\n"
+    );
+
+    config.markdown.merge_adjacent_blockquotes = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<blockquote>\n<p>Hello</p>\n<p>world</p>\n</blockquote>\n<p>Between</p>\n<blockquote>\n<p>Alone</p>\n</blockquote>\n"
+    );
+}
+
+#[test]
+fn can_render_blockquote_attribution() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    context.set_blockquote_attribution(Some(BlockquoteAttribution::Footer));
+    let res = render_content("> Hello world\n>\n> — Ada *Lovelace*", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<blockquote>\n<p>Hello world</p>\n<footer>Ada <em>Lovelace</em></footer>\n</blockquote>\n"
+    );
+
+    context.set_blockquote_attribution(Some(BlockquoteAttribution::Cite));
+    let res = render_content("> Hello\n> world\n> -- Ada", &context).unwrap();
+    assert_eq!(res.body, "<blockquote>\n<p>Hello\nworld</p>\n<cite>Ada</cite>\n</blockquote>\n");
+}
+
+#[test]
+fn can_render_blockquote_attribution_with_smart_punctuation() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.smart_punctuation = true;
+    config.markdown.blockquote_attribution = Some(BlockquoteAttribution::Cite);
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("> Hello\n> -- Ada", &context).unwrap();
+    assert_eq!(res.body, "<blockquote>\n<p>Hello</p>\n<cite>Ada</cite>\n</blockquote>\n");
+}
+
+#[test]
+fn doesnt_change_blockquotes_without_attribution() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.blockquote_attribution = Some(BlockquoteAttribution::Footer);
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let content = "> Hello -- world\n>\n> - a list\n\n> Only a dash\n> —\n\n-- Not in a quote";
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<blockquote>\n<p>Hello -- world</p>\n<ul>\n<li>a list</li>\n</ul>\n</blockquote>\n<blockquote>\n<p>Only a dash\n—</p>\n</blockquote>\n<p>-- Not in a quote</p>\n"
+    );
+
+    // Disabled by default
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("> Hello\n>\n> — Ada", &context).unwrap();
+    assert_eq!(res.body, "<blockquote>\n<p>Hello</p>\n<p>— Ada</p>\n</blockquote>\n");
+}
+
+#[test]
+fn can_passthrough_markdown_from_shortcode() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+
+    let shortcode = r#"{% for line in body | split(pat="\n") %}
+> {{ line }}
+{%- endfor %}
+
+-- {{ author }}
+"#;
+    let markdown_string = r#"
+Hello
+
+{% quote(author="Vincent") %}
+# Passing through
+
+*to* **the** document
+{% end %}
+
+Bla bla"#;
+
+    let expected = r#"<p>Hello</p>
+<blockquote>
+<h1 id="passing-through">Passing through</h1>
+<p><em>to</em> <strong>the</strong> document</p>
+</blockquote>
+<p>-- Vincent</p>
+<p>Bla bla</p>
+"#;
+
+    tera.add_raw_template("shortcodes/quote.md", shortcode).unwrap();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let res = render_content(markdown_string, &context).unwrap();
+
+    assert_eq!(res.body, expected);
+}
+
+// https://github.com/getzola/zola/issues/1172
+#[test]
+fn can_render_shortcode_body_with_no_invalid_escaping() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+
+    let shortcode = r#"<a class="resize-image" href="/tlera-corp-gnat/gnat-with-picoblade-cable.jpg">
+    <img
+        src="https://placekitten.com/200/300"
+        alt="{{ alt }}">
+    </img>
+    <p>(click for full size)</p>
+</a>
+"#;
+
+    let markdown_string = r#"{{ resize_image(path="tlera-corp-gnat/gnat-with-picoblade-cable.jpg", width=600, alt="Some alt") }}"#;
+
+    let expected = "<a class=\"resize-image\" href=\"/tlera-corp-gnat/gnat-with-picoblade-cable.jpg\">\n    <img\n        src=\"https://placekitten.com/200/300\"\n        alt=\"Some alt\">\n    </img>\n    <p>(click for full size)</p>\n</a>\n";
+
+    tera.add_raw_template("shortcodes/resize_image.html", shortcode).unwrap();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, expected);
+}
+
+// TODO: handle it in the html part of the md renderer
+// https://github.com/getzola/zola/issues/1172
+#[test]
+fn can_render_commented_out_shortcodes_fine() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+
+    let shortcode = r#"<a width={{width}} class="resize-image" href="/tlera-corp-gnat/gnat-with-picoblade-cable.jpg">{{alt}}</a>"#;
+
+    let markdown_string = r#"<!--{{ resize_image(path="gnat-with-picoblade-cable.jpg", width=600, alt="Alt1") }}{{ resize_image(path="gnat-with-picoblade-cable.jpg", width=610, alt="Alt2") }}-->"#;
+    let expected = "<!--<a width=600 class=\"resize-image\" href=\"/tlera-corp-gnat/gnat-with-picoblade-cable.jpg\">Alt1</a><a width=610 class=\"resize-image\" href=\"/tlera-corp-gnat/gnat-with-picoblade-cable.jpg\">Alt2</a>-->";
+
+    tera.add_raw_template("shortcodes/resize_image.html", shortcode).unwrap();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, expected);
+}
+
+// https://zola.discourse.group/t/zola-12-issue-with-continue-reading/590/7
+#[test]
+fn can_render_read_more_after_shortcode() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+
+    let shortcode = r#"<p>Quote: {{body}}</p>"#;
+    tera.add_raw_template("shortcodes/quote.md", shortcode).unwrap();
+    let markdown_string = r#"
+# Title
+
+Some text
+{{ quote(body="Nothing is impossible. The word itself says - I'm Possible" author="Audrey Hepburn")}}
+<!-- more -->
+
+Again more text"#;
+
+    let expected = r#"<h1 id="title">Title</h1>
+<p>Some text</p>
+<p>Quote: Nothing is impossible. The word itself says - I'm Possible</p>
+<span id="continue-reading"></span>
+<p>Again more text</p>
+"#;
+
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, expected);
+}
+
+#[test]
+fn can_render_emoji_alias() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.render_emoji = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("Hello, World! :smile:", &context).unwrap();
+    assert_eq!(res.body, "<p>Hello, World! 😄</p>\n");
+}
+
+#[test]
+fn emoji_aliases_are_ignored_when_disabled_in_config() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("Hello, World! :smile:", &context).unwrap();
+    assert_eq!(res.body, "<p>Hello, World! :smile:</p>\n");
+}
+
+#[test]
+fn invocation_count_increments_in_shortcode() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+
+    let shortcode_template_a = r#"<p>a: {{ nth }}</p>"#;
+    let shortcode_template_b = r#"<p>b: {{ nth }}</p>"#;
+
+    let markdown_string = r#"{{ a() }}
+{{ b() }}
+{{ a() }}
+{{ b() }}
+"#;
+
+    let expected = r#"<p><p>a: 1</p>
+<p>b: 1</p>
+<p>a: 2</p>
+<p>b: 2</p></p>
+"#;
+
+    tera.add_raw_template("shortcodes/a.html", shortcode_template_a).unwrap();
+    tera.add_raw_template("shortcodes/b.html", shortcode_template_b).unwrap();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, expected);
+}
+
+#[test]
+fn basic_external_links_unchanged() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("<https://google.com>", &context).unwrap();
+    assert_eq!(res.body, "<p><a href=\"https://google.com\">https://google.com</a></p>\n");
+}
+
+#[test]
+fn can_set_target_blank_for_external_link() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.external_links_target_blank = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("<https://google.com>", &context).unwrap();
+    assert_eq!(res.body, "<p><a rel=\"noopener\" target=\"_blank\" href=\"https://google.com\">https://google.com</a></p>\n");
+}
+
+#[test]
+fn can_set_nofollow_for_external_link() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.external_links_no_follow = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    // Testing href escaping while we're there
+    let res = render_content("<https://google.com/éllo>", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p><a rel=\"nofollow\" href=\"https://google.com/%C3%A9llo\">https://google.com/éllo</a></p>\n"
+    );
+}
+
+#[test]
+fn can_set_noreferrer_for_external_link() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.external_links_no_referrer = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("<https://google.com>", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p><a rel=\"noreferrer\" href=\"https://google.com\">https://google.com</a></p>\n"
+    );
+}
+
+#[test]
+fn can_set_all_options_for_external_link() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.external_links_target_blank = true;
+    config.markdown.external_links_no_follow = true;
+    config.markdown.external_links_no_referrer = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("<https://google.com>", &context).unwrap();
+    assert_eq!(res.body, "<p><a rel=\"noopener nofollow noreferrer\" target=\"_blank\" href=\"https://google.com\">https://google.com</a></p>\n");
+}
+
+#[test]
+fn can_use_smart_punctuation() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.smart_punctuation = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content(r#"This -- is "it"..."#, &context).unwrap();
+    assert_eq!(res.body, "<p>This – is “it”…</p>\n");
+}
+
+// https://zola.discourse.group/t/zola-0-15-md-shortcode-stopped-working/1099/2
+#[test]
+fn md_shortcode_regression() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("shortcodes/code.md", "123").unwrap();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let markdown_string = r#"
+ttest1
+
+{{ code(path = "content/software/supercollider/pakt-februari/pakt29.scd", syntax = "supercollider") }}
+
+ttest2
+
+{{ code(path = "content/software/supercollider/pakt-februari/pakt29.scd", syntax = "supercollider") }}"#;
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, "<p>ttest1</p>\n<p>123</p>\n<p>ttest2</p>\n<p>123</p>\n");
+}
+
+// https://github.com/getzola/zola/issues/1689
+#[test]
+fn html_shortcode_regression() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("shortcodes/ex.html", "1").unwrap();
+    tera.add_raw_template("shortcodes/book.html", "2").unwrap();
+    tera.add_raw_template("shortcodes/std.html", "3").unwrap();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let markdown_string = r#"{{ book(page="") }} {{ ex(page="") }} {{ std(page="std") }}"#;
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, "<p>2 1 3</p>\n");
+
+    // And in html
+    let markdown_string = r#"<p>{{ book(page="") }} {{ ex(page="") }} {{ std(page="std") }}</p>"#;
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, "<p>2 1 3</p>");
+
+    // Another one with newlines
+    let markdown_string = "<p>\n{{ book(page='') }}\n</p>";
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, "<p>\n2\n</p>");
+
+    // And another one
+    let markdown_string = "<span>{{ book(page='') }}</span>\n**The Book** {{ book(page='') }}";
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, "<p><span>2</span>\n<strong>The Book</strong> 2</p>\n");
+
+    // with some text in between
+    let markdown_string = r#"a.{{ book(page="") }} b.{{ ex(page="") }} c.{{ std(page="std") }}"#;
+    let res = render_content(markdown_string, &context).unwrap();
+    assert_eq!(res.body, "<p>a.2 b.1 c.3</p>\n");
+}
+
+#[test]
+fn can_expand_shortcodes_output_by_shortcodes() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template(
+        "shortcodes/outer.html",
+        "<div>{% raw %}{{ inner(name='zola') }}{% endraw %}</div>",
+    )
+    .unwrap();
+    tera.add_raw_template("shortcodes/inner.html", "<span>{{ name }}</span>").unwrap();
+    let mut config = Config::default_for_test();
+    config.markdown.shortcode_max_depth = 1;
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+
+    let res = render_content("{{ outer() }}", &context).unwrap();
+    assert_eq!(res.body, "<div><span>zola</span></div>");
+}
+
+#[test]
+fn doesnt_expand_shortcodes_output_by_shortcodes_past_max_depth() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("shortcodes/recurse.html", "{% raw %}{{ recurse() }}{% endraw %}")
+        .unwrap();
+    let mut config = Config::default_for_test();
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+
+    // Not expanded by default
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    context.set_shortcode_definitions(&shortcode_def);
+    let res = render_content("{{ recurse() }}", &context).unwrap();
+    assert_eq!(res.body, "{{ recurse() }}");
+
+    config.markdown.shortcode_max_depth = 3;
+    let mut context = RenderContext::new(
+        &tera,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    context.set_shortcode_definitions(&shortcode_def);
+    let res = render_content("{{ recurse() }}", &context);
+    assert!(res.is_err());
+    let err = format!("{}", res.unwrap_err());
+    assert!(err.contains("`recurse` shortcode still contains shortcodes after 3 levels"));
+}
+
+#[test]
+fn can_link_bare_emails() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.link_bare_emails = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("Contact me_x@example.com or [me](mailto:a@b.fr).", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Contact <a href=\"mailto:me_x@example.com\">me_x@example.com</a> or <a href=\"mailto:a@b.fr\">me</a>.</p>\n"
+    );
+
+    let res = render_content("`a@b.fr` and <a@b.fr>\n\n```\na@b.fr\n```", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p><code>a@b.fr</code> and <a href=\"mailto:a@b.fr\">a@b.fr</a></p>\n<pre><code>a@b.fr\n</code></pre>\n"
+    );
+}
+
+#[test]
+fn can_link_mentions_after_bare_emails_and_anchor_links() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.link_bare_emails = true;
+    config.markdown.mention_base_url = Some("https://github.com/".into());
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::Left,
+    );
+
+    let res = render_content("Mail a@b.fr or ask @Keats", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Mail <a href=\"mailto:a@b.fr\">a@b.fr</a> or ask <a href=\"https://github.com/Keats\">@Keats</a></p>\n"
+    );
+
+    let res = render_content("# Hello\n\nThanks @Keats", &context).unwrap();
+    assert!(res.body.ends_with("<p>Thanks <a href=\"https://github.com/Keats\">@Keats</a></p>\n"));
+}
+
+#[test]
+fn can_obfuscate_bare_emails() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.link_bare_emails = true;
+    config.markdown.obfuscate_emails = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("a@b.fr", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p><a href=\"&#109;&#97;&#105;&#108;&#116;&#111;&#58;&#97;&#64;&#98;&#46;&#102;&#114;\">&#97;&#64;&#98;&#46;&#102;&#114;</a></p>\n"
+    );
+}
+
+#[test]
+fn can_link_bare_emails_from_render_context() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("a@b.fr", &context).unwrap();
+    assert_eq!(res.body, "<p>a@b.fr</p>\n");
+
+    context.set_link_bare_emails(true);
+    let res = render_content("a@b.fr", &context).unwrap();
+    assert_eq!(res.body, "<p><a href=\"mailto:a@b.fr\">a@b.fr</a></p>\n");
+
+    context.set_obfuscate_emails(true);
+    let res = render_content("a@b.fr", &context).unwrap();
+    assert!(res.body.starts_with("<p><a href=\"&#109;&#97;"));
+}
+
+#[test]
+fn can_add_class_to_horizontal_rules() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("Hello\n\n---\n\nWorld", &context).unwrap();
+    assert_eq!(res.body, "<p>Hello</p>\n<hr />\n<p>World</p>\n");
+
+    config.markdown.horizontal_rule_class = Some("divider".to_owned());
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("Hello\n\n***\n\nWorld", &context).unwrap();
+    assert_eq!(res.body, "<p>Hello</p>\n<hr class=\"divider\" />\n<p>World</p>\n");
+}
+
+#[test]
+fn can_render_consecutive_code_blocks_as_tab_group() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.code_tab_groups = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let content = "```rust,group=hello\nfn main() {}\n```\n\n```python,group=hello\npass\n```\n";
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<div class=\"code-tabs\" data-group=\"hello\">\n<div class=\"code-tab-labels\">\
+         <span class=\"code-tab-label\">rust</span><span class=\"code-tab-label\">python</span></div>\n\
+         <div class=\"code-tab\" data-label=\"rust\">\n\
+         <pre data-lang=\"rust\" class=\"language-rust \"><code class=\"language-rust\" data-lang=\"rust\">fn main() {}\n</code></pre>\n\
+         </div>\n\
+         <div class=\"code-tab\" data-label=\"python\">\n\
+         <pre data-lang=\"python\" class=\"language-python \"><code class=\"language-python\" data-lang=\"python\">pass\n</code></pre>\n\
+         </div>\n</div>\n"
+    );
+}
+
+#[test]
+fn leaves_code_blocks_outside_of_tab_groups_separate() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.code_tab_groups = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    // Unmarked blocks, a block alone in its group and blocks separated by a paragraph
+    let content = "```rust\nfn main() {}\n```\n\n```python\npass\n```\n\n\
+                   ```rust,group=a\nfn main() {}\n```\n\n```python,group=b\npass\n```\n\n\
+                   ```rust,group=c\nfn main() {}\n```\n\nHello\n\n```python,group=c\npass\n```\n";
+    let res = render_content(content, &context).unwrap();
+    assert!(!res.body.contains("code-tab"));
+    assert_eq!(res.body.matches("<pre").count(), 6);
+
+    // The marker is ignored when the option is disabled
+    config.markdown.code_tab_groups = false;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let content = "```rust,group=hello\nfn main() {}\n```\n\n```python,group=hello\npass\n```\n";
+    let res = render_content(content, &context).unwrap();
+    assert!(!res.body.contains("code-tab"));
+    assert!(res.body.starts_with("<pre data-lang=\"rust\" class=\"language-rust \">"));
+}
+
+#[test]
+fn can_sanitize_style_attributes() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.style_attributes = StyleAttributes::Strict;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let tests = vec![
+        // Safe declarations are kept
+        (
+            "<p style=\"color: red; margin: 0 auto\">Hi</p>",
+            "<p style=\"color: red; margin: 0 auto\">Hi</p>",
+        ),
+        (
+            "<p style=\"color: red; width: expression(alert(1))\">Hi</p>",
+            "<p style=\"color: red\">Hi</p>",
+        ),
+        ("<p style=\"background: url('javascript:alert(1)')\">Hi</p>", "<p>Hi</p>"),
+        ("<p style='width: expr/**/ession(alert(1))'>Hi</p>", "<p>Hi</p>"),
+        ("<p style=\"behavior: url(x.htc)\">Hi</p>", "<p>Hi</p>"),
+        // Relative URLs are always kept
+        (
+            "<p style=\"background: url(/bg.png)\">Hi</p>",
+            "<p style=\"background: url(/bg.png)\">Hi</p>",
+        ),
+        ("<p style=\"background: url(https://example.com/bg.png)\">Hi</p>", "<p>Hi</p>"),
+        // Unquoted values
+        ("<p style=background:url(https://evil.example/x)>Hi</p>", "<p>Hi</p>"),
+        ("<p style=color:red;width:expression(alert(1))>Hi</p>", "<p style=\"color:red\">Hi</p>"),
+        // Character references
+        ("<p style=\"width: expression&#40;alert(1))\">Hi</p>", "<p>Hi</p>"),
+        ("<p style=\"background: url(&quot;https://evil.example/x&quot;)\">Hi</p>", "<p>Hi</p>"),
+        // Tags written on several lines
+        (
+            "<div\n  style=\"width: expression(alert(1)); background: url(https://evil.example/x)\">\nHi\n</div>",
+            "<div>\nHi\n</div>",
+        ),
+        (
+            "<div class=\"a\"\n  style=\"color: red; background: url(https://evil.example/x)\"\n>Hi</div>",
+            "<div class=\"a\"\n  style=\"color: red\"\n>Hi</div>",
+        ),
+        // Hidden in another attribute, a comment or the content of a textarea
+        (
+            "<p title=' style=\"' style=\"width: expression(alert(1))\" x='\"'>Hi</p>",
+            "<p title=' style=\"' x='\"'>Hi</p>",
+        ),
+        (
+            "<!-- <p title=\" --><p style=\"width: expression(alert(1))\">\">Hi</p>",
+            "<!-- <p title=\" --><p>\">Hi</p>",
+        ),
+        (
+            "<div><textarea><p title=\"</textarea><p style=\"width: expression(alert(1))\">\">Hi</p></div>",
+            "<div><textarea><p title=\"</textarea><p>\">Hi</p></div>",
+        ),
+    ];
+    for (input, expected) in tests {
+        let res = render_content(input, &context).unwrap();
+        assert_eq!(res.body, expected);
+    }
+
+    config.markdown.style_attributes = StyleAttributes::Lenient;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res =
+        render_content("<p style=\"background: url(https://example.com/bg.png)\">Hi</p>", &context)
+            .unwrap();
+    assert_eq!(res.body, "<p style=\"background: url(https://example.com/bg.png)\">Hi</p>");
+    let res = render_content("<p style=\"width: expression(alert(1))\">Hi</p>", &context).unwrap();
+    assert_eq!(res.body, "<p>Hi</p>");
+
+    // Left as written by default
+    config.markdown.style_attributes = StyleAttributes::Keep;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("<p style=\"width: expression(alert(1))\">Hi</p>", &context).unwrap();
+    assert_eq!(res.body, "<p style=\"width: expression(alert(1))\">Hi</p>");
+}
+
+#[test]
+fn can_break_lines_in_table_cells() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.table_cell_line_break = Some(";;".to_owned());
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content(
+        "| a | b |\n|---|---|\n| first;;second ;; third | end;;|\n\nOutside;;of a table\n",
+        &context,
+    )
+    .unwrap();
+    assert!(res.body.contains("<td>first<br>second <br> third</td><td>end<br></td>"));
+    assert!(res.body.contains("<p>Outside;;of a table</p>"));
+
+    // An escaped marker stays literal
+    let res = render_content("| a |\n|---|\n| x \\;; y;;z |\n", &context).unwrap();
+    assert!(res.body.contains("<td>x ;; y<br>z</td>"));
+    let res = render_content("| a |\n|---|\n| \\;;x |\n", &context).unwrap();
+    assert!(res.body.contains("<td>;;x</td>"));
+}
+
+#[test]
+fn can_wrap_tables() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let table = "| a | b |\n|---|---|\n| 1 | 2 |\n";
+    let res = render_content(table, &context).unwrap();
+    assert!(res.body.starts_with("<table>"));
+
+    config.markdown.table_wrapper_class = Some("table-wrapper".to_owned());
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content(&format!("Hello\n\n{}\nWorld", table), &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Hello</p>\n<div class=\"table-wrapper\">\n<table><thead><tr><th>a</th><th>b</th></tr></thead><tbody>\n<tr><td>1</td><td>2</td></tr>\n</tbody></table>\n</div>\n<p>World</p>\n"
+    );
+}
+
+#[test]
+fn can_render_standalone_images_as_figures() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.image_captions = Some(ImageCaptions::Title);
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res =
+        render_content("![A cat](cat.jpg \"My <cat>\")\n\n![No title](dog.jpg)", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<figure><img src=\"cat.jpg\" alt=\"A cat\" title=\"My &lt;cat&gt;\" /><figcaption>My &lt;cat&gt;</figcaption></figure>\n<p><img src=\"dog.jpg\" alt=\"No title\" /></p>\n"
+    );
+    assert_eq!(res.images, vec!["cat.jpg".to_string(), "dog.jpg".to_string()]);
+
+    config.markdown.image_captions = Some(ImageCaptions::Alt);
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("![A *black* cat](cat.jpg)", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<figure><img src=\"cat.jpg\" alt=\"A black cat\" /><figcaption>A black cat</figcaption></figure>\n"
+    );
+}
+
+#[test]
+fn doesnt_render_inline_images_as_figures() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.image_captions = Some(ImageCaptions::Title);
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("Look at ![A cat](cat.jpg \"My cat\") here", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Look at <img src=\"cat.jpg\" alt=\"A cat\" title=\"My cat\" /> here</p>\n"
+    );
+}
+
+#[test]
+fn keeps_first_definition_of_duplicate_footnotes() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    let res = render_content("Hello[^1]\n\n[^1]: first\n\n[^1]: second\n\n[^2]: other", &context)
+        .unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Hello<sup class=\"footnote-reference\"><a href=\"#1\">1</a></sup></p>\n<div class=\"footnote-definition\" id=\"1\"><sup class=\"footnote-definition-label\">1</sup>\n<p>first</p>\n</div>\n<div class=\"footnote-definition\" id=\"2\"><sup class=\"footnote-definition-label\">2</sup>\n<p>other</p>\n</div>\n"
+    );
+}
+
+#[test]
+fn errors_on_duplicate_footnotes_if_asked() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    context.set_duplicate_footnotes(DuplicateFootnotes::Error);
+    context.set_current_page_path("blog/hello.md");
+    let res = render_content("Hello[^a]\n\n[^a]: first\n\n[^a]: second", &context);
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Footnote(s) `[^a]` defined more than once in blog/hello.md"
+    );
+    assert!(render_content("Hello[^a]\n\n[^a]: first\n\n[^b]: second", &context).is_ok());
+}
+
+#[test]
+fn can_add_last_modified_note() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.last_modified_note = Some("Last updated: %Y-%m-%d".to_owned());
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    let modified =
+        chrono::DateTime::<chrono::Local>::from(source.metadata().unwrap().modified().unwrap());
+    let note =
+        format!("<p class=\"last-modified\">Last updated: {}</p>\n", modified.format("%Y-%m-%d"));
+
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    // Nothing to get the time from
+    assert_eq!(render_content("Hello", &context).unwrap().body, "<p>Hello</p>\n");
+    context.set_source_path(&source);
+    assert_eq!(render_content("Hello", &context).unwrap().body, format!("<p>Hello</p>\n{}", note));
+
+    config.markdown.last_modified_position = LastModifiedPosition::Start;
+    let mut context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let content = "
-[a link](http://google.com)
-[a link](http://google.comy)
-Email: [foo@bar.baz](mailto:foo@bar.baz)
-Email: <foo@bar.baz>
-    ";
-    let res = render_content(content, &context).unwrap();
-    assert_eq!(
-        res.external_links,
-        &["http://google.com".to_owned(), "http://google.comy".to_owned()]
-    );
+    context.set_source_path(&source);
+    assert_eq!(render_content("Hello", &context).unwrap().body, format!("{}<p>Hello</p>\n", note));
 }
 
 #[test]
-fn can_handle_summaries() {
+fn can_highlight_code_block_with_fallback_theme() {
     let tera_ctx = Tera::default();
     let permalinks_ctx = HashMap::new();
-    let config = Config::default_for_test();
+    let mut config = Config::default_for_test();
+    config.markdown.highlight_code = true;
+    config.markdown.highlight_theme = "i-do-not-exist".to_owned();
+    config.markdown.highlight_theme_fallback = true;
+    config.markdown.init_extra_syntaxes_and_highlight_themes(std::path::Path::new(".")).unwrap();
     let context = RenderContext::new(
         &tera_ctx,
         &config,
@@ -1107,51 +3259,19 @@ fn can_handle_summaries() {
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content(
-        r#"
-Hello [My site][world]
-
-<!-- more -->
-
-Bla bla
-
-[world]: https://vincentprouillet.com
-"#,
-        &context,
-    )
-    .unwrap();
+    let res = render_content("```\n$ gutenberg server\n```", &context).unwrap();
     assert_eq!(
         res.body,
-        "<p>Hello <a href=\"https://vincentprouillet.com\">My site</a></p>\n<span id=\"continue-reading\"></span>\n<p>Bla bla</p>\n"
-    );
-    assert_eq!(
-        res.summary_len,
-        Some("<p>Hello <a href=\"https://vincentprouillet.com/\">My site</a></p>".len())
+        "<pre style=\"background-color:#2b303b;color:#c0c5ce;\"><code><span>$ gutenberg server\n</span></code></pre>\n"
     );
 }
 
-// https://github.com/Keats/gutenberg/issues/522
 #[test]
-fn doesnt_try_to_highlight_content_from_shortcode() {
+fn can_render_content_as_events() {
     let permalinks_ctx = HashMap::new();
     let mut tera = Tera::default();
     tera.extend(&ZOLA_TERA).unwrap();
-
-    let shortcode = r#"<figure>
-     {% if width %}
-     <img src="/images/{{ src }}" alt="{{ caption }}" width="{{ width }}" />
-     {% else %}
-     <img src="/images/{{ src }}" alt="{{ caption }}" />
-     {% endif %}
-
-     <figcaption>{{ caption }}</figcaption>
-</figure>"#;
-
-    let markdown_string = r#"{{ figure(src="spherecluster.png", caption="Some spheres.") }}"#;
-
-    let expected = "<figure>\n     \n     <img src=\"/images/spherecluster.png\" alt=\"Some spheres.\" />\n     \n\n     <figcaption>Some spheres.</figcaption>\n</figure>";
-
-    tera.add_raw_template("shortcodes/figure.html", shortcode).unwrap();
+    tera.add_raw_template("shortcodes/bold.html", "<b>{{ word }}</b>").unwrap();
     let config = Config::default_for_test();
     let mut context = RenderContext::new(
         &tera,
@@ -1164,26 +3284,37 @@ fn doesnt_try_to_highlight_content_from_shortcode() {
     let shortcode_def = utils::templates::get_shortcodes(&tera);
     context.set_shortcode_definitions(&shortcode_def);
 
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, expected);
+    let events = render_events("# Title\n\nSome *text* {{ bold(word='here') }}", &context).unwrap();
+    assert_eq!(
+        events,
+        vec![
+            Event::Html("<h1 id=\"title\">".into()),
+            Event::Text("Title".into()),
+            Event::End(Tag::Heading(1)),
+            Event::Start(Tag::Paragraph),
+            Event::Text("Some ".into()),
+            Event::Start(Tag::Emphasis),
+            Event::Text("text".into()),
+            Event::End(Tag::Emphasis),
+            Event::Text(" ".into()),
+            Event::Html("<b>here</b>".into()),
+            Event::End(Tag::Paragraph),
+        ]
+    );
 }
 
 #[test]
-fn can_emit_newlines_and_whitespace_with_shortcode() {
+fn errors_when_output_is_bigger_than_max_size() {
     let permalinks_ctx = HashMap::new();
     let mut tera = Tera::default();
     tera.extend(&ZOLA_TERA).unwrap();
-
-    let shortcode = r#"<pre>
-{{ body }}
-</pre>"#;
-
-    let markdown_string = "{% preformatted() %}\nHello\n    \n    Zola\n   \n  !\n{% end %}";
-
-    let expected = "<pre>\nHello\n    \n    Zola\n   \n  !\n</pre>";
-
-    tera.add_raw_template(&format!("shortcodes/{}.html", "preformatted"), shortcode).unwrap();
-    let config = Config::default_for_test();
+    tera.add_raw_template(
+        "shortcodes/huge.html",
+        "{% for i in range(end=1000) %}spam {% endfor %}",
+    )
+    .unwrap();
+    let mut config = Config::default_for_test();
+    config.markdown.max_output_size = Some(1000);
     let mut context = RenderContext::new(
         &tera,
         &config,
@@ -1194,131 +3325,84 @@ fn can_emit_newlines_and_whitespace_with_shortcode() {
     );
     let shortcode_def = utils::templates::get_shortcodes(&tera);
     context.set_shortcode_definitions(&shortcode_def);
+    context.set_current_page_path("blog/spam.md");
 
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, expected);
+    let res = render_content("Hello {{ huge() }}", &context);
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Rendering blog/spam.md produced 5014 bytes of HTML, more than the 1000 allowed by `markdown.max_output_size`"
+    );
+    let res = render_content("Hello *world*", &context).unwrap();
+    assert_eq!(res.body, "<p>Hello <em>world</em></p>\n");
 }
 
-// TODO: re-enable once it's fixed in Tera
-// https://github.com/Keats/tera/issues/373
-//#[test]
-//fn can_split_lines_shortcode_body() {
-//    let permalinks_ctx = HashMap::new();
-//    let mut tera = Tera::default();
-//    tera.extend(&ZOLA_TERA).unwrap();
-//
-//    let shortcode = r#"{{ body | split(pat="\n") }}"#;
-//
-//    let markdown_string = r#"
-//{% alert() %}
-//multi
-//ple
-//lines
-//{% end %}
-//    "#;
-//
-//    let expected = r#"<p>["multi", "ple", "lines"]</p>"#;
-//
-//    tera.add_raw_template(&format!("shortcodes/{}.html", "alert"), shortcode).unwrap();
-//    let config = Config::default_for_test();
-//    let context = RenderContext::new(&tera, &config, &config.default_language, "", &permalinks_ctx, InsertAnchor::None);
-//
-//    let res = render_content(markdown_string, &context).unwrap();
-//    assert_eq!(res.body, expected);
-//}
-
-// https://github.com/getzola/zola/issues/747
-// https://github.com/getzola/zola/issues/816
 #[test]
-fn leaves_custom_url_scheme_untouched() {
-    let content = r#"[foo@bar.tld](xmpp:foo@bar.tld)
-
-[(123) 456-7890](tel:+11234567890)
-
-[blank page](about:blank)
-"#;
-
-    let tera_ctx = Tera::default();
-    let config = Config::default_for_test();
+fn can_render_content_with_timeout() {
     let permalinks_ctx = HashMap::new();
-
-    let context = RenderContext::new(
-        &tera_ctx,
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template(
+        "shortcodes/slow.html",
+        "{% for i in range(end=5000) %}{% for j in range(end=5000) %}{% endfor %}{% endfor %}",
+    )
+    .unwrap();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &tera,
         &config,
         &config.default_language,
-        "https://vincent.is/",
+        "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
+    context.set_current_page_path("blog/slow.md");
 
-    let res = render_content(content, &context).unwrap();
-
-    let expected = r#"<p><a href="xmpp:foo@bar.tld">foo@bar.tld</a></p>
-<p><a href="tel:+11234567890">(123) 456-7890</a></p>
-<p><a href="about:blank">blank page</a></p>
-"#;
+    let res =
+        render_content_with_timeout("Hello {{ slow() }}", &context, Duration::from_millis(50));
+    assert_eq!(res.unwrap_err().to_string(), "Rendering blog/slow.md took more than 50ms");
 
-    assert_eq!(res.body, expected);
+    let res =
+        render_content_with_timeout("Hello *world*", &context, Duration::from_secs(10)).unwrap();
+    assert_eq!(res.body, "<p>Hello <em>world</em></p>\n");
 }
 
 #[test]
-fn stops_with_an_error_on_an_empty_link() {
-    let content = r#"[some link]()"#;
-
-    let tera_ctx = Tera::default();
-    let config = Config::default_for_test();
+fn can_list_heading_ids() {
     let permalinks_ctx = HashMap::new();
-
-    let context = RenderContext::new(
-        &tera_ctx,
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("shortcodes/title.html", "World").unwrap();
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
+        &tera,
         &config,
         &config.default_language,
-        "https://vincent.is/",
+        "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
+    let shortcode_def = utils::templates::get_shortcodes(&tera);
+    context.set_shortcode_definitions(&shortcode_def);
 
-    let res = render_content(content, &context);
-
-    let expected = "There is a link that is missing a URL";
-
-    assert!(res.is_err());
-    assert_eq!(res.unwrap_err().to_string(), expected);
+    let content = "# Hello\n## Hello\n{{ title() }}\n### Hello\n## Custom {#hello-1}";
+    let ids = heading_ids(content, &context).unwrap();
+    assert_eq!(ids, vec!["hello", "hello-2", "hello-3", "hello-1"]);
+    let rendered = render_content(content, &context).unwrap();
+    for id in &ids {
+        assert!(rendered.body.contains(&format!("id=\"{}\"", id)));
+    }
 }
 
 #[test]
-fn can_passthrough_markdown_from_shortcode() {
+fn can_render_adjacent_inline_shortcodes() {
     let permalinks_ctx = HashMap::new();
     let mut tera = Tera::default();
     tera.extend(&ZOLA_TERA).unwrap();
-
-    let shortcode = r#"{% for line in body | split(pat="\n") %}
-> {{ line }}
-{%- endfor %}
-
--- {{ author }}
-"#;
-    let markdown_string = r#"
-Hello
-
-{% quote(author="Vincent") %}
-# Passing through
-
-*to* **the** document
-{% end %}
-
-Bla bla"#;
-
-    let expected = r#"<p>Hello</p>
-<blockquote>
-<h1 id="passing-through">Passing through</h1>
-<p><em>to</em> <strong>the</strong> document</p>
-</blockquote>
-<p>-- Vincent</p>
-<p>Bla bla</p>
-"#;
-
-    tera.add_raw_template("shortcodes/quote.md", shortcode).unwrap();
+    tera.add_raw_template("shortcodes/a.html", "<i>a</i>").unwrap();
+    tera.add_raw_template("shortcodes/b.html", "<i>b</i>").unwrap();
+    tera.add_raw_template("shortcodes/c.md", "**c**").unwrap();
     let config = Config::default_for_test();
     let mut context = RenderContext::new(
         &tera,
@@ -1331,63 +3415,69 @@ Bla bla"#;
     let shortcode_def = utils::templates::get_shortcodes(&tera);
     context.set_shortcode_definitions(&shortcode_def);
 
-    let res = render_content(markdown_string, &context).unwrap();
+    let res = render_content("Hey {{ a() }}{{ b() }} there", &context).unwrap();
+    assert_eq!(res.body, "<p>Hey <i>a</i><i>b</i> there</p>\n");
 
-    assert_eq!(res.body, expected);
+    let res = render_content("{{ a() }}{{ c() }}{{ b() }}", &context).unwrap();
+    assert_eq!(res.body, "<p><i>a</i><strong>c</strong><i>b</i></p>\n");
 }
 
-// https://github.com/getzola/zola/issues/1172
 #[test]
-fn can_render_shortcode_body_with_no_invalid_escaping() {
+fn doesnt_render_intra_word_underscores_as_emphasis() {
     let permalinks_ctx = HashMap::new();
-    let mut tera = Tera::default();
-    tera.extend(&ZOLA_TERA).unwrap();
-
-    let shortcode = r#"<a class="resize-image" href="/tlera-corp-gnat/gnat-with-picoblade-cable.jpg">
-    <img
-        src="https://placekitten.com/200/300"
-        alt="{{ alt }}">
-    </img>
-    <p>(click for full size)</p>
-</a>
-"#;
-
-    let markdown_string = r#"{{ resize_image(path="tlera-corp-gnat/gnat-with-picoblade-cable.jpg", width=600, alt="Some alt") }}"#;
-
-    let expected = "<a class=\"resize-image\" href=\"/tlera-corp-gnat/gnat-with-picoblade-cable.jpg\">\n    <img\n        src=\"https://placekitten.com/200/300\"\n        alt=\"Some alt\">\n    </img>\n    <p>(click for full size)</p>\n</a>\n";
-
-    tera.add_raw_template("shortcodes/resize_image.html", shortcode).unwrap();
     let config = Config::default_for_test();
-    let mut context = RenderContext::new(
-        &tera,
+    let context = RenderContext::new(
+        &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let shortcode_def = utils::templates::get_shortcodes(&tera);
-    context.set_shortcode_definitions(&shortcode_def);
 
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, expected);
+    let res = render_content("Set my_var_name to __init__ or foo_bar_", &context).unwrap();
+    assert_eq!(res.body, "<p>Set my_var_name to <strong>init</strong> or foo_bar_</p>\n");
+
+    let res = render_content("Some _emph_ and my_var_name", &context).unwrap();
+    assert_eq!(res.body, "<p>Some <em>emph</em> and my_var_name</p>\n");
 }
 
-// TODO: handle it in the html part of the md renderer
-// https://github.com/getzola/zola/issues/1172
 #[test]
-fn can_render_commented_out_shortcodes_fine() {
+fn can_autolink_mentions() {
     let permalinks_ctx = HashMap::new();
-    let mut tera = Tera::default();
-    tera.extend(&ZOLA_TERA).unwrap();
+    let mut config = Config::default_for_test();
+    config.markdown.mention_base_url = Some("https://github.com/".into());
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
 
-    let shortcode = r#"<a width={{width}} class="resize-image" href="/tlera-corp-gnat/gnat-with-picoblade-cable.jpg">{{alt}}</a>"#;
+    let res = render_content("@Keats fixed it, thanks to (@some-one-).", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p><a href=\"https://github.com/Keats\">@Keats</a> fixed it, thanks to (<a href=\"https://github.com/some-one\">@some-one</a>-).</p>\n"
+    );
 
-    let markdown_string = r#"<!--{{ resize_image(path="gnat-with-picoblade-cable.jpg", width=600, alt="Alt1") }}{{ resize_image(path="gnat-with-picoblade-cable.jpg", width=610, alt="Alt2") }}-->"#;
-    let expected = "<!--<a width=600 class=\"resize-image\" href=\"/tlera-corp-gnat/gnat-with-picoblade-cable.jpg\">Alt1</a><a width=610 class=\"resize-image\" href=\"/tlera-corp-gnat/gnat-with-picoblade-cable.jpg\">Alt2</a>-->";
+    let res =
+        render_content("Mail jane@example.com or a@b, run `npm i @scope/pkg`", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Mail jane@example.com or a@b, run <code>npm i @scope/pkg</code></p>\n"
+    );
+}
 
-    tera.add_raw_template("shortcodes/resize_image.html", shortcode).unwrap();
-    let config = Config::default_for_test();
+#[test]
+fn can_autolink_mentions_after_html_shortcodes() {
+    let permalinks_ctx = HashMap::new();
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("shortcodes/cmd.html", "<code>{{ name }}</code>").unwrap();
+    let mut config = Config::default_for_test();
+    config.markdown.mention_base_url = Some("https://github.com/".into());
     let mut context = RenderContext::new(
         &tera,
         &config,
@@ -1399,73 +3489,74 @@ fn can_render_commented_out_shortcodes_fine() {
     let shortcode_def = utils::templates::get_shortcodes(&tera);
     context.set_shortcode_definitions(&shortcode_def);
 
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, expected);
+    let res = render_content("Run {{ cmd(name=\"@a\") }} and ask @Keats", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Run <code>@a</code> and ask <a href=\"https://github.com/Keats\">@Keats</a></p>\n"
+    );
 }
 
-// https://zola.discourse.group/t/zola-12-issue-with-continue-reading/590/7
 #[test]
-fn can_render_read_more_after_shortcode() {
+fn can_autolink_version_tags_and_commit_hashes() {
     let permalinks_ctx = HashMap::new();
-    let mut tera = Tera::default();
-    tera.extend(&ZOLA_TERA).unwrap();
-
-    let shortcode = r#"<p>Quote: {{body}}</p>"#;
-    tera.add_raw_template("shortcodes/quote.md", shortcode).unwrap();
-    let markdown_string = r#"
-# Title
-
-Some text
-{{ quote(body="Nothing is impossible. The word itself says - I'm Possible" author="Audrey Hepburn")}}
-<!-- more -->
-
-Again more text"#;
-
-    let expected = r#"<h1 id="title">Title</h1>
-<p>Some text</p>
-<p>Quote: Nothing is impossible. The word itself says - I'm Possible</p>
-<span id="continue-reading"></span>
-<p>Again more text</p>
-"#;
-
-    let config = Config::default_for_test();
-    let mut context = RenderContext::new(
-        &tera,
+    let mut config = Config::default_for_test();
+    config.markdown.release_base_url = Some("https://github.com/getzola/zola/releases/tag/".into());
+    config.markdown.commit_base_url = Some("https://github.com/getzola/zola/commit".into());
+    let context = RenderContext::new(
+        &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let shortcode_def = utils::templates::get_shortcodes(&tera);
-    context.set_shortcode_definitions(&shortcode_def);
 
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, expected);
+    let res = render_content("Released in v1.2.3, not in av1.2.3", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Released in <a href=\"https://github.com/getzola/zola/releases/tag/v1.2.3\">v1.2.3</a>, not in av1.2.3</p>\n"
+    );
+
+    let res = render_content("Fixed by 3c2598a (deadbeef is a word)", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Fixed by <a href=\"https://github.com/getzola/zola/commit/3c2598a\">3c2598a</a> (deadbeef is a word)</p>\n"
+    );
+
+    let res = render_content("See `3c2598a` and x3c2598a\n\n```\ngit show 3c2598a\n```", &context)
+        .unwrap();
+    assert_eq!(
+        res.body,
+        "<p>See <code>3c2598a</code> and x3c2598a</p>\n<pre><code>git show 3c2598a\n</code></pre>\n"
+    );
 }
 
 #[test]
-fn can_render_emoji_alias() {
+fn can_autolink_version_tags_after_headings_with_anchors() {
     let permalinks_ctx = HashMap::new();
     let mut config = Config::default_for_test();
-    config.markdown.render_emoji = true;
+    config.markdown.release_base_url = Some("https://github.com/getzola/zola/releases/tag/".into());
+    config.markdown.commit_base_url = Some("https://github.com/getzola/zola/commit".into());
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
-        InsertAnchor::None,
+        InsertAnchor::Right,
     );
-    let res = render_content("Hello, World! :smile:", &context).unwrap();
-    assert_eq!(res.body, "<p>Hello, World! 😄</p>\n");
+
+    let res = render_content("# v1.2.3\n\nFixed by 3c2598a in v1.2.3", &context).unwrap();
+    assert!(res.body.ends_with(
+        "<p>Fixed by <a href=\"https://github.com/getzola/zola/commit/3c2598a\">3c2598a</a> in <a href=\"https://github.com/getzola/zola/releases/tag/v1.2.3\">v1.2.3</a></p>\n"
+    ));
 }
 
 #[test]
-fn emoji_aliases_are_ignored_when_disabled_in_config() {
+fn can_set_custom_autolinks() {
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
-    let context = RenderContext::new(
+    let mut context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
@@ -1473,33 +3564,24 @@ fn emoji_aliases_are_ignored_when_disabled_in_config() {
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("Hello, World! :smile:", &context).unwrap();
-    assert_eq!(res.body, "<p>Hello, World! :smile:</p>\n");
+    context.set_autolinks(vec![
+        AutolinkRule::new("ZOLA-\\d+", "https://tickets.example.com").unwrap()
+    ]);
+
+    let res = render_content("Fixes ZOLA-42", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<p>Fixes <a href=\"https://tickets.example.com/ZOLA-42\">ZOLA-42</a></p>\n"
+    );
 }
 
 #[test]
-fn invocation_count_increments_in_shortcode() {
+fn can_choose_to_decode_entities_in_shortcode_args() {
     let permalinks_ctx = HashMap::new();
     let mut tera = Tera::default();
     tera.extend(&ZOLA_TERA).unwrap();
-
-    let shortcode_template_a = r#"<p>a: {{ nth }}</p>"#;
-    let shortcode_template_b = r#"<p>b: {{ nth }}</p>"#;
-
-    let markdown_string = r#"{{ a() }}
-{{ b() }}
-{{ a() }}
-{{ b() }}
-"#;
-
-    let expected = r#"<p><p>a: 1</p>
-<p>b: 1</p>
-<p>a: 2</p>
-<p>b: 2</p></p>
-"#;
-
-    tera.add_raw_template("shortcodes/a.html", shortcode_template_a).unwrap();
-    tera.add_raw_template("shortcodes/b.html", shortcode_template_b).unwrap();
+    tera.add_raw_template("shortcodes/text.html", "{{ value | length }}:{{ value | safe }}")
+        .unwrap();
     let config = Config::default_for_test();
     let mut context = RenderContext::new(
         &tera,
@@ -1510,17 +3592,24 @@ fn invocation_count_increments_in_shortcode() {
         InsertAnchor::None,
     );
     let shortcode_def = utils::templates::get_shortcodes(&tera);
+    let mut decoding_def = shortcode_def.clone();
+    utils::templates::set_shortcodes_decoding_entities(&mut decoding_def, &["text".to_string()]);
     context.set_shortcode_definitions(&shortcode_def);
 
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, expected);
+    let content = r#"{{ text(value="Tom &amp; Jerry") }}"#;
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(res.body, "15:Tom &amp; Jerry");
+
+    context.set_shortcode_definitions(&decoding_def);
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(res.body, "11:Tom & Jerry");
 }
 
 #[test]
-fn basic_external_links_unchanged() {
+fn can_render_interactive_task_lists() {
     let permalinks_ctx = HashMap::new();
     let config = Config::default_for_test();
-    let context = RenderContext::new(
+    let mut context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
@@ -1528,15 +3617,26 @@ fn basic_external_links_unchanged() {
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("<https://google.com>", &context).unwrap();
-    assert_eq!(res.body, "<p><a href=\"https://google.com\">https://google.com</a></p>\n");
+    let content = "- [ ] todo\n- [x] done";
+
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<ul>\n<li><input disabled=\"\" type=\"checkbox\"/>\ntodo</li>\n<li><input disabled=\"\" type=\"checkbox\" checked=\"\"/>\ndone</li>\n</ul>\n"
+    );
+
+    context.set_interactive_task_lists(true);
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<ul>\n<li><input id=\"task-1\" type=\"checkbox\"/>\ntodo</li>\n<li><input id=\"task-2\" type=\"checkbox\" checked=\"\"/>\ndone</li>\n</ul>\n"
+    );
 }
 
 #[test]
-fn can_set_target_blank_for_external_link() {
+fn can_render_inline_markdown_and_escaped_pipes_in_table_cells() {
     let permalinks_ctx = HashMap::new();
-    let mut config = Config::default_for_test();
-    config.markdown.external_links_target_blank = true;
+    let config = Config::default_for_test();
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
@@ -1545,15 +3645,25 @@ fn can_set_target_blank_for_external_link() {
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("<https://google.com>", &context).unwrap();
-    assert_eq!(res.body, "<p><a rel=\"noopener\" target=\"_blank\" href=\"https://google.com\">https://google.com</a></p>\n");
+    let content = "| a | b |\n|---|---|\n| [link](https://getzola.org) *em* `code` | x \\| y |\n| `a \\| b` | z |";
+    let res = render_content(content, &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<table><thead><tr><th>a</th><th>b</th></tr></thead><tbody>\n\
+        <tr><td><a href=\"https://getzola.org\">link</a> <em>em</em> <code>code</code></td><td>x | y</td></tr>\n\
+        <tr><td><code>a | b</code></td><td>z</td></tr>\n\
+        </tbody></table>\n"
+    );
 }
 
 #[test]
-fn can_set_nofollow_for_external_link() {
+fn can_render_block_content_in_table_cells() {
     let permalinks_ctx = HashMap::new();
     let mut config = Config::default_for_test();
-    config.markdown.external_links_no_follow = true;
+    let content =
+        "| a | b |\n|---|---|\n| - one<br>- **two** | p1<br><br>p2<br>line |\n| a<br>b | c |";
+
+    config.markdown.table_cell_blocks = true;
     let context = RenderContext::new(
         &ZOLA_TERA,
         &config,
@@ -1562,20 +3672,21 @@ fn can_set_nofollow_for_external_link() {
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    // Testing href escaping while we're there
-    let res = render_content("<https://google.com/éllo>", &context).unwrap();
+    let res = render_content(content, &context).unwrap();
     assert_eq!(
         res.body,
-        "<p><a rel=\"nofollow\" href=\"https://google.com/%C3%A9llo\">https://google.com/éllo</a></p>\n"
+        "<table><thead><tr><th>a</th><th>b</th></tr></thead><tbody>\n\
+        <tr><td><ul><li>one</li><li><strong>two</strong></li></ul></td><td><p>p1</p><p>p2<br>line</p></td></tr>\n\
+        <tr><td>a<br>b</td><td>c</td></tr>\n\
+        </tbody></table>\n"
     );
 }
 
 #[test]
-fn can_set_noreferrer_for_external_link() {
+fn can_set_text_direction() {
     let permalinks_ctx = HashMap::new();
-    let mut config = Config::default_for_test();
-    config.markdown.external_links_no_referrer = true;
-    let context = RenderContext::new(
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
@@ -1583,21 +3694,24 @@ fn can_set_noreferrer_for_external_link() {
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("<https://google.com>", &context).unwrap();
+
+    let res = render_content("Hello", &context).unwrap();
+    assert_eq!(res.body, "<p>Hello</p>\n");
+
+    context.set_text_direction(Some(TextDirection::Rtl));
+    let res = render_content("שלום\n\n<!-- more -->\n\nעוד", &context).unwrap();
     assert_eq!(
         res.body,
-        "<p><a rel=\"noreferrer\" href=\"https://google.com\">https://google.com</a></p>\n"
+        "<div dir=\"rtl\">\n<p>שלום</p>\n<span id=\"continue-reading\"></span>\n<p>עוד</p>\n</div>\n"
     );
+    assert_eq!(res.summary(), Some("<div dir=\"rtl\">\n<p>שלום</p>\n</div>\n".to_string()));
 }
 
 #[test]
-fn can_set_all_options_for_external_link() {
+fn can_detect_rtl_paragraphs_in_auto_text_direction() {
     let permalinks_ctx = HashMap::new();
-    let mut config = Config::default_for_test();
-    config.markdown.external_links_target_blank = true;
-    config.markdown.external_links_no_follow = true;
-    config.markdown.external_links_no_referrer = true;
-    let context = RenderContext::new(
+    let config = Config::default_for_test();
+    let mut context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
@@ -1605,16 +3719,21 @@ fn can_set_all_options_for_external_link() {
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content("<https://google.com>", &context).unwrap();
-    assert_eq!(res.body, "<p><a rel=\"noopener nofollow noreferrer\" target=\"_blank\" href=\"https://google.com\">https://google.com</a></p>\n");
+    context.set_text_direction(Some(TextDirection::Auto));
+
+    let res = render_content("Hello\n\n**مرحبا** world\n\n1. שלום", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<div dir=\"auto\">\n<p>Hello</p>\n<p dir=\"rtl\"><strong>مرحبا</strong> world</p>\n<ol>\n<li>שלום</li>\n</ol>\n</div>\n"
+    );
 }
 
 #[test]
-fn can_use_smart_punctuation() {
+fn can_detect_rtl_lead_paragraph() {
     let permalinks_ctx = HashMap::new();
     let mut config = Config::default_for_test();
-    config.markdown.smart_punctuation = true;
-    let context = RenderContext::new(
+    config.markdown.lead_paragraph_class = Some("lead".to_owned());
+    let mut context = RenderContext::new(
         &ZOLA_TERA,
         &config,
         &config.default_language,
@@ -1622,83 +3741,135 @@ fn can_use_smart_punctuation() {
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let res = render_content(r#"This -- is "it"..."#, &context).unwrap();
-    assert_eq!(res.body, "<p>This – is “it”…</p>\n");
+    context.set_text_direction(Some(TextDirection::Auto));
+
+    let res = render_content("**مرحبا** world\n\nשלום", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<div dir=\"auto\">\n<p class=\"lead\" dir=\"rtl\"><strong>مرحبا</strong> world</p>\n<p dir=\"rtl\">שלום</p>\n</div>\n"
+    );
+
+    let res = render_content("Hello\n\nשלום", &context).unwrap();
+    assert_eq!(
+        res.body,
+        "<div dir=\"auto\">\n<p class=\"lead\">Hello</p>\n<p dir=\"rtl\">שלום</p>\n</div>\n"
+    );
 }
 
-// https://zola.discourse.group/t/zola-0-15-md-shortcode-stopped-working/1099/2
 #[test]
-fn md_shortcode_regression() {
+fn can_render_summary_before_more_marker() {
     let permalinks_ctx = HashMap::new();
-    let mut tera = Tera::default();
-    tera.extend(&ZOLA_TERA).unwrap();
-    tera.add_raw_template("shortcodes/code.md", "123").unwrap();
     let config = Config::default_for_test();
-    let mut context = RenderContext::new(
-        &tera,
+    let context = RenderContext::new(
+        &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let shortcode_def = utils::templates::get_shortcodes(&tera);
-    context.set_shortcode_definitions(&shortcode_def);
-
-    let markdown_string = r#"
-ttest1
-
-{{ code(path = "content/software/supercollider/pakt-februari/pakt29.scd", syntax = "supercollider") }}
+    let content = "# Title\n\nFirst *paragraph*\n\nSecond paragraph\n\n<!-- more -->\n\nThe rest";
+    let summary = render_summary(content, None, &context).unwrap();
+    assert_eq!(
+        summary,
+        "<h1 id=\"title\">Title</h1>\n<p>First <em>paragraph</em></p>\n<p>Second paragraph</p>\n"
+    );
+    assert_eq!(summary, render_content(content, &context).unwrap().summary().unwrap());
+}
 
-ttest2
+#[test]
+fn can_render_first_paragraph_as_summary() {
+    let permalinks_ctx = HashMap::new();
+    let config = Config::default_for_test();
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::None,
+    );
+    // The marker in a code block doesn't count
+    let content = "# Title\n\n- A list\n\n  with a paragraph\n\nThe **first** one\nstill\n\n```\n<!-- more -->\n```\n\nThe rest";
+    assert_eq!(
+        render_summary(content, None, &context).unwrap(),
+        "<p>The <strong>first</strong> one\nstill</p>\n"
+    );
 
-{{ code(path = "content/software/supercollider/pakt-februari/pakt29.scd", syntax = "supercollider") }}"#;
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, "<p>ttest1</p>\n<p>123</p>\n<p>ttest2</p>\n<p>123</p>\n");
+    assert_eq!(render_summary("# Only a title", None, &context).unwrap(), "");
 }
 
-// https://github.com/getzola/zola/issues/1689
 #[test]
-fn html_shortcode_regression() {
+fn can_render_front_matter_summary() {
     let permalinks_ctx = HashMap::new();
-    let mut tera = Tera::default();
-    tera.extend(&ZOLA_TERA).unwrap();
-    tera.add_raw_template("shortcodes/ex.html", "1").unwrap();
-    tera.add_raw_template("shortcodes/book.html", "2").unwrap();
-    tera.add_raw_template("shortcodes/std.html", "3").unwrap();
     let config = Config::default_for_test();
-    let mut context = RenderContext::new(
-        &tera,
+    let context = RenderContext::new(
+        &ZOLA_TERA,
         &config,
         &config.default_language,
         "",
         &permalinks_ctx,
         InsertAnchor::None,
     );
-    let shortcode_def = utils::templates::get_shortcodes(&tera);
-    context.set_shortcode_definitions(&shortcode_def);
-
-    let markdown_string = r#"{{ book(page="") }} {{ ex(page="") }} {{ std(page="std") }}"#;
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, "<p>2 1 3</p>\n");
+    // It is used instead of the content even when the content has a marker
+    let content = "First paragraph
 
-    // And in html
-    let markdown_string = r#"<p>{{ book(page="") }} {{ ex(page="") }} {{ std(page="std") }}</p>"#;
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, "<p>2 1 3</p>");
+<!-- more -->
 
-    // Another one with newlines
-    let markdown_string = "<p>\n{{ book(page='') }}\n</p>";
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, "<p>\n2\n</p>");
+The rest";
+    assert_eq!(
+        render_summary(content, Some("A *short* summary"), &context).unwrap(),
+        "<p>A <em>short</em> summary</p>
+"
+    );
+}
 
-    // And another one
-    let markdown_string = "<span>{{ book(page='') }}</span>\n**The Book** {{ book(page='') }}";
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, "<p><span>2</span>\n<strong>The Book</strong> 2</p>\n");
+#[test]
+fn can_wrap_headings_in_sections() {
+    let permalinks_ctx = HashMap::new();
+    let mut config = Config::default_for_test();
+    config.markdown.heading_sections = true;
+    let context = RenderContext::new(
+        &ZOLA_TERA,
+        &config,
+        &config.default_language,
+        "",
+        &permalinks_ctx,
+        InsertAnchor::Left,
+    );
+    let content =
+        "Intro\n\n# One\n\nFirst\n\n## One A\n\n> ## Quoted\n\n## One B {#custom}\n\n# Two\n\nLast";
+    let res = render_content(content, &context).unwrap();
+    let anchor = |id: &str| {
+        format!(
+            "<a class=\"zola-anchor\" href=\"#{}\" aria-label=\"Anchor link for: {}\">🔗</a>",
+            id, id
+        )
+    };
+    assert_eq!(
+        res.body,
+        format!(
+            "<p>Intro</p>\n\
+             <section role=\"region\" aria-labelledby=\"one\">\n<h1 id=\"one\">{}One</h1>\n<p>First</p>\n\
+             <section role=\"region\" aria-labelledby=\"one-a\">\n<h2 id=\"one-a\">{}One A</h2>\n\
+             <blockquote>\n<h2 id=\"quoted\">{}Quoted</h2>\n</blockquote>\n\
+             </section>\n\
+             <section role=\"region\" aria-labelledby=\"custom\">\n<h2 id=\"custom\">{}One B</h2>\n\
+             </section>\n</section>\n\
+             <section role=\"region\" aria-labelledby=\"two\">\n<h1 id=\"two\">{}Two</h1>\n<p>Last</p>\n\
+             </section>\n",
+            anchor("one"),
+            anchor("one-a"),
+            anchor("quoted"),
+            anchor("custom"),
+            anchor("two")
+        )
+    );
 
-    // with some text in between
-    let markdown_string = r#"a.{{ book(page="") }} b.{{ ex(page="") }} c.{{ std(page="std") }}"#;
-    let res = render_content(markdown_string, &context).unwrap();
-    assert_eq!(res.body, "<p>a.2 b.1 c.3</p>\n");
+    // The sections opened before the summary marker are closed in the summary
+    let res =
+        render_content("# One\n\n## One A\n\nHello\n\n<!-- more -->\n\nMore", &context).unwrap();
+    let summary = res.summary().unwrap();
+    assert_eq!(summary.matches("<section").count(), 2);
+    assert!(summary.ends_with("</section>\n</section>\n"));
 }
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+
+use tera::{to_value, Tera};
+
+use config::Config;
+use front_matter::InsertAnchor;
+use rendering::testing::{
+    insert_md_shortcodes, markdown_to_html, Shortcode, SHORTCODE_PLACEHOLDER,
+};
+use rendering::RenderContext;
+use templates::ZOLA_TERA;
+
+#[test]
+fn can_build_shortcodes_outside_of_rendering() {
+    let mut tera = Tera::default();
+    tera.extend(&ZOLA_TERA).unwrap();
+    tera.add_raw_template("shortcodes/greet.md", "Hello *{{ name }}* ({{ nth }})").unwrap();
+    tera.add_raw_template("shortcodes/note.html", "<aside>{{ body }}</aside>").unwrap();
+    let config = Config::default_for_test();
+    let permalinks = HashMap::new();
+    let context = RenderContext::new(&tera, &config, "en", "", &permalinks, InsertAnchor::None);
+
+    let mut args = HashMap::new();
+    args.insert("name", "Zola");
+    let content = format!("{}\n\n{}", SHORTCODE_PLACEHOLDER, SHORTCODE_PLACEHOLDER);
+    let second_start = SHORTCODE_PLACEHOLDER.len() + 2;
+    let shortcodes = vec![
+        Shortcode::new("greet", "shortcodes/greet.md", 0..SHORTCODE_PLACEHOLDER.len())
+            .with_args(to_value(&args).unwrap())
+            .with_nth(2),
+        Shortcode::new(
+            "note",
+            "shortcodes/note.html",
+            second_start..second_start + SHORTCODE_PLACEHOLDER.len(),
+        )
+        .with_body("Some note"),
+    ];
+
+    let (content, html_shortcodes) = insert_md_shortcodes(content, shortcodes, &context).unwrap();
+    assert_eq!(content, format!("Hello *Zola* (2)\n\n{}", SHORTCODE_PLACEHOLDER));
+    assert_eq!(html_shortcodes.len(), 1);
+
+    let res = markdown_to_html(&content, &context, html_shortcodes).unwrap();
+    assert_eq!(res.body, "<p>Hello <em>Zola</em> (2)</p>\n<aside>Some note</aside>");
+}
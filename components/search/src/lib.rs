@@ -4,28 +4,33 @@ use elasticlunr::pipeline;
 use elasticlunr::pipeline::TokenizerFn;
 use elasticlunr::{Index, Language};
 use lazy_static::lazy_static;
+use serde_derive::Serialize;
 
 use config::{Config, Search};
-use errors::{bail, Result};
-use library::{Library, Section};
+use errors::{bail, Error, Result};
+use library::{Library, Page, Section};
+use rendering::Heading;
 
 pub const ELASTICLUNR_JS: &str = include_str!("elasticlunr.min.js");
 
+/// A cleaner removing all the tags, and the content of the given ones
+fn tag_stripper(clean_content_tags: &[&'static str]) -> ammonia::Builder<'static> {
+    let mut builder = ammonia::Builder::new();
+    builder
+        .tags(HashSet::new())
+        .tag_attributes(HashMap::new())
+        .generic_attributes(HashSet::new())
+        .link_rel(None)
+        .allowed_classes(HashMap::new())
+        .clean_content_tags(clean_content_tags.iter().copied().collect());
+    builder
+}
+
 lazy_static! {
-    static ref AMMONIA: ammonia::Builder<'static> = {
-        let mut clean_content = HashSet::new();
-        clean_content.insert("script");
-        clean_content.insert("style");
-        let mut builder = ammonia::Builder::new();
-        builder
-            .tags(HashSet::new())
-            .tag_attributes(HashMap::new())
-            .generic_attributes(HashSet::new())
-            .link_rel(None)
-            .allowed_classes(HashMap::new())
-            .clean_content_tags(clean_content);
-        builder
-    };
+    static ref AMMONIA: ammonia::Builder<'static> = tag_stripper(&["script", "style"]);
+    /// Same as above but also removes the code blocks
+    static ref AMMONIA_WITHOUT_CODE: ammonia::Builder<'static> =
+        tag_stripper(&["script", "style", "pre"]);
 }
 
 fn build_fields(search_config: &Search) -> Vec<String> {
@@ -106,7 +111,7 @@ fn fill_index(
     }
 
     if search_config.include_content {
-        let body = AMMONIA.clean(content).to_string();
+        let body = clean_content(search_config, content);
         if let Some(truncate_len) = search_config.truncate_content_length {
             // Not great for unicode
             // TODO: fix it like the truncate in Tera
@@ -122,6 +127,77 @@ fn fill_index(
     row
 }
 
+/// The HTML content without its tags, and without its code blocks if they are not indexed.
+/// The text is still HTML-escaped
+fn clean_content(search_config: &Search, content: &str) -> String {
+    if search_config.include_code_blocks {
+        AMMONIA.clean(content).to_string()
+    } else {
+        AMMONIA_WITHOUT_CODE.clean(content).to_string()
+    }
+}
+
+/// Unescapes the characters escaped by ammonia, to get plain text
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&amp;", "&")
+}
+
+/// An entry of the JSON index of the pages built with `build_json_index`
+#[derive(Debug, Serialize)]
+struct JsonIndexEntry<'a> {
+    title: &'a str,
+    url: &'a str,
+    body_plaintext: String,
+    headings: Vec<&'a str>,
+}
+
+/// The titles of the headings and all their children, in order
+fn heading_titles<'a>(headings: &'a [Heading], titles: &mut Vec<&'a str>) {
+    for heading in headings {
+        titles.push(&heading.title);
+        heading_titles(&heading.children, titles);
+    }
+}
+
+fn json_index_entry<'a>(page: &'a Page, config: &Config) -> JsonIndexEntry<'a> {
+    let search_config = &config.languages[&page.lang].search;
+    let mut headings = Vec::new();
+    heading_titles(&page.toc, &mut headings);
+
+    JsonIndexEntry {
+        title: page.meta.title.as_deref().unwrap_or_default(),
+        url: &page.permalink,
+        body_plaintext: unescape_html(clean_content(search_config, &page.content).trim()),
+        headings,
+    }
+}
+
+/// Returns a JSON array with the title, permalink, plain text content and heading titles of
+/// every page of the site in the search index, in all languages, to be used by any client-side
+/// search library
+pub fn build_json_index(library: &Library, config: &Config) -> Result<String> {
+    let mut entries = Vec::new();
+    for section in library.sections_values() {
+        if !section.meta.in_search_index {
+            continue;
+        }
+        for key in &section.pages {
+            let page = library.get_page_by_key(*key);
+            if page.meta.in_search_index {
+                entries.push(json_index_entry(page, config));
+            }
+        }
+    }
+
+    serde_json::to_string(&entries)
+        .map_err(|e| Error::chain("Failed to serialize the JSON search index", e))
+}
+
 /// Returns the generated JSON index with all the documents of the site added using
 /// the language given
 /// Errors if the language given is not available in Elasticlunr
@@ -269,4 +345,16 @@ mod tests {
         assert_eq!(res[0], title.unwrap());
         assert_eq!(res[1], content[..5]);
     }
+
+    #[test]
+    fn can_leave_code_blocks_out_of_content() {
+        let mut config = Config::default();
+        let content = "<p>Some &lt;content&gt;</p><pre><code>let a = 1;</code></pre>";
+        assert_eq!(clean_content(&config.search, content), "Some &lt;content&gt;let a = 1;");
+
+        config.search.include_code_blocks = false;
+        let cleaned = clean_content(&config.search, content);
+        assert_eq!(cleaned, "Some &lt;content&gt;");
+        assert_eq!(unescape_html(&cleaned), "Some <content>");
+    }
 }
@@ -0,0 +1,142 @@
+//! Removing the rules of the highlight themes CSS that no page uses, when
+//! `markdown.purge_highlight_css` is set.
+//!
+//! A theme has rules for hundreds of scopes while a site only highlights a few languages, so
+//! most of them are never matched. Only the files written to the output directory are changed,
+//! the ones in the `static` directory keep every rule.
+use std::collections::HashSet;
+use std::fs::read_to_string;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use walkdir::WalkDir;
+
+use crate::Site;
+use errors::{Error, Result};
+
+lazy_static! {
+    static ref CLASS_RE: Regex =
+        Regex::new(r#"(?i)\sclass\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+}
+
+/// Adds the classes of each element of the HTML to `used`, sorted so they can be deduplicated
+pub fn used_classes(html: &str, used: &mut HashSet<Vec<String>>) {
+    for caps in CLASS_RE.captures_iter(html) {
+        let value = caps.get(1).or_else(|| caps.get(2)).map(|m| m.as_str()).unwrap_or_default();
+        let mut classes: Vec<String> = value.split_whitespace().map(|c| c.to_string()).collect();
+        if classes.is_empty() {
+            continue;
+        }
+        classes.sort();
+        classes.dedup();
+        used.insert(classes);
+    }
+}
+
+/// Whether a selector like `.z-source.z-rust .z-string` can match: each of its compound
+/// selectors needs an element with all of its classes
+fn is_selector_used(selector: &str, used: &HashSet<Vec<String>>) -> bool {
+    selector.split_whitespace().all(|compound| {
+        let classes: Vec<&str> = compound.split('.').filter(|c| !c.is_empty()).collect();
+        // Not a plain class selector, so keep it rather than guessing
+        if !compound.starts_with('.') || classes.iter().any(|c| c.contains(&[':', '['][..])) {
+            return true;
+        }
+        used.iter().any(|element| classes.iter().all(|c| element.iter().any(|e| e == c)))
+    })
+}
+
+/// Removes the selectors of the CSS that can't match any element with the `used` classes,
+/// and the rules left without selectors. Comments between the rules are kept.
+pub fn purge_unused_classes(css: &str, used: &HashSet<Vec<String>>) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+
+    while let Some(end) = rest.find('}') {
+        let rule = &rest[..=end];
+        rest = &rest[end + 1..];
+        let open = match rule.find('{') {
+            Some(open) => open,
+            None => {
+                out.push_str(rule);
+                continue;
+            }
+        };
+        let prelude = &rule[..open];
+        let selectors_start = prelude.rfind("*/").map(|i| i + 2).unwrap_or(0);
+        let (comment, selectors) = prelude.split_at(selectors_start);
+        out.push_str(comment);
+
+        let kept: Vec<&str> =
+            selectors.split(',').map(|s| s.trim()).filter(|s| is_selector_used(s, used)).collect();
+        if kept.is_empty() {
+            continue;
+        }
+        let leading = &selectors[..selectors.len() - selectors.trim_start().len()];
+        out.push_str(leading);
+        out.push_str(&kept.join(", "));
+        out.push(' ');
+        out.push_str(&rule[open..]);
+    }
+    out.push_str(rest);
+
+    out
+}
+
+/// Purges the highlight themes CSS files of the output directory of the rules not used by
+/// any of its HTML files.
+pub fn purge_highlight_css_files(site: &Site) -> Result<()> {
+    let mut used = HashSet::new();
+    for entry in WalkDir::new(&site.output_path).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension().map(|e| e != "html").unwrap_or(true) {
+            continue;
+        }
+        let html = read_to_string(path)
+            .map_err(|e| Error::chain(format!("Failed to read {}", path.display()), e))?;
+        used_classes(&html, &mut used);
+    }
+
+    for theme in &site.config.markdown.highlight_themes_css {
+        let path = site.output_path.join(&theme.filename);
+        if !path.is_file() {
+            continue;
+        }
+        let css = read_to_string(&path)
+            .map_err(|e| Error::chain(format!("Failed to read {}", path.display()), e))?;
+        std::fs::write(&path, purge_unused_classes(&css, &used))
+            .map_err(|e| Error::chain(format!("Failed to write {}", path.display()), e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{purge_unused_classes, used_classes};
+
+    #[test]
+    fn can_purge_unused_highlight_classes() {
+        let css =
+            "/*\n * theme \"Test\" generated by syntect\n */\n\n.z-code {\n color: #fff;\n}\n\n\
+.z-comment, .z-punctuation.z-definition.z-comment {\n color: #aaa;\n}\n\
+.z-keyword {\n color: #bbb;\n}\n\
+.z-entity.z-name.z-function, .z-meta.z-require {\n color: #ccc;\n}\n\
+.z-source.z-rust .z-string {\n color: #ddd;\n}\n";
+        let html = r#"<pre class="language-rust z-code"><code><span class="z-source z-rust">
+<span class="z-storage z-type z-function z-rust">fn</span> <span class="z-entity z-name z-function z-rust">main</span>
+<span class="z-comment">// hi</span><span class="z-string">"a"</span></span></code></pre>"#;
+        let mut used = HashSet::new();
+        used_classes(html, &mut used);
+
+        assert_eq!(
+            purge_unused_classes(css, &used),
+            "/*\n * theme \"Test\" generated by syntect\n */\n\n.z-code {\n color: #fff;\n}\n\n\
+.z-comment {\n color: #aaa;\n}\n\
+.z-entity.z-name.z-function {\n color: #ccc;\n}\n\
+.z-source.z-rust .z-string {\n color: #ddd;\n}\n"
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! A lightweight well-formedness check of the generated HTML, used by `zola build --validate-html`.
+//!
+//! This is not a full HTML5 validator: it only looks at the structure of the document to catch
+//! the kind of mistakes templates and shortcodes tend to make, like a tag that is never closed
+//! or a `<div>` ending up inside a `<p>`.
+use std::fs::read_to_string;
+use std::path::Path;
+
+use walkdir::WalkDir;
+
+use crate::Site;
+use errors::{Error, ErrorKind, Result};
+
+/// Elements that never have content or a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements whose closing tag can be omitted
+const OPTIONAL_CLOSE_ELEMENTS: &[&str] = &[
+    "body", "caption", "colgroup", "dd", "dt", "head", "html", "li", "optgroup", "option", "p",
+    "rp", "rt", "tbody", "td", "tfoot", "th", "thead", "tr",
+];
+
+/// Elements whose content is raw text and should not be parsed
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title"];
+
+/// Elements that can only contain inline content
+const INLINE_CONTAINERS: &[&str] = &[
+    "abbr", "b", "bdi", "bdo", "cite", "code", "data", "dfn", "em", "h1", "h2", "h3", "h4", "h5",
+    "h6", "i", "kbd", "label", "mark", "p", "q", "s", "samp", "small", "span", "strong", "sub",
+    "sup", "time", "u", "var",
+];
+
+/// Elements that can't be put in an inline container
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Returns the tags implicitly closed when `opening` is opened right after them
+fn implicitly_closed_by(opening: &str) -> &'static [&'static str] {
+    match opening {
+        "li" => &["li"],
+        "dt" | "dd" => &["dt", "dd"],
+        "tr" => &["tr", "td", "th"],
+        "td" | "th" => &["td", "th"],
+        "option" => &["option"],
+        "p" => &["p"],
+        _ => &[],
+    }
+}
+
+fn line_at(html: &str, offset: usize) -> usize {
+    html[..offset].matches('\n').count() + 1
+}
+
+/// Finds the end of a tag starting at `start`, skipping over `>` in quoted attribute values
+fn find_tag_end(html: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in html[start..].char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Some(start + i),
+            _ => (),
+        }
+    }
+    None
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == ':')
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Checks that the given HTML is well-formed, returning a description of every problem found
+/// along with the line it is on
+pub fn check_html(html: &str) -> Vec<(usize, String)> {
+    let mut errors = Vec::new();
+    // The currently opened elements and the line they were opened on
+    let mut stack: Vec<(String, usize)> = Vec::new();
+    let mut pos = 0;
+
+    while let Some(found) = html[pos..].find('<') {
+        let start = pos + found;
+        let rest = &html[start..];
+
+        if rest.starts_with("<!--") {
+            pos = match rest.find("-->") {
+                Some(end) => start + end + 3,
+                None => {
+                    errors.push((line_at(html, start), "Comment is not closed".to_string()));
+                    break;
+                }
+            };
+            continue;
+        }
+
+        let is_closing = rest.starts_with("</");
+        let name_start = if is_closing { start + 2 } else { start + 1 };
+        // Doctypes, processing instructions and text like `a < b` are not tags we care about
+        if !html[name_start..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            pos = if rest.starts_with("<!") || rest.starts_with("<?") {
+                find_tag_end(html, start).map(|end| end + 1).unwrap_or(html.len())
+            } else {
+                start + 1
+            };
+            continue;
+        }
+
+        let line = line_at(html, start);
+        let end = match find_tag_end(html, start) {
+            Some(end) => end,
+            None => {
+                errors.push((line, "Tag is not terminated by `>`".to_string()));
+                break;
+            }
+        };
+        let name = tag_name(&html[name_start..end]);
+        pos = end + 1;
+
+        if is_closing {
+            match stack.iter().rposition(|(n, _)| n == &name) {
+                Some(idx) => {
+                    for (unclosed, opened_on) in stack.drain(idx..).skip(1).rev() {
+                        if !OPTIONAL_CLOSE_ELEMENTS.contains(&unclosed.as_str()) {
+                            errors.push((
+                                opened_on,
+                                format!("`<{}>` is not closed before `</{}>`", unclosed, name),
+                            ));
+                        }
+                    }
+                }
+                None => errors.push((line, format!("`</{}>` doesn't close any open tag", name))),
+            }
+            continue;
+        }
+
+        if html[start..end].ends_with('/') || VOID_ELEMENTS.contains(&name.as_str()) {
+            continue;
+        }
+
+        let closed_by = implicitly_closed_by(&name);
+        if stack.last().map(|(n, _)| closed_by.contains(&n.as_str())).unwrap_or(false) {
+            stack.pop();
+        }
+
+        if BLOCK_ELEMENTS.contains(&name.as_str()) {
+            if let Some((parent, _)) =
+                stack.iter().rev().find(|(n, _)| INLINE_CONTAINERS.contains(&n.as_str()))
+            {
+                errors.push((line, format!("`<{}>` is not allowed inside `<{}>`", name, parent)));
+            }
+        }
+
+        if RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+            let closing = format!("</{}", name);
+            pos = match html[pos..].to_ascii_lowercase().find(&closing) {
+                Some(idx) => find_tag_end(html, pos + idx).map(|e| e + 1).unwrap_or(html.len()),
+                None => {
+                    errors.push((line, format!("`<{}>` is not closed", name)));
+                    html.len()
+                }
+            };
+            continue;
+        }
+
+        stack.push((name, line));
+    }
+
+    for (unclosed, opened_on) in stack {
+        if !OPTIONAL_CLOSE_ELEMENTS.contains(&unclosed.as_str()) {
+            errors.push((opened_on, format!("`<{}>` is not closed", unclosed)));
+        }
+    }
+
+    errors.sort_by_key(|(line, _)| *line);
+    errors
+}
+
+/// Checks all the HTML files of the output directory, failing if any of them is malformed.
+pub fn check_html_files(site: &Site) -> Result<()> {
+    println!("Checking the generated HTML.");
+    let mut checked = 0usize;
+    let mut errors = Vec::new();
+
+    for entry in WalkDir::new(&site.output_path).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension().map(|e| e != "html").unwrap_or(true) {
+            continue;
+        }
+        let html = read_to_string(path)
+            .map_err(|e| Error::chain(format!("Failed to read {}", path.display()), e))?;
+        checked += 1;
+        let relative = path.strip_prefix(&site.output_path).unwrap_or_else(|_| Path::new(path));
+        for (line, err) in check_html(&html) {
+            errors.push(format!("{}:{}: {}", relative.display(), line, err));
+        }
+    }
+
+    match errors.len() {
+        0 => {
+            println!("> Successfully checked {} HTML file(s).", checked);
+            Ok(())
+        }
+        errors_total => {
+            println!("> Checked {} HTML file(s): {} problem(s) found.", checked, errors_total);
+            Err(Error { kind: ErrorKind::Msg(errors.join("\n")), source: None })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_html;
+
+    #[test]
+    fn accepts_well_formed_html() {
+        let html = r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="utf-8"><title>a < b</title>
+<script>if (a < b && "</div>") {}</script></head>
+<body>
+<!-- <div> -->
+<p>Hello <a href="/" title="a > b"><strong>world</strong></a><br></p>
+<ul><li>one<li>two</ul>
+<a href="/"><div>block link</div></a>
+<svg><path d="M0 0" /></svg>
+</body>
+</html>"#;
+        assert_eq!(check_html(html), vec![]);
+    }
+
+    #[test]
+    fn reports_unclosed_tags() {
+        let html = "<div>\n<section>\n</div>";
+        assert_eq!(
+            check_html(html),
+            vec![(2, "`<section>` is not closed before `</div>`".to_string())]
+        );
+        assert_eq!(
+            check_html("<div><span>"),
+            vec![
+                (1, "`<div>` is not closed".to_string()),
+                (1, "`<span>` is not closed".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_stray_closing_tags() {
+        assert_eq!(
+            check_html("<div></div></div>"),
+            vec![(1, "`</div>` doesn't close any open tag".to_string())]
+        );
+    }
+
+    #[test]
+    fn reports_blocks_inside_inline_elements() {
+        assert_eq!(
+            check_html("<p>Hello\n<div>world</div></p>"),
+            vec![(2, "`<div>` is not allowed inside `<p>`".to_string())]
+        );
+        assert_eq!(
+            check_html("<span><ul><li>a</li></ul></span>"),
+            vec![(1, "`<ul>` is not allowed inside `<span>`".to_string())]
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Checking that the local images of the generated HTML exist, used by
+//! `zola build --validate-images` and `zola check --validate-images`.
+//!
+//! Only the `src` of `<img>` elements is looked at: images hosted elsewhere and `data:` URIs
+//! are skipped, the others need to be a file of the output directory when building, or of the
+//! `static` directories or an asset next to the content when checking.
+use std::collections::HashSet;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::svg_sprite::{attributes, local_src_path, IMG_RE};
+use crate::Site;
+use errors::{Error, ErrorKind, Result};
+
+/// Returns the `src` of every `<img>` of the HTML that doesn't exist, along with the line it is
+/// on. `resolve` gives the file a `src` points to, or `None` for images that aren't local.
+pub fn missing_images(
+    html: &str,
+    resolve: impl Fn(&str) -> Option<PathBuf>,
+) -> Vec<(usize, String)> {
+    IMG_RE
+        .find_iter(html)
+        .filter_map(|img| {
+            let src = attributes(img.as_str()).remove("src")?;
+            let file = resolve(&src)?;
+            if file.is_file() {
+                return None;
+            }
+            Some((html[..img.start()].matches('\n').count() + 1, src))
+        })
+        .collect()
+}
+
+/// Checks the images of all the HTML files of the output directory, failing if any of them
+/// points to a file that doesn't exist.
+pub fn check_image_files(site: &Site) -> Result<()> {
+    println!("Checking the images of the generated HTML.");
+    let mut errors = Vec::new();
+
+    for entry in WalkDir::new(&site.output_path).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension().map(|e| e != "html").unwrap_or(true) {
+            continue;
+        }
+        let html = read_to_string(path)
+            .map_err(|e| Error::chain(format!("Failed to read {}", path.display()), e))?;
+        let relative = path.strip_prefix(&site.output_path).unwrap_or_else(|_| Path::new(path));
+        for (line, src) in missing_images(&html, |src| local_src_path(site, path, src)) {
+            errors.push(format!("{}:{}: image `{}` not found", relative.display(), line, src));
+        }
+    }
+
+    match errors.len() {
+        0 => {
+            println!("> All the images were found.");
+            Ok(())
+        }
+        errors_total => {
+            println!("> {} missing image(s) found.", errors_total);
+            Err(Error { kind: ErrorKind::Msg(errors.join("\n")), source: None })
+        }
+    }
+}
+
+/// Checks the images of the content of the pages and sections without building the site,
+/// failing if any of them points to a file that isn't in the `static` directories or an asset
+/// next to the content. The images resized by `resize_image` are only created when building
+/// so they are skipped.
+pub fn check_content_images(site: &Site) -> Result<()> {
+    println!("Checking the images of the content.");
+    let library = site.library.read().unwrap();
+
+    let mut static_dirs = vec![site.static_path.clone()];
+    if let Some(ref theme) = site.config.theme {
+        static_dirs.push(site.base_path.join("themes").join(theme).join("static"));
+    }
+    // Where the assets next to the content end up, relative to the output directory
+    let mut assets = HashSet::new();
+    let mut contents = Vec::new();
+    for page in library.pages_values() {
+        assets.extend(asset_paths(&page.file.path, &page.path, &page.assets));
+        contents.push((&page.file.path, &page.path, &page.content));
+    }
+    for section in library.sections_values() {
+        assets.extend(asset_paths(&section.file.path, &section.path, &section.assets));
+        contents.push((&section.file.path, &section.path, &section.content));
+    }
+
+    let mut errors = Vec::new();
+    for (file, path, content) in contents {
+        // Where the page would be written, to resolve the `src` as when building
+        let output = site.output_path.join(path.trim_start_matches('/')).join("index.html");
+        let resolve = |src: &str| {
+            let file = local_src_path(site, &output, src)?;
+            let relative = file.strip_prefix(&site.output_path).ok()?.to_path_buf();
+            if relative.starts_with("processed_images")
+                || assets.contains(&relative)
+                || static_dirs.iter().any(|dir| dir.join(&relative).is_file())
+            {
+                return None;
+            }
+            Some(file)
+        };
+        let relative = file.strip_prefix(&site.base_path).unwrap_or(file);
+        for (_, src) in missing_images(content, resolve) {
+            errors.push(format!("{}: image `{}` not found", relative.display(), src));
+        }
+    }
+
+    match errors.len() {
+        0 => {
+            println!("> All the images were found.");
+            Ok(())
+        }
+        errors_total => {
+            println!("> {} missing image(s) found.", errors_total);
+            Err(Error { kind: ErrorKind::Msg(errors.join("\n")), source: None })
+        }
+    }
+}
+
+/// The paths relative to the output directory the assets of the page or section written at
+/// `path` from `file` are copied to
+fn asset_paths<'a>(
+    file: &'a Path,
+    path: &'a str,
+    assets: &'a [PathBuf],
+) -> impl Iterator<Item = PathBuf> + 'a {
+    assets.iter().filter_map(move |asset| {
+        let relative = asset.strip_prefix(file.parent()?).ok()?;
+        Some(Path::new(path.trim_start_matches('/')).join(relative))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+    use std::path::PathBuf;
+
+    use tempfile::tempdir;
+
+    use super::missing_images;
+
+    #[test]
+    fn reports_missing_local_images_only() {
+        let dir = tempdir().unwrap();
+        write(dir.path().join("present.png"), "").unwrap();
+        let resolve = |src: &str| -> Option<PathBuf> {
+            if src.starts_with("data:") || src.starts_with("https:") {
+                return None;
+            }
+            Some(dir.path().join(src.trim_start_matches('/')))
+        };
+        let html = r#"<p><img src="/present.png" alt="ok"></p>
+<p><img alt="missing" src="/missing.png"></p>
+<p><img src="data:image/png;base64,iVBORw0KGgo="></p>
+<p><img src="https://example.com/remote.png"></p>"#;
+
+        assert_eq!(missing_images(html, resolve), vec![(2, "/missing.png".to_string())]);
+    }
+}
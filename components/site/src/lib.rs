@@ -1,7 +1,11 @@
 pub mod feed;
+pub mod highlight_css;
+pub mod html_validation;
+pub mod image_validation;
 pub mod link_checking;
 pub mod sass;
 pub mod sitemap;
+pub mod svg_sprite;
 pub mod tpls;
 
 use std::collections::HashMap;
@@ -14,11 +18,12 @@ use rayon::prelude::*;
 use tera::{Context, Tera};
 use walkdir::{DirEntry, WalkDir};
 
-use config::{get_config, Config};
+use config::{get_config, AliasRedirects, Config};
 use errors::{bail, Error, Result};
 use front_matter::InsertAnchor;
 use library::{find_taxonomies, Library, Page, Paginator, Section, Taxonomy};
 use relative_path::RelativePathBuf;
+use rendering::SHORTCODE_PLACEHOLDER;
 use std::time::Instant;
 use templates::{load_tera, render_redirect_template};
 use utils::fs::{
@@ -63,6 +68,12 @@ pub struct Site {
     pub library: Arc<RwLock<Library>>,
     /// Whether to load draft pages
     include_drafts: bool,
+    /// Whether to check the generated HTML is well-formed after building
+    validate_html: bool,
+    /// Whether to check that the local images of the generated HTML exist after building
+    validate_images: bool,
+    /// If set, a JSON index of the pages for client-side search is written to that file
+    search_index_json: Option<PathBuf>,
     build_mode: BuildMode,
     shortcode_definitions: HashMap<String, ShortcodeDefinition>,
 }
@@ -81,7 +92,8 @@ impl Site {
         }
 
         let tera = load_tera(path, &config)?;
-        let shortcode_definitions = utils::templates::get_shortcodes(&tera);
+        let mut shortcode_definitions = utils::templates::get_shortcodes(&tera);
+        config.markdown.apply_shortcode_config(&mut shortcode_definitions);
 
         let content_path = path.join("content");
         let static_path = path.join("static");
@@ -100,6 +112,9 @@ impl Site {
             taxonomies: Vec::new(),
             permalinks: HashMap::new(),
             include_drafts: false,
+            validate_html: false,
+            validate_images: false,
+            search_index_json: None,
             // We will allocate it properly later on
             library: Arc::new(RwLock::new(Library::new(0, 0, false))),
             build_mode: BuildMode::Disk,
@@ -122,6 +137,22 @@ impl Site {
         self.include_drafts = true;
     }
 
+    /// Set the site to check the generated HTML once built.
+    pub fn enable_html_validation(&mut self) {
+        self.validate_html = true;
+    }
+
+    /// Set the site to check that every local image of the generated HTML exists once built.
+    pub fn enable_image_validation(&mut self) {
+        self.validate_images = true;
+    }
+
+    /// Set the site to write a JSON index of its pages for client-side search to that file
+    /// when building.
+    pub fn set_search_index_json<P: AsRef<Path>>(&mut self, path: P) {
+        self.search_index_json = Some(path.as_ref().to_path_buf());
+    }
+
     /// The index sections are ALWAYS at those paths
     /// There are one index section for the default language + 1 per language
     fn index_section_paths(&self) -> Vec<(PathBuf, Option<&str>)> {
@@ -504,6 +535,35 @@ impl Site {
         html
     }
 
+    /// Adds the `hreflang` alternate links of the translations of a page or section to its `<head>`
+    /// if `inject_hreflang_links` is set
+    fn inject_hreflang(&self, mut html: String, alternates: &[(&str, &str)]) -> String {
+        if !self.config.inject_hreflang_links || alternates.is_empty() {
+            return html;
+        }
+
+        let mut links = String::new();
+        for (lang, permalink) in alternates {
+            links.push_str(&format!(
+                "<link rel=\"alternate\" hreflang=\"{}\" href=\"{}\">\n",
+                lang, permalink
+            ));
+        }
+        if let Some((_, permalink)) =
+            alternates.iter().find(|(lang, _)| *lang == self.config.default_language)
+        {
+            links.push_str(&format!(
+                "<link rel=\"alternate\" hreflang=\"x-default\" href=\"{}\">\n",
+                permalink
+            ));
+        }
+        if let Some(index) = html.find("</head>") {
+            html.insert_str(index, &links);
+        }
+
+        html
+    }
+
     /// Copy the main `static` folder and the theme `static` folder if a theme is used
     pub fn copy_static_directories(&self) -> Result<()> {
         // The user files will overwrite the theme files
@@ -575,7 +635,8 @@ impl Site {
         let final_content = if !filename.ends_with("html") || !self.config.minify_html {
             content
         } else {
-            match minify::html(content) {
+            let keep_comments = self.config.minify_html_keep_comments;
+            match minify::html_preserving(content, &[SHORTCODE_PLACEHOLDER], keep_comments) {
                 Ok(minified_content) => minified_content,
                 Err(error) => bail!(error),
             }
@@ -603,12 +664,31 @@ impl Site {
 
     /// Renders a single content page
     pub fn render_page(&self, page: &Page) -> Result<()> {
-        let output = page.render_html(&self.tera, &self.config, &self.library.read().unwrap())?;
-        let content = self.inject_livereload(output);
+        let library = self.library.read().unwrap();
+        let mut outputs = match self.config.split_pages_longer_than {
+            Some(max_len) => page.render_html_parts(&self.tera, &self.config, &library, max_len)?,
+            None => vec![page.render_html(&self.tera, &self.config, &library)?],
+        };
+        let alternates = library.find_page_alternates(page);
         let components: Vec<&str> = page.path.split('/').collect();
+        let rest = outputs.split_off(1);
+        let output = self.inject_hreflang(outputs.remove(0), &alternates);
+        let content = self.inject_livereload(output);
         let current_path =
             self.write_content(&components, "index.html", content, !page.assets.is_empty())?;
 
+        // The other parts of a page split in several ones
+        for (i, output) in rest.into_iter().enumerate() {
+            let index = format!("{}", i + 2);
+            let mut part_components = components.clone();
+            part_components.retain(|c| !c.is_empty());
+            part_components.push("page");
+            part_components.push(&index);
+            let output = self.inject_hreflang(output, &alternates);
+            let content = self.inject_livereload(output);
+            self.write_content(&part_components, "index.html", content, false)?;
+        }
+
         // Copy any asset we found previously into the same directory as the index.html
         for asset in &page.assets {
             let asset_path = asset.as_path();
@@ -653,6 +733,15 @@ impl Site {
             start = log_time(start, "Built search index");
         }
 
+        if let Some(ref path) = self.search_index_json {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                ensure_directory_exists(parent)?;
+            }
+            let index = search::build_json_index(&self.library.read().unwrap(), &self.config)?;
+            create_file(path, &index)?;
+            start = log_time(start, "Built JSON search index");
+        }
+
         // Render aliases first to allow overwriting
         self.render_aliases()?;
         start = log_time(start, "Rendered aliases");
@@ -703,7 +792,29 @@ impl Site {
         start = log_time(start, "Processed images");
         // Processed images will be in static so the last step is to copy it
         self.copy_static_directories()?;
-        log_time(start, "Copied static dir");
+        start = log_time(start, "Copied static dir");
+
+        // Icons are inlined in the HTML written to disk, once the static files are there
+        if self.config.inline_svg_icons && self.build_mode == BuildMode::Disk {
+            svg_sprite::inline_svg_icon_files(self)?;
+            start = log_time(start, "Inlined SVG icons");
+        }
+
+        if self.config.markdown.purge_highlight_css && self.build_mode == BuildMode::Disk {
+            highlight_css::purge_highlight_css_files(self)?;
+            start = log_time(start, "Purged highlight css");
+        }
+
+        // Only the files written to disk can be checked
+        if self.validate_html && self.build_mode == BuildMode::Disk {
+            html_validation::check_html_files(self)?;
+            start = log_time(start, "Validated HTML");
+        }
+
+        if self.validate_images && self.build_mode == BuildMode::Disk {
+            image_validation::check_image_files(self)?;
+            log_time(start, "Validated images");
+        }
 
         Ok(())
     }
@@ -775,20 +886,32 @@ impl Site {
         Ok(())
     }
 
-    /// Renders all the aliases for each page/section: a magic HTML template that redirects to
-    /// the canonical one
+    /// Renders all the aliases for each page/section: either a magic HTML template that redirects to
+    /// the canonical one or a line in a `_redirects` file, depending on `alias_redirects`
     pub fn render_aliases(&self) -> Result<()> {
         ensure_directory_exists(&self.output_path)?;
         let library = self.library.read().unwrap();
-        for (_, page) in library.pages() {
-            for alias in &page.meta.aliases {
-                self.render_alias(alias, &page.permalink)?;
+        let mut redirects = Vec::new();
+        let pages = library.pages().values().map(|p| (&p.meta.aliases, &p.permalink));
+        let sections = library.sections().values().map(|s| (&s.meta.aliases, &s.permalink));
+        for (aliases, permalink) in pages.chain(sections) {
+            for alias in aliases {
+                match self.config.alias_redirects {
+                    AliasRedirects::Html => self.render_alias(alias, permalink)?,
+                    AliasRedirects::File => redirects.push(format!(
+                        "/{} {} 301",
+                        alias.trim_start_matches('/'),
+                        permalink
+                    )),
+                }
             }
         }
-        for (_, section) in library.sections() {
-            for alias in &section.meta.aliases {
-                self.render_alias(alias, &section.permalink)?;
-            }
+
+        if self.config.alias_redirects == AliasRedirects::File {
+            redirects.sort();
+            let mut content = redirects.join("\n");
+            content.push('\n');
+            self.write_content(&[], "_redirects", content, false)?;
         }
         Ok(())
     }
@@ -805,9 +928,16 @@ impl Site {
         Ok(())
     }
 
-    /// Renders robots.txt
+    /// Renders robots.txt, disallowing everything if drafts are included and
+    /// `disallow_robots_with_drafts` is set
     pub fn render_robots(&self) -> Result<()> {
         ensure_directory_exists(&self.output_path)?;
+        if self.include_drafts && self.config.disallow_robots_with_drafts {
+            let content = "User-agent: *\nDisallow: /\n".to_string();
+            self.write_content(&[], "robots.txt", content, false)?;
+            return Ok(());
+        }
+
         let mut context = Context::new();
         context.insert("config", &self.config.serialize(&self.config.default_language));
         let content = render_template("robots.txt", &self.tera, context, &self.config.theme)?;
@@ -888,6 +1018,16 @@ impl Site {
             .collect::<Result<()>>()
     }
 
+    /// The permalinks of everything the site renders, the same ones listed in the sitemap,
+    /// sorted alphabetically
+    pub fn routes(&self) -> Vec<String> {
+        let library = self.library.read().unwrap();
+        sitemap::find_entries(&library, &self.taxonomies[..], &self.config)
+            .into_iter()
+            .map(|entry| entry.permalink.into_owned())
+            .collect()
+    }
+
     /// What it says on the tin
     pub fn render_sitemap(&self) -> Result<()> {
         ensure_directory_exists(&self.output_path)?;
@@ -1054,8 +1194,9 @@ impl Site {
                 &Paginator::from_section(section, &self.library.read().unwrap()),
             )?;
         } else {
-            let output =
-                section.render_html(&self.tera, &self.config, &self.library.read().unwrap())?;
+            let library = self.library.read().unwrap();
+            let output = section.render_html(&self.tera, &self.config, &library)?;
+            let output = self.inject_hreflang(output, &library.find_section_alternates(section));
             let content = self.inject_livereload(output);
             self.write_content(&components, "index.html", content, false)?;
         }
@@ -1103,12 +1244,14 @@ impl Site {
                 pager_components.push(&paginator.paginate_path);
                 let pager_path = format!("{}", pager.index);
                 pager_components.push(&pager_path);
-                let output = paginator.render_pager(
-                    pager,
-                    &self.config,
-                    &self.tera,
-                    &self.library.read().unwrap(),
-                )?;
+                let library = self.library.read().unwrap();
+                let output = paginator.render_pager(pager, &self.config, &self.tera, &library)?;
+                let output = match paginator.section() {
+                    Some(section) => {
+                        self.inject_hreflang(output, &library.find_section_alternates(section))
+                    }
+                    None => output,
+                };
                 let content = self.inject_livereload(output);
 
                 if pager.index > 1 {
@@ -61,18 +61,22 @@ pub fn find_entries<'a>(
     taxonomies: &'a [Taxonomy],
     config: &'a Config,
 ) -> Vec<SitemapEntry<'a>> {
-    let pages = library
-        .pages_values()
-        .iter()
-        .map(|p| {
-            let mut entry = SitemapEntry::new(
-                Cow::Borrowed(&p.permalink),
-                p.meta.updated.clone().or_else(|| p.meta.date.clone()),
-            );
-            entry.add_extra(&p.meta.extra);
-            entry
-        })
-        .collect::<Vec<_>>();
+    let mut pages = Vec::new();
+    for p in library.pages_values() {
+        let date = p.meta.updated.clone().or_else(|| p.meta.date.clone());
+        let mut entry = SitemapEntry::new(Cow::Borrowed(&p.permalink), date.clone());
+        entry.add_extra(&p.meta.extra);
+        pages.push(entry);
+
+        // The other parts of the pages split by `split_pages_longer_than`
+        if let Some(max_len) = config.split_pages_longer_than {
+            for permalink in p.part_permalinks(max_len).into_iter().skip(1) {
+                let mut entry = SitemapEntry::new(Cow::Owned(permalink), date.clone());
+                entry.add_extra(&p.meta.extra);
+                pages.push(entry);
+            }
+        }
+    }
 
     let mut sections = library
         .sections_values()
@@ -0,0 +1,224 @@
+//! Inlining the local SVG icons referenced by `<img>` elements in a sprite, used when
+//! `inline_svg_icons` is set.
+//!
+//! Every icon a page uses becomes a `<symbol>` of a hidden `<svg>` added at the start of its
+//! `<body>`, and the `<img>` elements are replaced by `<svg><use href="#..."></use></svg>`,
+//! so a page with many icons doesn't need one request per icon.
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use walkdir::WalkDir;
+
+use crate::Site;
+use errors::{Error, Result};
+
+lazy_static! {
+    pub(crate) static ref IMG_RE: Regex = Regex::new(r"(?i)<img\s[^>]*>").unwrap();
+    static ref ATTR_RE: Regex =
+        Regex::new(r#"([a-zA-Z][\w:-]*)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    static ref SVG_RE: Regex = Regex::new(r"(?is)<svg\b([^>]*)>(.*)</svg>").unwrap();
+    static ref BODY_RE: Regex = Regex::new(r"(?i)<body\b[^>]*>").unwrap();
+}
+
+/// The attributes of a tag, with lowercased names
+pub(crate) fn attributes(tag: &str) -> HashMap<String, String> {
+    ATTR_RE
+        .captures_iter(tag)
+        .map(|c| {
+            let value = c.get(2).or_else(|| c.get(3)).map(|m| m.as_str()).unwrap_or_default();
+            (c[1].to_lowercase(), value.to_string())
+        })
+        .collect()
+}
+
+/// Turns the SVG file into a `<symbol>` with the given id, keeping its `viewBox`
+fn make_symbol(svg: &str, id: &str) -> Option<String> {
+    let caps = SVG_RE.captures(svg)?;
+    let view_box = attributes(&caps[1])
+        .remove("viewbox")
+        .map(|v| format!(" viewBox=\"{}\"", v))
+        .unwrap_or_default();
+    Some(format!("<symbol id=\"{}\"{}>{}</symbol>\n", id, view_box, caps[2].trim()))
+}
+
+/// An id for the symbol of the icon at `path`, based on its filename
+fn symbol_id(path: &Path, taken: &[String]) -> String {
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    let stem: String =
+        stem.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect();
+    let base = format!("icon-{}", stem);
+    let mut id = base.clone();
+    let mut i = 1;
+    while taken.contains(&id) {
+        i += 1;
+        id = format!("{}-{}", base, i);
+    }
+    id
+}
+
+/// Replaces the `<img>` elements of the HTML pointing to SVG files by references to symbols of a
+/// sprite added at the start of the `<body>`. `resolve` gives the file of the `src` of an image,
+/// images it doesn't know about are left as is. Each file is only added once to the sprite.
+pub fn inline_svg_icons(html: &str, resolve: impl Fn(&str) -> Option<PathBuf>) -> Result<String> {
+    // The id of the symbol of each file, and the symbols in order
+    let mut ids: HashMap<PathBuf, String> = HashMap::new();
+    let mut symbols = Vec::new();
+    let mut taken = Vec::new();
+    let mut out = String::with_capacity(html.len());
+    let mut last = 0;
+
+    for img in IMG_RE.find_iter(html) {
+        let attrs = attributes(img.as_str());
+        let src = match attrs.get("src") {
+            Some(src) => src,
+            None => continue,
+        };
+        let path = src.split(&['?', '#'][..]).next().unwrap_or_default();
+        if !path.to_lowercase().ends_with(".svg") {
+            continue;
+        }
+        let file = match resolve(path) {
+            Some(file) => file,
+            None => continue,
+        };
+
+        let id = match ids.get(&file) {
+            Some(id) => id.clone(),
+            None => {
+                let svg = read_to_string(&file).map_err(|e| {
+                    Error::chain(format!("Failed to read SVG icon {}", file.display()), e)
+                })?;
+                let id = symbol_id(&file, &taken);
+                match make_symbol(&svg, &id) {
+                    Some(symbol) => symbols.push(symbol),
+                    None => continue,
+                }
+                taken.push(id.clone());
+                ids.insert(file, id.clone());
+                id
+            }
+        };
+
+        let mut svg = String::from("<svg");
+        for name in &["class", "width", "height"] {
+            if let Some(value) = attrs.get(*name) {
+                svg.push_str(&format!(" {}=\"{}\"", name, value));
+            }
+        }
+        match attrs.get("alt") {
+            Some(alt) if !alt.is_empty() => {
+                svg.push_str(&format!(" role=\"img\" aria-label=\"{}\"", alt))
+            }
+            _ => svg.push_str(" aria-hidden=\"true\""),
+        }
+        svg.push_str(&format!("><use href=\"#{}\"></use></svg>", id));
+
+        out.push_str(&html[last..img.start()]);
+        out.push_str(&svg);
+        last = img.end();
+    }
+
+    if symbols.is_empty() {
+        return Ok(html.to_string());
+    }
+    out.push_str(&html[last..]);
+
+    let sprite = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" style=\"display: none\">\n{}</svg>\n",
+        symbols.join("")
+    );
+    let insert_at = BODY_RE.find(&out).map(|m| m.end()).unwrap_or(0);
+    out.insert_str(insert_at, &sprite);
+    Ok(out)
+}
+
+/// Finds the file in the output directory an `<img>` of the page at `page_path` points to.
+/// Images hosted elsewhere give `None`.
+fn resolve_src(site: &Site, page_path: &Path, src: &str) -> Option<PathBuf> {
+    local_src_path(site, page_path, src).filter(|file| file.is_file())
+}
+
+/// The path in the output directory a `src` of the page at `page_path` points to, whether it
+/// exists or not. External URLs, including `data:` ones, give `None`.
+pub(crate) fn local_src_path(site: &Site, page_path: &Path, src: &str) -> Option<PathBuf> {
+    let base_url = site.config.base_url.trim_end_matches('/');
+    let local = if !base_url.is_empty() && src.starts_with(base_url) {
+        &src[base_url.len()..]
+    } else {
+        src
+    };
+    if local.starts_with("//") || local.split('/').next().unwrap_or_default().contains(':') {
+        return None;
+    }
+    let local = local.split(&['?', '#'][..]).next().unwrap_or_default();
+    let local = percent_decode_str(local).decode_utf8_lossy();
+
+    match local.strip_prefix('/') {
+        Some(rest) => Some(site.output_path.join(rest)),
+        None => Some(page_path.parent()?.join(&*local)),
+    }
+}
+
+/// Inlines the SVG icons of all the HTML files of the output directory.
+pub fn inline_svg_icon_files(site: &Site) -> Result<()> {
+    for entry in WalkDir::new(&site.output_path).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension().map(|e| e != "html").unwrap_or(true) {
+            continue;
+        }
+        let html = read_to_string(path)
+            .map_err(|e| Error::chain(format!("Failed to read {}", path.display()), e))?;
+        let inlined = inline_svg_icons(&html, |src| resolve_src(site, path, src))?;
+        if inlined != html {
+            std::fs::write(path, inlined)
+                .map_err(|e| Error::chain(format!("Failed to write {}", path.display()), e))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use tempfile::tempdir;
+
+    use super::inline_svg_icons;
+
+    #[test]
+    fn can_inline_repeated_icons_once() {
+        let dir = tempdir().unwrap();
+        write(
+            dir.path().join("star.svg"),
+            "<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 24 24\"><path d=\"M12 2z\"/></svg>\n",
+        )
+        .unwrap();
+        write(dir.path().join("dot.svg"), "<svg><circle r=\"1\"/></svg>").unwrap();
+
+        let html = r#"<html><body class="page"><img src="/star.svg" alt="Star" class="icon">
+<p><img src="/star.svg?v=2" alt=""><img src="/dot.svg"><img src="/photo.png"></p></body></html>"#;
+        let res = inline_svg_icons(html, |src| Some(dir.path().join(src.trim_start_matches('/'))))
+            .unwrap();
+        assert_eq!(
+            res,
+            r##"<html><body class="page"><svg xmlns="http://www.w3.org/2000/svg" style="display: none">
+<symbol id="icon-star" viewBox="0 0 24 24"><path d="M12 2z"/></symbol>
+<symbol id="icon-dot"><circle r="1"/></symbol>
+</svg>
+<svg class="icon" role="img" aria-label="Star"><use href="#icon-star"></use></svg>
+<p><svg aria-hidden="true"><use href="#icon-star"></use></svg><svg aria-hidden="true"><use href="#icon-dot"></use></svg><img src="/photo.png"></p></body></html>"##
+        );
+        assert_eq!(res.matches("<symbol").count(), 2);
+    }
+
+    #[test]
+    fn leaves_pages_without_local_icons_untouched() {
+        let html = r#"<body><img src="https://example.org/logo.svg"><img src="/a.png"></body>"#;
+        assert_eq!(inline_svg_icons(html, |_| None).unwrap(), html);
+    }
+}
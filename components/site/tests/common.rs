@@ -71,6 +71,24 @@ where
     (site, tmp_dir, public.clone())
 }
 
+/// Builds a site made of the given `(path, content)` files, written in a temporary directory
+pub fn build_site_from_files(files: &[(&str, &str)]) -> (Site, TempDir, PathBuf) {
+    let tmp_dir = tempdir().expect("create temp dir");
+    let root = tmp_dir.path();
+    for (path, content) in files {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    let mut site = Site::new(root, root.join("config.toml")).unwrap();
+    site.load().unwrap();
+    let public = root.join("public");
+    site.set_output_path(&public);
+    site.build().expect("Couldn't build the site");
+    (site, tmp_dir, public)
+}
+
 /// Finds the unified path (eg. _index.fr.md -> _index.md) and
 /// potential language (if not default) associated with a path
 /// When the path is not a markdown file (.md), None is returned
@@ -4,8 +4,9 @@ use std::collections::HashMap;
 use std::env;
 use std::path::Path;
 
-use common::{build_site, build_site_with_setup};
-use config::Taxonomy;
+use common::{build_site, build_site_from_files, build_site_with_setup};
+use config::{AliasRedirects, Taxonomy};
+use site::image_validation::check_content_images;
 use site::sitemap;
 use site::Site;
 
@@ -210,6 +211,85 @@ fn can_build_site_without_live_reload() {
     ));
 }
 
+#[test]
+fn can_disallow_robots_with_drafts() {
+    let (_, _tmp_dir, public) = build_site_with_setup("test_site", |mut site| {
+        site.config.disallow_robots_with_drafts = true;
+        site.include_drafts();
+        (site, true)
+    });
+    assert_eq!(
+        std::fs::read_to_string(public.join("robots.txt")).unwrap(),
+        "User-agent: *\nDisallow: /\n"
+    );
+
+    // Drafts are not included so the template is used
+    let (_, _tmp_dir, public) = build_site_with_setup("test_site", |mut site| {
+        site.config.disallow_robots_with_drafts = true;
+        (site, true)
+    });
+    assert!(file_contains!(public, "robots.txt", "User-agent: zola"));
+    assert!(!file_contains!(public, "robots.txt", "Disallow"));
+}
+
+#[test]
+fn can_write_aliases_to_a_redirects_file() {
+    let (site, _tmp_dir, public) = build_site_with_setup("test_site", |mut site| {
+        site.config.alias_redirects = AliasRedirects::File;
+        (site, true)
+    });
+    let permalink = site.config.make_permalink("posts/something-else/");
+    assert!(file_contains!(
+        public,
+        "_redirects",
+        &format!("/an-old-url/old-page {} 301\n", permalink)
+    ));
+    assert!(file_contains!(
+        public,
+        "_redirects",
+        &format!("/an-old-url/an-old-alias.html {} 301\n", permalink)
+    ));
+    assert!(!file_exists!(public, "an-old-url/old-page/index.html"));
+    assert!(!file_exists!(public, "an-old-url/an-old-alias.html"));
+}
+
+#[test]
+fn can_build_json_search_index() {
+    let index_dir = tempfile::tempdir().unwrap();
+    let index_path = index_dir.path().join("search").join("index.json");
+    let (site, _tmp_dir, _public) = build_site_with_setup("test_site", |mut site| {
+        let lang = site.config.default_language.clone();
+        site.config.languages.get_mut(&lang).unwrap().search.include_code_blocks = false;
+        site.set_search_index_json(&index_path);
+        (site, true)
+    });
+
+    let index: Vec<serde_json::Value> =
+        serde_json::from_str(&std::fs::read_to_string(&index_path).unwrap()).unwrap();
+    let library = site.library.read().unwrap();
+    let indexed = library.pages_values().iter().filter(|p| p.meta.in_search_index).count();
+    assert_eq!(index.len(), indexed);
+    for entry in &index {
+        let fields: Vec<&String> = entry.as_object().unwrap().keys().collect();
+        assert_eq!(fields, vec!["title", "url", "body_plaintext", "headings"]);
+    }
+
+    let python = index
+        .iter()
+        .find(|e| e["url"] == "https://replace-this-with-your-url.com/posts/python/")
+        .unwrap();
+    assert_eq!(python["title"], "Python in posts");
+    assert!(python["body_plaintext"]
+        .as_str()
+        .unwrap()
+        .starts_with("Same filename but different path"));
+    assert!(!python["body_plaintext"].as_str().unwrap().contains('<'));
+
+    // Code blocks are not included
+    let syntax = index.iter().find(|e| e["title"] == "Extra Syntax").unwrap();
+    assert!(!syntax["body_plaintext"].as_str().unwrap().contains("for (int i"));
+}
+
 #[test]
 fn can_build_site_with_live_reload_and_drafts() {
     let (site, _tmp_dir, public) = build_site_with_setup("test_site", |mut site| {
@@ -738,6 +818,43 @@ fn can_build_site_with_html_minified() {
     ));
 }
 
+#[test]
+fn can_validate_generated_html() {
+    let tmp_dir = tempfile::tempdir().expect("create temp dir");
+    let path = tmp_dir.path();
+    let files = [
+        ("config.toml", "base_url = \"https://example.com\"\n"),
+        ("templates/index.html", "<html><body>{{ section.content | safe }}</body></html>"),
+        ("templates/page.html", "<html><body>{{ page.content | safe }}</body></html>"),
+        ("templates/shortcodes/note.html", "<div class=\"note\">{{ body }}</div>"),
+        ("content/_index.md", "+++\n+++\nA valid paragraph"),
+        (
+            "content/page.md",
+            "+++\ntitle = \"Page\"\n+++\nSome {% note() %}inline note{% end %} text",
+        ),
+    ];
+    for (file, content) in &files {
+        let file_path = path.join(file);
+        std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        std::fs::write(file_path, content).unwrap();
+    }
+
+    let mut site = Site::new(path, path.join("config.toml")).unwrap();
+    site.load().unwrap();
+    site.set_output_path(path.join("public"));
+    site.enable_html_validation();
+
+    let err = site.build().expect_err("The malformed page should be flagged");
+    // Only the page using the shortcode inline is malformed, not the index
+    assert_eq!(
+        format!("{}", err),
+        format!(
+            "{}:1: `<div>` is not allowed inside `<p>`",
+            Path::new("page").join("index.html").display()
+        )
+    );
+}
+
 #[test]
 fn can_ignore_markdown_content() {
     let (_, _tmp_dir, public) = build_site("test_site");
@@ -784,3 +901,145 @@ fn check_site() {
 fn current_path(path: &str) -> String {
     format!("[current_path]({})", path)
 }
+
+#[test]
+fn can_list_routes_of_a_small_site() {
+    let (site, _tmp_dir, public) = build_site_from_files(&[
+        (
+            "config.toml",
+            "base_url = \"https://example.com\"\ntaxonomies = [{ name = \"tags\" }]\n",
+        ),
+        ("content/_index.md", "+++\n+++\n"),
+        ("content/blog/_index.md", "+++\ntitle = \"Blog\"\n+++\n"),
+        (
+            "content/blog/hello.md",
+            "+++\ntitle = \"Hello\"\naliases = [\"/old-hello/\"]\n[taxonomies]\ntags = [\"Rust\"]\n+++\nHello",
+        ),
+        ("content/about.md", "+++\ntitle = \"About\"\n+++\nAbout"),
+        ("templates/index.html", "index"),
+        ("templates/section.html", "{{ section.title }}"),
+        ("templates/page.html", "{{ page.title }}"),
+        ("templates/tags/list.html", "tags"),
+        ("templates/tags/single.html", "{{ term.name }}"),
+    ]);
+
+    let routes = site.routes();
+    assert_eq!(
+        routes,
+        vec![
+            "https://example.com/",
+            "https://example.com/about/",
+            "https://example.com/blog/",
+            "https://example.com/blog/hello/",
+            "https://example.com/tags/",
+            "https://example.com/tags/rust/",
+        ]
+    );
+
+    // Every rendered page is listed, the alias is only a redirect
+    let mut built: Vec<String> = walkdir::WalkDir::new(&public)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() == "index.html")
+        .map(|e| e.path().parent().unwrap().strip_prefix(&public).unwrap().to_path_buf())
+        .filter(|p| p != Path::new("old-hello"))
+        .map(|p| match p.to_str().unwrap() {
+            "" => "https://example.com/".to_string(),
+            p => format!("https://example.com/{}/", p.replace('\\', "/")),
+        })
+        .collect();
+    built.sort();
+    assert_eq!(routes, built);
+}
+
+#[test]
+fn can_split_long_pages_into_several_ones() {
+    let (site, _tmp_dir, public) = build_site_from_files(&[
+        ("config.toml", "base_url = \"https://example.com\"\nsplit_pages_longer_than = 60\n"),
+        ("content/_index.md", "+++\n+++\n"),
+        (
+            "content/long.md",
+            "+++\ntitle = \"Long\"\n+++\n# One\n\nThe first part.\n\n# Two\n\nThe second part.\n\n# Three\n\nThe third part.\n",
+        ),
+        ("templates/index.html", "index"),
+        (
+            "templates/page.html",
+            "{{ page.content | safe }}{% if page_split %}prev={{ page_split.previous | default(value='-') | safe }} \
+             next={{ page_split.next | default(value='-') | safe }}{% endif %}",
+        ),
+    ]);
+
+    assert!(file_contains!(public, "long/index.html", "The first part."));
+    assert!(file_contains!(
+        public,
+        "long/page/2/index.html",
+        "prev=https://example.com/long/ next=https://example.com/long/page/3/"
+    ));
+    assert!(file_contains!(public, "long/page/2/index.html", "The second part."));
+    assert!(file_contains!(public, "long/page/3/index.html", "next=-"));
+
+    // The parts are listed in the sitemap and the routes
+    assert!(file_contains!(public, "sitemap.xml", "<loc>https://example.com/long/page/2/</loc>"));
+    assert!(file_contains!(public, "sitemap.xml", "<loc>https://example.com/long/page/3/</loc>"));
+    let routes = site.routes();
+    assert!(routes.contains(&"https://example.com/long/page/2/".to_string()));
+    assert!(routes.contains(&"https://example.com/long/page/3/".to_string()));
+}
+
+#[test]
+fn can_split_long_pages_with_heading_sections() {
+    let (_, _tmp_dir, public) = build_site_from_files(&[
+        (
+            "config.toml",
+            "base_url = \"https://example.com\"\nsplit_pages_longer_than = 60\n[markdown]\nheading_sections = true\n",
+        ),
+        ("content/_index.md", "+++\n+++\n"),
+        (
+            "content/long.md",
+            "+++\ntitle = \"Long\"\n+++\n# One\n\nThe first part.\n\n# Two\n\nThe second part.\n",
+        ),
+        ("templates/index.html", "index"),
+        ("templates/page.html", "{{ page.content | safe }}"),
+    ]);
+
+    assert!(file_contains!(
+        public,
+        "long/index.html",
+        "<section role=\"region\" aria-labelledby=\"one\">\n<h1 id=\"one\">One</h1>\n<p>The first part.</p>\n</section>\n"
+    ));
+    assert!(!file_contains!(public, "long/index.html", "The second part."));
+    assert!(file_contains!(
+        public,
+        "long/page/2/index.html",
+        "<section role=\"region\" aria-labelledby=\"two\">\n<h1 id=\"two\">Two</h1>\n<p>The second part.</p>\n</section>\n"
+    ));
+}
+
+#[test]
+fn can_check_images_of_the_content() {
+    let (site, _tmp_dir, _public) = build_site_from_files(&[
+        ("config.toml", "base_url = \"https://example.com\"\n"),
+        ("content/_index.md", "+++\n+++\n![missing](/missing.png)"),
+        (
+            "content/blog/post/index.md",
+            "+++\ntitle = \"Post\"\n+++\n![static](/logo.png) ![asset](photo.png) ![nested](img/a.png) \
+             ![remote](https://example.org/x.png) ![gone](gone.png)",
+        ),
+        ("content/blog/post/photo.png", ""),
+        ("content/blog/post/img/a.png", ""),
+        ("static/logo.png", ""),
+        ("templates/index.html", "index"),
+        ("templates/page.html", "{{ page.content | safe }}"),
+    ]);
+
+    let err = check_content_images(&site).unwrap_err().to_string();
+    let mut errors: Vec<_> = err.lines().collect();
+    errors.sort_unstable();
+    assert_eq!(
+        errors,
+        vec![
+            "content/_index.md: image `/missing.png` not found",
+            "content/blog/post/index.md: image `gone.png` not found",
+        ]
+    );
+}
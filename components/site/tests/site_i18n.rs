@@ -198,3 +198,55 @@ fn correct_translations_on_all_pages() {
         assert!(ensure_translations_in_output(&site, path, &link));
     }
 }
+
+#[test]
+fn can_inject_hreflang_links() {
+    let (_, _tmp_dir, public) = build_site_with_setup("test_site_i18n", |mut site| {
+        site.config.inject_hreflang_links = true;
+        site.tera
+            .add_raw_template(
+                "page.html",
+                "<html><head></head><body>{{ page.title }}</body></html>",
+            )
+            .unwrap();
+        (site, true)
+    });
+
+    let links = r#"<link rel="alternate" hreflang="en" href="https://example.com/blog/something/">
+<link rel="alternate" hreflang="fr" href="https://example.com/fr/blog/something/">
+<link rel="alternate" hreflang="x-default" href="https://example.com/blog/something/">
+</head>"#;
+    assert!(file_contains!(public, "blog/something/index.html", links));
+    assert!(file_contains!(public, "fr/blog/something/index.html", links));
+    // Pages without translations don't get any
+    assert!(!file_contains!(public, "blog/not-translated/index.html", "hreflang"));
+}
+
+#[test]
+fn can_inject_hreflang_links_in_paginated_sections() {
+    let section = "+++\ntitle = \"Blog\"\npaginate_by = 1\n+++\n";
+    let page = "+++\ntitle = \"Hello\"\n+++\nHello";
+    let (_, _tmp_dir, public) = build_site_from_files(&[
+        (
+            "config.toml",
+            "base_url = \"https://example.com\"\ninject_hreflang_links = true\n[languages.fr]\n",
+        ),
+        ("content/_index.md", "+++\n+++\n"),
+        ("content/blog/_index.md", section),
+        ("content/blog/_index.fr.md", section),
+        ("content/blog/hello.md", page),
+        ("content/blog/world.md", page),
+        ("content/blog/hello.fr.md", page),
+        ("templates/index.html", "index"),
+        ("templates/section.html", "<html><head></head><body>{{ section.title }}</body></html>"),
+        ("templates/page.html", "{{ page.title }}"),
+    ]);
+
+    let links = r#"<link rel="alternate" hreflang="en" href="https://example.com/blog/">
+<link rel="alternate" hreflang="fr" href="https://example.com/fr/blog/">
+<link rel="alternate" hreflang="x-default" href="https://example.com/blog/">
+</head>"#;
+    assert!(file_contains!(public, "blog/index.html", links));
+    assert!(file_contains!(public, "blog/page/2/index.html", links));
+    assert!(file_contains!(public, "fr/blog/index.html", links));
+}
@@ -40,7 +40,8 @@ impl TeraFilter for MarkdownFilter {
         let mut context = RenderContext::from_config(&self.config);
         context.permalinks = Cow::Borrowed(&self.permalinks);
         context.tera = Cow::Borrowed(&self.tera);
-        let def = utils::templates::get_shortcodes(&self.tera);
+        let mut def = utils::templates::get_shortcodes(&self.tera);
+        self.config.markdown.apply_shortcode_config(&mut def);
         context.set_shortcode_definitions(&def);
 
         let s = try_get_value!("markdown", "value", String, value);
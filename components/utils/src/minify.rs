@@ -1,15 +1,50 @@
 use errors::{bail, Result};
 use minify_html::{minify, Cfg};
+use regex::{Captures, Regex};
 
 pub fn html(html: String) -> Result<String> {
+    html_preserving(html, &[], false)
+}
+
+/// Same as `html` but the `preserved` strings, eg placeholders, are left as written along with
+/// the whitespace around them, and the comments are kept if `keep_comments` is set
+pub fn html_preserving(html: String, preserved: &[&str], keep_comments: bool) -> Result<String> {
+    let preserved: Vec<_> = preserved
+        .iter()
+        .filter(|p| !p.is_empty() && html.contains(*p))
+        .map(|p| regex::escape(p))
+        .collect();
+    // The preserved strings are swapped for bogus comments, `<!...>`, which the minifier
+    // leaves alone, and put back afterwards
+    let mut originals = Vec::new();
+    let html = if preserved.is_empty() {
+        html
+    } else {
+        let re = Regex::new(&format!(r"\s*(?:{})\s*", preserved.join("|"))).unwrap();
+        re.replace_all(&html, |caps: &Captures| {
+            originals.push(caps[0].to_string());
+            format!("<!zola-preserved-{}>", originals.len() - 1)
+        })
+        .into_owned()
+    };
+
     let mut cfg = Cfg::spec_compliant();
     cfg.keep_html_and_head_opening_tags = true;
+    cfg.keep_comments = keep_comments;
 
     let minified = minify(html.as_bytes(), &cfg);
-    match std::str::from_utf8(&minified) {
-        Ok(result) => Ok(result.to_string()),
+    let mut result = match std::str::from_utf8(&minified) {
+        Ok(result) => result.to_string(),
         Err(err) => bail!("Failed to convert bytes to string : {}", err),
+    };
+    for (i, original) in originals.iter().enumerate() {
+        let token = format!("<!zola-preserved-{}>", i);
+        if !result.contains(&token) {
+            bail!("Failed to preserve `{}` while minifying", original.trim());
+        }
+        result = result.replacen(&token, original, 1);
     }
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -81,4 +116,29 @@ mod tests {
         let res = html(input.to_owned()).unwrap();
         assert_eq!(res, expected);
     }
+
+    #[test]
+    fn can_minify_and_preserve_placeholders() {
+        let input = r#"
+<div>
+  <p>  Before @@PLACEHOLDER@@ after  </p>
+  @@PLACEHOLDER@@
+  <a title="@@PLACEHOLDER@@" href="/">Link</a>
+</div>
+"#;
+        let expected = r#"<div><p>Before @@PLACEHOLDER@@ after</p>
+  @@PLACEHOLDER@@
+  <a title="@@PLACEHOLDER@@" href=/>Link</a></div>"#;
+        let res = html_preserving(input.to_owned(), &["@@PLACEHOLDER@@"], false).unwrap();
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn can_minify_and_keep_comments() {
+        let input = "<div>\n  <!-- shortcode: youtube -->\n  <p>Hello</p>\n</div>\n";
+        let res = html_preserving(input.to_owned(), &[], true).unwrap();
+        assert_eq!(res, "<div><!-- shortcode: youtube --><p>Hello</div>");
+        let res = html_preserving(input.to_owned(), &[], false).unwrap();
+        assert_eq!(res, "<div><p>Hello</div>");
+    }
 }
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use std::hash::BuildHasher;
 use unicode_segmentation::UnicodeSegmentation;
 
-use errors::Result;
+use errors::{bail, Result};
 
 /// Get word count and estimated reading time
 pub fn get_reading_analytics(content: &str) -> (usize, usize) {
@@ -26,38 +26,90 @@ pub struct ResolvedInternalLink {
     pub anchor: Option<String>,
 }
 
+/// A link to a markdown file, normalized to the path of that file in the `content` directory.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NormalizedLink {
+    /// Path to the .md file relative to the `content` directory, eg `blog/post.md`.
+    pub md_path: String,
+    /// Optional anchor target.
+    pub anchor: Option<String>,
+}
+
+/// Normalizes a link to a markdown file, either an internal link of the `@/posts/something.md#hey`
+/// sort or a path relative to `current_page` like `../something.md`, to the path of that file in
+/// the `content` directory. `current_page` is itself relative to the `content` directory.
+/// A link with only an anchor points to the current page.
+/// External links, absolute paths, paths going above the `content` directory and links to
+/// anything but a markdown file are rejected.
+pub fn normalize_internal_link(href: &str, current_page: &str) -> Result<NormalizedLink> {
+    let (path, anchor) = match href.split_once('#') {
+        Some((path, anchor)) => (path, Some(anchor.to_string())),
+        None => (href, None),
+    };
+    if path.is_empty() {
+        if anchor.is_none() || current_page.is_empty() {
+            bail!("Link `{}` doesn't point to any content", href);
+        }
+        return Ok(NormalizedLink { md_path: current_page.to_string(), anchor });
+    }
+    if path.split('/').next().unwrap_or_default().contains(':') {
+        bail!("Link `{}` is an external link", href);
+    }
+
+    let (relative_to, path) = match path.strip_prefix("@/") {
+        Some(path) => ("", path),
+        None if path.starts_with('/') => bail!("Link `{}` is an absolute path", href),
+        // Relative to the directory of the current page
+        None => (current_page.rsplit_once('/').map(|(dir, _)| dir).unwrap_or_default(), path),
+    };
+    // If we have slugification turned off, we might end up with some escaped characters so we
+    // need to decode them first
+    let decoded = percent_decode(path.as_bytes()).decode_utf8_lossy();
+
+    let mut components: Vec<&str> = relative_to.split('/').filter(|c| !c.is_empty()).collect();
+    for component in decoded.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                if components.pop().is_none() {
+                    bail!("Link `{}` points outside of the content directory", href);
+                }
+            }
+            _ => components.push(component),
+        }
+    }
+    if !components.last().map(|c| c.ends_with(".md")).unwrap_or(false) {
+        bail!("Link `{}` doesn't point to a markdown file", href);
+    }
+
+    Ok(NormalizedLink { md_path: components.join("/"), anchor })
+}
+
 /// Resolves an internal link (of the `@/posts/something.md#hey` sort) to its absolute link and
 /// returns the path + anchor as well
 pub fn resolve_internal_link<S: BuildHasher>(
     link: &str,
     permalinks: &HashMap<String, String, S>,
 ) -> Result<ResolvedInternalLink> {
-    // First we remove the ./ since that's zola specific
-    let clean_link = link.replacen("@/", "", 1);
-    // Then we remove any potential anchor
-    // parts[0] will be the file path and parts[1] the anchor if present
-    let parts = clean_link.split('#').collect::<Vec<_>>();
-    // If we have slugification turned off, we might end up with some escaped characters so we need
-    // to decode them first
-    let decoded = percent_decode(parts[0].as_bytes()).decode_utf8_lossy().to_string();
-    let target =
-        permalinks.get(&decoded).ok_or_else(|| format!("Relative link {} not found.", link))?;
-    if parts.len() > 1 {
-        Ok(ResolvedInternalLink {
-            permalink: format!("{}#{}", target, parts[1]),
-            md_path: decoded,
-            anchor: Some(parts[1].to_string()),
-        })
-    } else {
-        Ok(ResolvedInternalLink { permalink: target.to_string(), md_path: decoded, anchor: None })
-    }
+    let normalized = normalize_internal_link(link, "")
+        .map_err(|_| format!("Relative link {} not found.", link))?;
+    let target = permalinks
+        .get(&normalized.md_path)
+        .ok_or_else(|| format!("Relative link {} not found.", link))?;
+    let permalink = match normalized.anchor {
+        Some(ref anchor) => format!("{}#{}", target, anchor),
+        None => target.to_string(),
+    };
+    Ok(ResolvedInternalLink { permalink, md_path: normalized.md_path, anchor: normalized.anchor })
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
 
-    use super::{get_reading_analytics, resolve_internal_link};
+    use super::{
+        get_reading_analytics, normalize_internal_link, resolve_internal_link, NormalizedLink,
+    };
 
     #[test]
     fn can_resolve_valid_internal_link() {
@@ -104,6 +156,46 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[test]
+    fn can_normalize_internal_links() {
+        assert_eq!(
+            normalize_internal_link("@/blog/post.md#sec", "pages/about.md").unwrap(),
+            NormalizedLink { md_path: "blog/post.md".to_string(), anchor: Some("sec".to_string()) }
+        );
+        assert_eq!(
+            normalize_internal_link("@/pages/about%20space.md", "").unwrap(),
+            NormalizedLink { md_path: "pages/about space.md".to_string(), anchor: None }
+        );
+    }
+
+    #[test]
+    fn can_normalize_relative_links() {
+        assert_eq!(
+            normalize_internal_link("../x.md", "blog/2021/post.md").unwrap(),
+            NormalizedLink { md_path: "blog/x.md".to_string(), anchor: None }
+        );
+        assert_eq!(
+            normalize_internal_link("./_index.md#top", "blog/post.md").unwrap(),
+            NormalizedLink {
+                md_path: "blog/_index.md".to_string(),
+                anchor: Some("top".to_string())
+            }
+        );
+        assert_eq!(
+            normalize_internal_link("#sec", "blog/post.md").unwrap(),
+            NormalizedLink { md_path: "blog/post.md".to_string(), anchor: Some("sec".to_string()) }
+        );
+    }
+
+    #[test]
+    fn errors_normalizing_invalid_links() {
+        for link in
+            ["../../x.md", "https://example.com/x.md", "/blog/x.md", "@/blog/image.png", "@/", ""]
+        {
+            assert!(normalize_internal_link(link, "blog/post.md").is_err(), "{}", link);
+        }
+    }
+
     #[test]
     fn reading_analytics_empty_text() {
         let (word_count, reading_time) = get_reading_analytics("  ");
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
-use tera::{Context, Tera};
+use serde::{Deserialize, Serialize};
+use tera::{Context, Tera, Value};
 
 use errors::{bail, Result};
 
@@ -21,16 +22,105 @@ pub enum ShortcodeFileType {
     Html,
 }
 
+/// The type an argument of a shortcode should have, checked when the shortcode is found
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShortcodeArgType {
+    String,
+    Number,
+    Boolean,
+    Array,
+}
+
+impl ShortcodeArgType {
+    /// The type of the value, if it's one of the types arguments can have
+    pub fn of(value: &Value) -> Option<ShortcodeArgType> {
+        match value {
+            Value::String(_) => Some(ShortcodeArgType::String),
+            Value::Number(_) => Some(ShortcodeArgType::Number),
+            Value::Bool(_) => Some(ShortcodeArgType::Boolean),
+            Value::Array(_) => Some(ShortcodeArgType::Array),
+            _ => None,
+        }
+    }
+
+    /// The name of the type for error messages, eg `a boolean`
+    pub fn description(self) -> &'static str {
+        match self {
+            ShortcodeArgType::String => "a string",
+            ShortcodeArgType::Number => "a number",
+            ShortcodeArgType::Boolean => "a boolean",
+            ShortcodeArgType::Array => "an array",
+        }
+    }
+}
+
+/// How an argument of a shortcode is checked: the type it should have and whether it has to be
+/// given. In the config, either a type like `"string"` for an optional argument or a table like
+/// `{ type = "string", required = true }`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "RawShortcodeArg")]
+pub struct ShortcodeArg {
+    #[serde(rename = "type")]
+    pub arg_type: ShortcodeArgType,
+    pub required: bool,
+}
+
+impl ShortcodeArg {
+    pub fn optional(arg_type: ShortcodeArgType) -> Self {
+        ShortcodeArg { arg_type, required: false }
+    }
+
+    pub fn required(arg_type: ShortcodeArgType) -> Self {
+        ShortcodeArg { arg_type, required: true }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawShortcodeArg {
+    Type(ShortcodeArgType),
+    Table {
+        #[serde(rename = "type")]
+        arg_type: ShortcodeArgType,
+        #[serde(default)]
+        required: bool,
+    },
+}
+
+impl From<RawShortcodeArg> for ShortcodeArg {
+    fn from(raw: RawShortcodeArg) -> Self {
+        match raw {
+            RawShortcodeArg::Type(arg_type) => ShortcodeArg::optional(arg_type),
+            RawShortcodeArg::Table { arg_type, required } => ShortcodeArg { arg_type, required },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ShortcodeDefinition {
     pub file_type: ShortcodeFileType,
     pub tera_name: String,
+    /// Whether HTML entities like `&amp;` in the string arguments are decoded before being
+    /// passed to the template. They are kept as written by default.
+    pub decode_entities: bool,
+    /// Whether the output of this markdown shortcode is inserted as HTML, like the one of HTML
+    /// shortcodes, instead of being rendered as markdown along the content.
+    pub html_output: bool,
+    /// The types some of the arguments should have and whether they are required, by name
+    pub arg_types: HashMap<String, ShortcodeArg>,
 }
 impl ShortcodeDefinition {
     pub fn new(file_type: ShortcodeFileType, tera_name: &str) -> ShortcodeDefinition {
         let tera_name = tera_name.to_string();
 
-        ShortcodeDefinition { file_type, tera_name }
+        ShortcodeDefinition {
+            file_type,
+            tera_name,
+            decode_entities: false,
+            html_output: false,
+            arg_types: HashMap::new(),
+        }
     }
 }
 
@@ -67,6 +157,45 @@ pub fn get_shortcodes(tera: &Tera) -> HashMap<String, ShortcodeDefinition> {
     shortcode_definitions
 }
 
+/// Marks the shortcodes with the given names as wanting the HTML entities of their string
+/// arguments decoded
+pub fn set_shortcodes_decoding_entities(
+    definitions: &mut HashMap<String, ShortcodeDefinition>,
+    names: &[String],
+) {
+    for name in names {
+        if let Some(def) = definitions.get_mut(name) {
+            def.decode_entities = true;
+        }
+    }
+}
+
+/// Marks the markdown shortcodes with the given names as outputting HTML that shouldn't be
+/// rendered as markdown
+pub fn set_shortcodes_with_html_output(
+    definitions: &mut HashMap<String, ShortcodeDefinition>,
+    names: &[String],
+) {
+    for name in names {
+        if let Some(def) = definitions.get_mut(name) {
+            def.html_output = true;
+        }
+    }
+}
+
+/// Sets the types the arguments of the shortcodes should have and whether they are required,
+/// by shortcode name
+pub fn set_shortcode_arg_types(
+    definitions: &mut HashMap<String, ShortcodeDefinition>,
+    arg_types: &HashMap<String, HashMap<String, ShortcodeArg>>,
+) {
+    for (name, types) in arg_types {
+        if let Some(def) = definitions.get_mut(name) {
+            def.arg_types = types.clone();
+        }
+    }
+}
+
 /// Renders the given template with the given context, but also ensures that, if the default file
 /// is not found, it will look up for the equivalent template for the current theme if there is one.
 /// Lastly, if it's a default template (index, section or page), it will just return an empty string
@@ -51,6 +51,18 @@ pub fn build_cli() -> App<'static, 'static> {
                         .long("drafts")
                         .takes_value(false)
                         .help("Include drafts when loading the site"),
+                    Arg::with_name("validate_html")
+                        .long("validate-html")
+                        .takes_value(false)
+                        .help("Check that the generated HTML is well-formed (unclosed tags, blocks inside inline elements...)"),
+                    Arg::with_name("validate_images")
+                        .long("validate-images")
+                        .takes_value(false)
+                        .help("Check that the src of every local image of the generated HTML points to an existing file"),
+                    Arg::with_name("search_index")
+                        .long("search-index")
+                        .takes_value(true)
+                        .help("Writes a JSON index of the pages (title, url, plain text and headings) to the given file, for client-side search"),
                 ]),
             SubCommand::with_name("serve")
                 .about("Serve the site. Rebuild and reload on change automatically")
@@ -89,6 +101,16 @@ pub fn build_cli() -> App<'static, 'static> {
                         .long("fast")
                         .takes_value(false)
                         .help("Only rebuild the minimum on change - useful when working on a specific page/section"),
+                    Arg::with_name("proxy")
+                        .long("proxy")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Forward the requests whose path starts with a prefix to another server, eg `/api=http://127.0.0.1:8000`. Can be used several times"),
+                    Arg::with_name("print_routes")
+                        .long("print-routes")
+                        .takes_value(false)
+                        .help("Print the URL of every page, section and taxonomy of the site on startup"),
                 ]),
             SubCommand::with_name("check")
                 .about("Try building the project without rendering it. Checks links")
@@ -97,6 +119,10 @@ pub fn build_cli() -> App<'static, 'static> {
                         .long("drafts")
                         .takes_value(false)
                         .help("Include drafts when loading the site"),
+                    Arg::with_name("validate_images")
+                        .long("validate-images")
+                        .takes_value(false)
+                        .help("Check that the src of every local image of the content points to a file of the static directories or an asset next to the content"),
                 ])
         ])
 }
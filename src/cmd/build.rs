@@ -8,12 +8,16 @@ use crate::prompt::ask_bool_timeout;
 
 const BUILD_PROMPT_TIMEOUT_MILLIS: u64 = 10_000;
 
+#[allow(clippy::too_many_arguments)]
 pub fn build(
     root_dir: &Path,
     config_file: &Path,
     base_url: Option<&str>,
     output_dir: Option<&Path>,
     include_drafts: bool,
+    validate_html: bool,
+    validate_images: bool,
+    search_index: Option<&Path>,
 ) -> Result<()> {
     let mut site = Site::new(root_dir, config_file)?;
     if let Some(output_dir) = output_dir {
@@ -46,6 +50,15 @@ pub fn build(
     if include_drafts {
         site.include_drafts();
     }
+    if validate_html {
+        site.enable_html_validation();
+    }
+    if validate_images {
+        site.enable_image_validation();
+    }
+    if let Some(path) = search_index {
+        site.set_search_index_json(path);
+    }
     site.load()?;
     console::notify_site_size(&site);
     console::warn_about_ignored_pages(&site);
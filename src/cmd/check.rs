@@ -1,6 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use errors::Result;
+use site::image_validation::check_content_images;
 use site::Site;
 
 use crate::console;
@@ -11,6 +12,7 @@ pub fn check(
     base_path: Option<&str>,
     base_url: Option<&str>,
     include_drafts: bool,
+    validate_images: bool,
 ) -> Result<()> {
     let bp = base_path.map(PathBuf::from).unwrap_or_else(|| PathBuf::from(root_dir));
     let mut site = Site::new(bp, config_file)?;
@@ -25,5 +27,8 @@ pub fn check(
     site.load()?;
     console::check_site_summary(&site);
     console::warn_about_ignored_pages(&site);
+    if validate_images {
+        check_content_images(&site)?;
+    }
     Ok(())
 }
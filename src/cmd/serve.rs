@@ -25,13 +25,14 @@ use std::fs::{read_dir, remove_dir_all};
 use std::net::{SocketAddrV4, TcpListener};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::channel;
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
 use hyper::header;
 use hyper::server::Server;
 use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Method, Request, Response, StatusCode};
+use hyper::{Body, Client, Method, Request, Response, StatusCode, Uri};
 use mime_guess::from_path as mimetype_from_path;
 
 use chrono::prelude::*;
@@ -68,11 +69,89 @@ enum WatchMode {
 
 static METHOD_NOT_ALLOWED_TEXT: &[u8] = b"Method Not Allowed";
 static NOT_FOUND_TEXT: &[u8] = b"Not Found";
+static BAD_GATEWAY_TEXT: &[u8] = b"Bad Gateway";
 
 // This is dist/livereload.min.js from the LiveReload.js v3.2.4 release
 const LIVE_RELOAD: &str = include_str!("livereload.js");
 
-async fn handle_request(req: Request<Body>, mut root: PathBuf) -> Result<Response<Body>> {
+/// A `--proxy` rule: requests whose path starts with `prefix` are forwarded to `target`
+#[derive(Debug, Clone, PartialEq)]
+struct ProxyRule {
+    prefix: String,
+    target: String,
+}
+
+impl ProxyRule {
+    /// Parses a rule written as `prefix=url`, eg `/api=http://127.0.0.1:8000`
+    fn parse(rule: &str) -> Result<ProxyRule> {
+        let (prefix, target) = match rule.split_once('=') {
+            Some((prefix, target)) if !prefix.is_empty() && !target.is_empty() => (prefix, target),
+            _ => {
+                return Err(format!(
+                    "Invalid proxy `{}`, it should be written as `prefix=url`, eg `/api=http://127.0.0.1:8000`.",
+                    rule
+                )
+                .into())
+            }
+        };
+
+        match target.parse::<Uri>() {
+            Ok(uri) if uri.scheme_str() == Some("http") && uri.host().is_some() => (),
+            _ => {
+                return Err(format!(
+                    "Invalid proxy target `{}`: only `http://` URLs are supported.",
+                    target
+                )
+                .into())
+            }
+        }
+
+        let prefix =
+            if prefix.starts_with('/') { prefix.to_string() } else { format!("/{}", prefix) };
+        Ok(ProxyRule { prefix, target: target.trim_end_matches('/').to_string() })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        if self.prefix.ends_with('/') {
+            return path.starts_with(&self.prefix);
+        }
+        path == self.prefix
+            || (path.starts_with(&self.prefix) && path[self.prefix.len()..].starts_with('/'))
+    }
+}
+
+/// Forwards the request as is to the target of the proxy rule, keeping the method, headers
+/// and body, and returns the response of that server
+async fn proxy_request(req: Request<Body>, rule: &ProxyRule) -> Response<Body> {
+    let path_and_query = req.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let uri = match format!("{}{}", rule.target, path_and_query).parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(_) => return bad_gateway(),
+    };
+
+    let (mut parts, body) = req.into_parts();
+    parts.uri = uri;
+    // Hyper will set the host of the target for us
+    parts.headers.remove(header::HOST);
+
+    match Client::new().request(Request::from_parts(parts, body)).await {
+        Ok(res) => res,
+        Err(e) => {
+            console::error(&format!("Failed to proxy a request to {}: {}", rule.target, e));
+            bad_gateway()
+        }
+    }
+}
+
+async fn handle_request(
+    req: Request<Body>,
+    mut root: PathBuf,
+    proxies: Arc<Vec<ProxyRule>>,
+) -> Result<Response<Body>> {
+    if let Some(rule) = proxies.iter().find(|rule| rule.matches(req.uri().path())) {
+        return Ok(proxy_request(req, rule).await);
+    }
+
     let original_root = root.clone();
     let mut path = RelativePathBuf::new();
     // https://zola.discourse.group/t/percent-encoding-for-slugs/736
@@ -177,6 +256,14 @@ fn method_not_allowed() -> Response<Body> {
         .expect("Could not build Method Not Allowed response")
 }
 
+fn bad_gateway() -> Response<Body> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/plain")
+        .status(StatusCode::BAD_GATEWAY)
+        .body(BAD_GATEWAY_TEXT.into())
+        .expect("Could not build Bad Gateway response")
+}
+
 fn io_error(err: std::io::Error) -> Response<Body> {
     match err.kind() {
         std::io::ErrorKind::NotFound => not_found(),
@@ -273,6 +360,39 @@ fn create_new_site(
     Ok((site, address))
 }
 
+/// Recreates the site from scratch and rebuilds it, eg after the config changed.
+/// If that fails, eg because the new config is invalid, the content of the previous build is
+/// restored so the server keeps serving it.
+#[allow(clippy::too_many_arguments)]
+fn rebuild_site_from_scratch(
+    root_dir: &Path,
+    interface: &str,
+    interface_port: u16,
+    output_dir: Option<&Path>,
+    base_url: &str,
+    config_file: &Path,
+    include_drafts: bool,
+    ws_port: Option<u16>,
+) -> Result<Site> {
+    let previous_content = SITE_CONTENT.read().unwrap().clone();
+    match create_new_site(
+        root_dir,
+        interface,
+        interface_port,
+        output_dir,
+        base_url,
+        config_file,
+        include_drafts,
+        ws_port,
+    ) {
+        Ok((site, _)) => Ok(site),
+        Err(e) => {
+            *SITE_CONTENT.write().unwrap() = previous_content;
+            Err(e)
+        }
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn serve(
     root_dir: &Path,
@@ -284,7 +404,11 @@ pub fn serve(
     open: bool,
     include_drafts: bool,
     fast_rebuild: bool,
+    proxies: &[&str],
+    print_routes: bool,
 ) -> Result<()> {
+    let proxies =
+        Arc::new(proxies.iter().map(|p| ProxyRule::parse(p)).collect::<Result<Vec<_>>>()?);
     let start = Instant::now();
     let (mut site, address) = create_new_site(
         root_dir,
@@ -366,10 +490,11 @@ pub fn serve(
             rt.block_on(async {
                 let make_service = make_service_fn(move |_| {
                     let static_root = static_root.clone();
+                    let proxies = proxies.clone();
 
                     async {
                         Ok::<_, hyper::Error>(service_fn(move |req| {
-                            handle_request(req, static_root.clone())
+                            handle_request(req, static_root.clone(), proxies.clone())
                         }))
                     }
                 });
@@ -419,6 +544,14 @@ pub fn serve(
         broadcaster
     };
 
+    if print_routes {
+        println!("Routes:");
+        for route in site.routes() {
+            println!("  {}", route);
+        }
+        println!();
+    }
+
     println!("Listening for changes in {}{{{}}}", root_dir.display(), watchers.join(", "));
 
     println!("Press Ctrl+C to stop\n");
@@ -480,7 +613,7 @@ pub fn serve(
         }
     };
 
-    let recreate_site = || match create_new_site(
+    let recreate_site = || match rebuild_site_from_scratch(
         root_dir,
         interface,
         interface_port,
@@ -490,12 +623,13 @@ pub fn serve(
         include_drafts,
         ws_port,
     ) {
-        Ok((s, _)) => {
+        Ok(s) => {
             rebuild_done_handling(&broadcaster, Ok(()), "/x.js");
             Some(s)
         }
         Err(e) => {
             console::unravel_errors("Failed to build the site", &e);
+            console::warn("-> Still serving the last successful build.");
             None
         }
     };
@@ -600,7 +734,10 @@ pub fn serve(
                                 }
                             }
                             (ChangeKind::Config, _) => {
-                                console::info("-> Config changed. The browser needs to be refreshed to make the changes visible.");
+                                console::info(&format!(
+                                    "-> Config file {} changed, rebuilding the whole site.",
+                                    config_file.display()
+                                ));
 
                                 if let Some(s) = recreate_site() {
                                     site = s;
@@ -687,7 +824,17 @@ fn is_folder_empty(dir: &Path) -> bool {
 mod tests {
     use std::path::{Path, PathBuf};
 
-    use super::{detect_change_kind, is_temp_file, ChangeKind};
+    use relative_path::RelativePath;
+
+    use hyper::server::Server;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, StatusCode};
+
+    use super::{
+        detect_change_kind, is_temp_file, proxy_request, rebuild_site_from_scratch, ChangeKind,
+        ProxyRule,
+    };
+    use site::SITE_CONTENT;
 
     #[test]
     fn can_recognize_temp_files() {
@@ -778,4 +925,115 @@ mod tests {
         let config_filename = Path::new("config.toml");
         assert_eq!(expected, detect_change_kind(pwd, path, config_filename));
     }
+
+    #[test]
+    fn can_parse_proxy_rules() {
+        assert_eq!(
+            ProxyRule::parse("/api=http://127.0.0.1:8000/").unwrap(),
+            ProxyRule { prefix: "/api".to_string(), target: "http://127.0.0.1:8000".to_string() }
+        );
+        assert_eq!(ProxyRule::parse("api=http://localhost:8000").unwrap().prefix, "/api");
+        assert!(ProxyRule::parse("/api").is_err());
+        assert!(ProxyRule::parse("=http://localhost:8000").is_err());
+        assert!(ProxyRule::parse("/api=https://example.com").is_err());
+        assert!(ProxyRule::parse("/api=localhost:8000").is_err());
+    }
+
+    #[test]
+    fn can_match_proxy_prefixes() {
+        let rule = ProxyRule::parse("/api=http://127.0.0.1:8000").unwrap();
+        assert!(rule.matches("/api"));
+        assert!(rule.matches("/api/users"));
+        assert!(!rule.matches("/apis"));
+        assert!(!rule.matches("/blog/api"));
+
+        let rule = ProxyRule::parse("/api/=http://127.0.0.1:8000").unwrap();
+        assert!(rule.matches("/api/users"));
+        assert!(!rule.matches("/api"));
+    }
+
+    #[test]
+    fn can_proxy_requests_to_a_backend() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Could not build tokio runtime");
+
+        rt.block_on(async {
+            // A stub backend sending back what it received
+            let make_service = make_service_fn(|_| async {
+                Ok::<_, hyper::Error>(service_fn(|req: Request<Body>| async move {
+                    let summary = format!(
+                        "{} {} {}",
+                        req.method(),
+                        req.uri(),
+                        req.headers().get("x-custom").and_then(|h| h.to_str().ok()).unwrap_or("")
+                    );
+                    let body = hyper::body::to_bytes(req.into_body()).await?;
+                    let res = Response::builder()
+                        .status(StatusCode::CREATED)
+                        .header("x-backend", summary)
+                        .body(Body::from(body))
+                        .unwrap();
+                    Ok::<_, hyper::Error>(res)
+                }))
+            });
+            let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+            let rule = ProxyRule::parse(&format!("/api=http://{}", server.local_addr())).unwrap();
+            tokio::spawn(server);
+
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri("/api/users?page=2")
+                .header("x-custom", "hello")
+                .body(Body::from("some payload"))
+                .unwrap();
+            let res = proxy_request(req, &rule).await;
+            assert_eq!(res.status(), StatusCode::CREATED);
+            assert_eq!(res.headers()["x-backend"], "POST /api/users?page=2 hello");
+            let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+            assert_eq!(&body[..], b"some payload");
+        });
+    }
+
+    #[test]
+    fn rebuilds_site_on_config_change_and_keeps_last_build_on_error() {
+        let tmp_dir = tempfile::tempdir().expect("create temp dir");
+        let root = tmp_dir.path();
+        std::fs::create_dir_all(root.join("content")).unwrap();
+        std::fs::create_dir_all(root.join("templates")).unwrap();
+        std::fs::write(root.join("templates").join("index.html"), "{{ config.title }}").unwrap();
+        let config_file = root.join("config.toml");
+        let rebuild = || {
+            rebuild_site_from_scratch(
+                root,
+                "127.0.0.1",
+                1111,
+                None,
+                "127.0.0.1",
+                &config_file,
+                false,
+                Some(1024),
+            )
+        };
+        // The index has the livereload script appended to it
+        let index_title = || {
+            let content = SITE_CONTENT.read().unwrap();
+            content.get(RelativePath::new("")).and_then(|c| c.split('<').next().map(String::from))
+        };
+
+        std::fs::write(&config_file, "base_url = \"https://example.com\"\ntitle = \"Before\"")
+            .unwrap();
+        assert!(rebuild().is_ok());
+        assert_eq!(index_title(), Some("Before".to_string()));
+
+        std::fs::write(&config_file, "base_url = \"https://example.com\"\ntitle = \"After\"")
+            .unwrap();
+        assert!(rebuild().is_ok());
+        assert_eq!(index_title(), Some("After".to_string()));
+
+        std::fs::write(&config_file, "base_url = \"https://example.com\"\ntitle = ").unwrap();
+        assert!(rebuild().is_err());
+        assert_eq!(index_title(), Some("After".to_string()));
+    }
 }
@@ -46,6 +46,9 @@ fn main() {
                 matches.value_of("base_url"),
                 output_dir,
                 matches.is_present("drafts"),
+                matches.is_present("validate_html"),
+                matches.is_present("validate_images"),
+                matches.value_of("search_index").map(Path::new),
             ) {
                 Ok(()) => console::report_elapsed_time(start),
                 Err(e) => {
@@ -66,6 +69,9 @@ fn main() {
             let open = matches.is_present("open");
             let include_drafts = matches.is_present("drafts");
             let fast = matches.is_present("fast");
+            let print_routes = matches.is_present("print_routes");
+            let proxies: Vec<&str> =
+                matches.values_of("proxy").map(|values| values.collect()).unwrap_or_default();
 
             // Default one
             if port != 1111 && !port_is_available(port) {
@@ -94,6 +100,8 @@ fn main() {
                 open,
                 include_drafts,
                 fast,
+                &proxies,
+                print_routes,
             ) {
                 Ok(()) => (),
                 Err(e) => {
@@ -111,6 +119,7 @@ fn main() {
                 matches.value_of("base_path"),
                 matches.value_of("base_url"),
                 matches.is_present("drafts"),
+                matches.is_present("validate_images"),
             ) {
                 Ok(()) => console::report_elapsed_time(start),
                 Err(e) => {